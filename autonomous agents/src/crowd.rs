@@ -0,0 +1,123 @@
+//! A crowd of vehicles finding their way around a maze, each heading for its own
+//! goal while keeping out of the others' way.
+
+use crate::{
+    navigation::{ObstacleMap, Route},
+    spatial::SpatialHash,
+    vector::Vector,
+    vehicle::Vehicle,
+    GRAY, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use rand::Rng;
+
+// Maze cells are this many pixels across, and corridors this many cells wide.
+const CELL: f64 = 30.0;
+const CORRIDOR: usize = 2;
+
+// Agents keep at least this far apart, center to center.
+const SPACING: f64 = 16.0;
+
+// Room routes leave between themselves and the walls when cutting corners.
+const CLEARANCE: f64 = 8.0;
+
+pub struct Crowd {
+    pub map: ObstacleMap,
+    pub agents: Vec<Vehicle>,
+    /// Where each agent is going, by index.
+    pub routes: Vec<Route>,
+    grid: SpatialHash,
+}
+impl Crowd {
+    /// `count` agents scattered around a new maze filling the window.
+    pub fn new<R: Rng>(count: usize, rng: &mut R) -> Crowd {
+        let cols = (WINDOW_WIDTH as f64 / CELL) as usize;
+        let rows = (WINDOW_HEIGHT as f64 / CELL) as usize;
+        let mut crowd = Crowd {
+            map: ObstacleMap::maze(cols, rows, CELL, CORRIDOR, rng),
+            agents: vec![],
+            routes: vec![],
+            grid: SpatialHash::new(SPACING),
+        };
+        for _ in 0..count {
+            let pos = crowd.map.random_open(rng).unwrap_or_default();
+            let mut agent = Vehicle::new(pos.x, pos.y, [0.2, 0.8, 1.0, 1.0]);
+            agent.r = 5.0;
+            agent.max_speed = 180.0;
+            agent.max_force = 1400.0;
+            // Agents are heavy with lots of drag, so they come to a stop soon after
+            // they're no longer pushed, and can turn sharply at corners.
+            agent.mass = 1.5;
+            agent.drag = 1.0;
+            agent.max_turn_rate = 8.0;
+            crowd.agents.push(agent);
+            crowd.routes.push(Route::new(vec![pos]));
+        }
+        crowd
+    }
+
+    /// Sends agent `i` to `goal`, if there's a way there.
+    pub fn send(&mut self, i: usize, goal: Vector) {
+        if let Some(route) = self.map.route(self.agents[i].pos, goal, CLEARANCE) {
+            self.routes[i] = route;
+        }
+    }
+
+    pub fn send_all(&mut self, goal: Vector) {
+        for i in 0..self.agents.len() {
+            self.send(i, goal);
+        }
+    }
+
+    /// Moves the crowd on by `dt` seconds.
+    pub fn update<R: Rng>(&mut self, dt: f64, rng: &mut R) {
+        self.grid.rebuild(self.agents.iter().map(|a| a.pos));
+
+        for i in 0..self.agents.len() {
+            // Agents that have got where they were going pick somewhere else, and ones
+            // that have been pushed out of sight of their route find a new one.
+            let pos = self.agents[i].pos;
+            let route = &self.routes[i];
+            if route.finished() {
+                if let Some(goal) = self.map.random_open(rng) {
+                    self.send(i, goal);
+                }
+            } else if !self.map.line_of_sight(pos, route.waypoints[route.next], 0.0) {
+                if let Some(goal) = route.goal() {
+                    self.send(i, goal);
+                }
+            }
+
+            let agent = &self.agents[i];
+            let others = self.grid.query(agent.pos, SPACING);
+            let force = agent.combine_weighted(&[
+                (1.0, agent.follow_route(&mut self.routes[i])),
+                (1.5, agent.separate(others.iter().map(|&j| &self.agents[j]), SPACING)),
+                (2.0, agent.avoid_walls(&self.map)),
+            ]);
+            self.agents[i].apply_force(force);
+        }
+
+        for agent in &mut self.agents {
+            let before = agent.pos;
+            agent.update(dt);
+            if self.map.blocked_at(agent.pos) {
+                agent.pos = before;
+            }
+            agent.pos = self.map.push_out(agent.pos, agent.r);
+        }
+    }
+
+    pub fn draw(&self, routes: bool, transform: Matrix2d, gl: &mut GlGraphics) {
+        self.map.draw(GRAY, transform, gl);
+        if routes {
+            for (agent, route) in self.agents.iter().zip(&self.routes) {
+                route.draw(agent.pos, [1.0, 1.0, 0.0, 0.3], transform, gl);
+            }
+        }
+        for agent in &self.agents {
+            agent.draw(transform, gl);
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Predators and prey that spend energy to move, eat to get it back and split in
+//! two when they have plenty, passing slightly mutated genes on to their young.
+
+use crate::{
+    spatial::SpatialHash, vector::Vector, vehicle::Vehicle, GRAY, GREEN, LIGHT_GREEN, RED,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use rand::Rng;
+use std::fmt::Write as _;
+
+// Neighbour queries are bucketed in cells this big.
+const GRID_CELL: f64 = 60.0;
+
+// Genes never mutate outside these ranges.
+const SPEED_RANGE: (f64, f64) = (60.0, 600.0);
+const FORCE_RANGE: (f64, f64) = (150.0, 4300.0);
+const PERCEPTION_RANGE: (f64, f64) = (10.0, 300.0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Species {
+    Prey,
+    Predator,
+}
+
+/// What a creature passes on to its young.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Genes {
+    pub max_speed: f64,
+    pub max_force: f64,
+    /// How far away it notices food, prey or predators.
+    pub perception: f64,
+}
+impl Genes {
+    pub fn starting(species: Species) -> Genes {
+        match species {
+            Species::Prey => Genes {
+                max_speed: 240.0,
+                max_force: 1440.0,
+                perception: 80.0,
+            },
+            Species::Predator => Genes {
+                max_speed: 360.0,
+                max_force: 2160.0,
+                perception: 120.0,
+            },
+        }
+    }
+
+    /// A copy with each gene scaled by a random factor within `rate` of 1.
+    pub fn mutated<R: Rng>(&self, rate: f64, rng: &mut R) -> Genes {
+        let mut scale = |value: f64, (min, max): (f64, f64)| {
+            if rate <= 0.0 {
+                return value;
+            }
+            (value * (1.0 + rng.gen_range(-rate..rate))).max(min).min(max)
+        };
+        Genes {
+            max_speed: scale(self.max_speed, SPEED_RANGE),
+            max_force: scale(self.max_force, FORCE_RANGE),
+            perception: scale(self.perception, PERCEPTION_RANGE),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Creature {
+    pub vehicle: Vehicle,
+    pub species: Species,
+    pub genes: Genes,
+    pub energy: f64,
+    /// How many ancestors it has, counting back to the first of its kind.
+    pub generation: u32,
+}
+
+// A count from a census, and the color it's plotted in.
+type Series = (fn(&Census) -> usize, [f32; 4]);
+impl Creature {
+    pub fn new(species: Species, genes: Genes, pos: Vector, energy: f64, generation: u32) -> Creature {
+        let color = match species {
+            Species::Prey => GREEN,
+            Species::Predator => RED,
+        };
+        let mut vehicle = Vehicle::new(pos.x, pos.y, color);
+        vehicle.r = match species {
+            Species::Prey => 5.0,
+            Species::Predator => 8.0,
+        };
+        vehicle.max_speed = genes.max_speed;
+        vehicle.max_force = genes.max_force;
+        Creature {
+            vehicle,
+            species,
+            genes,
+            energy,
+            generation,
+        }
+    }
+}
+
+/// The rules of an ecosystem. Energy is measured in whatever a creature starts
+/// out with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EcosystemSettings {
+    /// Food added per second, on average.
+    pub food_rate: f64,
+    pub max_food: usize,
+    /// Energy a prey gets from one piece of food.
+    pub food_energy: f64,
+    /// Fraction of a prey's energy a predator gets from eating it, on top of
+    /// `kill_energy`.
+    pub prey_energy: f64,
+    pub kill_energy: f64,
+    /// Energy every creature burns each second just by being alive.
+    pub living_cost: f64,
+    /// Energy burned each second for each unit of speed squared.
+    pub speed_cost: f64,
+    /// Energy a creature needs before it splits in two.
+    pub reproduce_at: f64,
+    /// Largest fraction a gene changes by from parent to child.
+    pub mutation: f64,
+    /// Whenever a species is down to fewer than this many, one more of it wanders
+    /// in with the average genes it last had, so a crash doesn't end the run.
+    pub immigration: usize,
+}
+impl Default for EcosystemSettings {
+    fn default() -> EcosystemSettings {
+        EcosystemSettings {
+            food_rate: 300.0,
+            max_food: 1200,
+            food_energy: 0.4,
+            prey_energy: 0.0,
+            kill_energy: 1.2,
+            living_cost: 0.18,
+            speed_cost: 0.000005,
+            reproduce_at: 2.0,
+            mutation: 0.1,
+            immigration: 2,
+        }
+    }
+}
+
+/// How many of each species there were at one moment, and their average genes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Census {
+    pub tick: u64,
+    pub prey: usize,
+    pub predators: usize,
+    pub food: usize,
+    pub prey_genes: Option<Genes>,
+    pub predator_genes: Option<Genes>,
+    /// The highest generation of either species still alive.
+    pub generation: u32,
+}
+
+pub struct Ecosystem {
+    pub creatures: Vec<Creature>,
+    pub food: Vec<Vector>,
+    pub settings: EcosystemSettings,
+    pub tick: u64,
+    /// A census taken every `census_every` ticks.
+    pub history: Vec<Census>,
+    pub census_every: u64,
+    food_owed: f64,
+    creature_grid: SpatialHash,
+    food_grid: SpatialHash,
+}
+impl Ecosystem {
+    pub fn new<R: Rng>(prey: usize, predators: usize, rng: &mut R) -> Ecosystem {
+        let mut ecosystem = Ecosystem {
+            creatures: vec![],
+            food: vec![],
+            settings: EcosystemSettings::default(),
+            tick: 0,
+            history: vec![],
+            census_every: 30,
+            food_owed: 0.0,
+            creature_grid: SpatialHash::new(GRID_CELL),
+            food_grid: SpatialHash::new(GRID_CELL),
+        };
+        for (species, count) in [(Species::Prey, prey), (Species::Predator, predators)].iter() {
+            for _ in 0..*count {
+                let pos = random_pos(rng);
+                ecosystem
+                    .creatures
+                    .push(Creature::new(*species, Genes::starting(*species), pos, 1.0, 0));
+            }
+        }
+        for _ in 0..ecosystem.settings.max_food / 2 {
+            ecosystem.food.push(random_pos(rng));
+        }
+        ecosystem.history.push(ecosystem.census());
+        ecosystem
+    }
+
+    pub fn count(&self, species: Species) -> usize {
+        self.creatures.iter().filter(|c| c.species == species).count()
+    }
+
+    /// Average genes of everyone of `species`, if any are alive.
+    pub fn mean_genes(&self, species: Species) -> Option<Genes> {
+        let mut sum = Genes {
+            max_speed: 0.0,
+            max_force: 0.0,
+            perception: 0.0,
+        };
+        let mut count = 0;
+        for c in self.creatures.iter().filter(|c| c.species == species) {
+            sum.max_speed += c.genes.max_speed;
+            sum.max_force += c.genes.max_force;
+            sum.perception += c.genes.perception;
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        let n = count as f64;
+        Some(Genes {
+            max_speed: sum.max_speed / n,
+            max_force: sum.max_force / n,
+            perception: sum.perception / n,
+        })
+    }
+
+    pub fn census(&self) -> Census {
+        Census {
+            tick: self.tick,
+            prey: self.count(Species::Prey),
+            predators: self.count(Species::Predator),
+            food: self.food.len(),
+            prey_genes: self.mean_genes(Species::Prey),
+            predator_genes: self.mean_genes(Species::Predator),
+            generation: self.creatures.iter().map(|c| c.generation).max().unwrap_or(0),
+        }
+    }
+
+    pub fn extinct(&self) -> bool {
+        self.creatures.is_empty()
+    }
+
+    // The closest creature of `species` within `radius` of `pos`, other than `skip`.
+    fn closest(&self, pos: Vector, radius: f64, species: Species, skip: usize) -> Option<usize> {
+        self.creature_grid
+            .query(pos, radius)
+            .into_iter()
+            .filter(|&j| j != skip && self.creatures[j].species == species)
+            .map(|j| (j, (self.creatures[j].vehicle.pos - pos).mag()))
+            .filter(|&(_, d)| d < radius)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(j, _)| j)
+    }
+
+    fn closest_food(&self, pos: Vector, radius: f64) -> Option<usize> {
+        self.food_grid
+            .query(pos, radius)
+            .into_iter()
+            .map(|j| (j, (self.food[j] - pos).mag()))
+            .filter(|&(_, d)| d < radius)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(j, _)| j)
+    }
+
+    /// Runs the ecosystem for one tick of `dt` seconds: everyone steers, moves and
+    /// pays for it, then eats, starves or reproduces, and new food grows.
+    pub fn update<R: Rng>(&mut self, dt: f64, rng: &mut R) {
+        let s = self.settings;
+        self.creature_grid.rebuild(self.creatures.iter().map(|c| c.vehicle.pos));
+        self.food_grid.rebuild(self.food.iter().copied());
+
+        // Prey run from the closest predator they can see, or else go for the closest
+        // food. Predators chase the closest prey. Anyone with nothing to do wanders.
+        for i in 0..self.creatures.len() {
+            let c = &self.creatures[i];
+            let (pos, perception) = (c.vehicle.pos, c.genes.perception);
+            let force = match c.species {
+                Species::Prey => match self.closest(pos, perception, Species::Predator, i) {
+                    Some(j) => Some(c.vehicle.evade(&self.creatures[j].vehicle)),
+                    None => self.closest_food(pos, perception).map(|f| c.vehicle.seek(self.food[f])),
+                },
+                Species::Predator => self
+                    .closest(pos, perception, Species::Prey, i)
+                    .map(|j| c.vehicle.persue(&self.creatures[j].vehicle)),
+            };
+            let vehicle = &mut self.creatures[i].vehicle;
+            let force = force.unwrap_or_else(|| vehicle.wander(dt));
+            vehicle.apply_force(force);
+        }
+        for c in &mut self.creatures {
+            c.vehicle.update(dt);
+            c.vehicle.edges();
+            let speed = c.vehicle.vel.mag();
+            c.energy -= (s.living_cost + s.speed_cost * speed * speed) * dt;
+        }
+
+        // Eating, with everyone where they've just moved to.
+        self.creature_grid.rebuild(self.creatures.iter().map(|c| c.vehicle.pos));
+        let mut eaten_food = vec![false; self.food.len()];
+        let mut killed = vec![false; self.creatures.len()];
+        for i in 0..self.creatures.len() {
+            let (pos, r, species) = {
+                let c = &self.creatures[i];
+                (c.vehicle.pos, c.vehicle.r, c.species)
+            };
+            match species {
+                Species::Prey => {
+                    for f in self.food_grid.query(pos, r) {
+                        if !eaten_food[f] && (self.food[f] - pos).mag() < r {
+                            eaten_food[f] = true;
+                            self.creatures[i].energy += s.food_energy;
+                        }
+                    }
+                }
+                Species::Predator => {
+                    for j in self.creature_grid.query(pos, 2.0 * r) {
+                        let prey = &self.creatures[j];
+                        let close = (prey.vehicle.pos - pos).mag() < r + prey.vehicle.r;
+                        if prey.species == Species::Prey && !killed[j] && close && prey.energy > 0.0 {
+                            killed[j] = true;
+                            let gained = s.kill_energy + s.prey_energy * prey.energy;
+                            self.creatures[i].energy += gained;
+                        }
+                    }
+                }
+            }
+        }
+        let mut f = 0;
+        self.food.retain(|_| {
+            f += 1;
+            !eaten_food[f - 1]
+        });
+        let mut i = 0;
+        self.creatures.retain(|c| {
+            i += 1;
+            !killed[i - 1] && c.energy > 0.0
+        });
+
+        // Anyone with enough energy splits it with a child that has mutated genes.
+        let mut born = vec![];
+        for c in &mut self.creatures {
+            if c.energy >= s.reproduce_at {
+                c.energy /= 2.0;
+                let genes = c.genes.mutated(s.mutation, rng);
+                let pos = c.vehicle.pos + Vector::from_angle(rng.gen_range(0.0..std::f64::consts::PI * 2.0)) * c.vehicle.r;
+                born.push(Creature::new(c.species, genes, pos, c.energy, c.generation + 1));
+            }
+        }
+        self.creatures.extend(born);
+
+        for &species in [Species::Prey, Species::Predator].iter() {
+            if self.count(species) < s.immigration {
+                let genes = self
+                    .history
+                    .iter()
+                    .rev()
+                    .filter_map(|c| match species {
+                        Species::Prey => c.prey_genes,
+                        Species::Predator => c.predator_genes,
+                    })
+                    .next()
+                    .unwrap_or_else(|| Genes::starting(species));
+                self.creatures.push(Creature::new(species, genes, random_pos(rng), 1.0, 0));
+            }
+        }
+
+        self.food_owed += s.food_rate * dt;
+        while self.food_owed >= 1.0 {
+            self.food_owed -= 1.0;
+            if self.food.len() < s.max_food {
+                self.food.push(random_pos(rng));
+            }
+        }
+
+        self.tick += 1;
+        if self.tick.is_multiple_of(self.census_every) {
+            self.history.push(self.census());
+        }
+    }
+
+    /// The history as CSV, one census per line.
+    pub fn history_csv(&self) -> String {
+        let mut csv = String::from(
+            "tick,prey,predators,food,generation,prey_speed,prey_force,prey_perception,predator_speed,predator_force,predator_perception\n",
+        );
+        let genes = |g: Option<Genes>| match g {
+            Some(g) => format!("{:.4},{:.4},{:.2}", g.max_speed, g.max_force, g.perception),
+            None => ",,".to_string(),
+        };
+        for c in &self.history {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                c.tick,
+                c.prey,
+                c.predators,
+                c.food,
+                c.generation,
+                genes(c.prey_genes),
+                genes(c.predator_genes)
+            );
+        }
+        csv
+    }
+
+    pub fn draw(&self, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        for f in &self.food {
+            rectangle(LIGHT_GREEN, [f.x - 1.5, f.y - 1.5, 3.0, 3.0], transform, gl);
+        }
+        for c in &self.creatures {
+            c.vehicle.draw(transform, gl);
+        }
+    }
+
+    /// Plots the prey, predator and food counts over the whole history inside the
+    /// rectangle `[x, y, width, height]`, scaled to the largest count.
+    pub fn draw_graph(&self, rect: [f64; 4], transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let [x, y, width, height] = rect;
+        rectangle([0.0, 0.0, 0.0, 0.6], rect, transform, gl);
+        Rectangle::new_border(GRAY, 0.5).draw(rect, &Default::default(), transform, gl);
+        if self.history.len() < 2 {
+            return;
+        }
+
+        let peak = self
+            .history
+            .iter()
+            .map(|c| c.prey.max(c.predators).max(c.food))
+            .max()
+            .unwrap_or(0)
+            .max(1) as f64;
+        let series: [Series; 3] = [
+            (|c| c.food, [0.5, 0.8, 0.5, 0.5]),
+            (|c| c.prey, GREEN),
+            (|c| c.predators, RED),
+        ];
+        // At most one point per pixel across, picked evenly from the history.
+        let points = (self.history.len()).min(width as usize).max(2);
+        for (count, color) in series.iter() {
+            let at = |p: usize| {
+                let census = &self.history[p * (self.history.len() - 1) / (points - 1)];
+                [
+                    x + width * p as f64 / (points - 1) as f64,
+                    y + height * (1.0 - count(census) as f64 / peak),
+                ]
+            };
+            for p in 1..points {
+                let (a, b) = (at(p - 1), at(p));
+                line(*color, 1.0, [a[0], a[1], b[0], b[1]], transform, gl);
+            }
+        }
+    }
+}
+
+fn random_pos<R: Rng>(rng: &mut R) -> Vector {
+    Vector::new(
+        rng.gen_range(0.0..WINDOW_WIDTH as f64),
+        rng.gen_range(0.0..WINDOW_HEIGHT as f64),
+    )
+}
@@ -0,0 +1,374 @@
+//! Vehicles steered by neural networks that sense the world through rays, the
+//! arenas they're trained in and the fitness functions that judge them.
+
+use crate::{
+    neuro::{Network, Population},
+    steering::Obstacle,
+    vector::Vector,
+    vehicle::Vehicle,
+    GRAY, LIGHT_GREEN, UPDATES_PER_SECOND, WINDOW_HEIGHT, WINDOW_WIDTH, YELLOW,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use rand::Rng;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+/// Rays are spread evenly around the vehicle, the first straight ahead.
+pub const SENSOR_RAYS: usize = 8;
+/// Each ray senses the nearest obstacle and the nearest food along it, and the
+/// vehicle also senses its own speed. The outputs are the forward and sideways
+/// steering force.
+pub const LAYERS: [usize; 3] = [SENSOR_RAYS * 2 + 1, 12, 2];
+/// How long each network gets to show what it can do, in ticks of
+/// `1 / UPDATES_PER_SECOND` seconds. Training always uses whole ticks so that a
+/// network scores the same with or without a window.
+pub const EPISODE_TICKS: u32 = 1500;
+
+// How far the rays reach.
+const SENSOR_RANGE: f64 = 250.0;
+const FOOD_RADIUS: f64 = 6.0;
+const FOOD_COUNT: usize = 60;
+const OBSTACLE_COUNT: usize = 14;
+
+/// The same world every network of a generation is tried in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arena {
+    pub obstacles: Vec<Obstacle>,
+    pub food: Vec<Vector>,
+    pub start: Vector,
+    /// Which way vehicles face at the start, in radians.
+    pub heading: f64,
+}
+impl Arena {
+    pub fn random<R: Rng>(rng: &mut R) -> Arena {
+        let (width, height) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+        let start = Vector::new(width / 2.0, height / 2.0);
+        let mut arena = Arena {
+            obstacles: vec![],
+            food: vec![],
+            start,
+            heading: rng.gen_range(0.0..2.0 * PI),
+        };
+        while arena.obstacles.len() < OBSTACLE_COUNT {
+            let r = rng.gen_range(30.0..90.0);
+            let obstacle = Obstacle::new(rng.gen_range(0.0..width), rng.gen_range(0.0..height), r);
+            if (obstacle.pos - start).mag() > r + 100.0 {
+                arena.obstacles.push(obstacle);
+            }
+        }
+        while arena.food.len() < FOOD_COUNT {
+            let pos = Vector::new(rng.gen_range(20.0..width - 20.0), rng.gen_range(20.0..height - 20.0));
+            if !arena.obstacles.iter().any(|o| (o.pos - pos).mag() < o.r + FOOD_RADIUS) {
+                arena.food.push(pos);
+            }
+        }
+        arena
+    }
+
+    /// Whether something of radius `r` at `pos` touches an obstacle or the edge of
+    /// the window.
+    pub fn collides(&self, pos: Vector, r: f64) -> bool {
+        pos.x < r
+            || pos.y < r
+            || pos.x > WINDOW_WIDTH as f64 - r
+            || pos.y > WINDOW_HEIGHT as f64 - r
+            || self.obstacles.iter().any(|o| (o.pos - pos).mag() < o.r + r)
+    }
+
+    /// Draws the obstacles, and the food `eaten` says is left.
+    pub fn draw(&self, eaten: &[bool], transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        for o in &self.obstacles {
+            ellipse(GRAY, [o.pos.x - o.r, o.pos.y - o.r, 2.0 * o.r, 2.0 * o.r], transform, gl);
+        }
+        for (f, eaten) in self.food.iter().zip(eaten) {
+            if !eaten {
+                let r = FOOD_RADIUS;
+                ellipse(LIGHT_GREEN, [f.x - r, f.y - r, 2.0 * r, 2.0 * r], transform, gl);
+            }
+        }
+    }
+}
+
+// How far along the ray from `pos` in direction `dir` it hits the circle at
+// `center`, if it does. Starting inside counts as a hit straight away.
+fn ray_circle(pos: Vector, dir: Vector, center: Vector, r: f64) -> Option<f64> {
+    let to = pos - center;
+    let b = to.dot(&dir);
+    let c = to.dot(&to) - r * r;
+    if c < 0.0 {
+        return Some(0.0);
+    }
+    let disc = b * b - c;
+    if disc < 0.0 || b > 0.0 {
+        return None;
+    }
+    Some(-b - disc.sqrt())
+}
+
+// How far along the ray from `pos` in direction `dir` it leaves the window.
+fn ray_edge(pos: Vector, dir: Vector) -> f64 {
+    let (width, height) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+    let tx = if dir.x > 0.0 {
+        (width - pos.x) / dir.x
+    } else if dir.x < 0.0 {
+        -pos.x / dir.x
+    } else {
+        f64::INFINITY
+    };
+    let ty = if dir.y > 0.0 {
+        (height - pos.y) / dir.y
+    } else if dir.y < 0.0 {
+        -pos.y / dir.y
+    } else {
+        f64::INFINITY
+    };
+    tx.min(ty).max(0.0)
+}
+
+/// What a vehicle got up to in one episode, for fitness functions to judge.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrialStats {
+    pub eaten: usize,
+    /// Ticks it lasted without crashing.
+    pub ticks: u32,
+    pub distance: f64,
+    pub crashed: bool,
+}
+
+/// Turns what a vehicle did into a score, higher being better.
+pub trait Fitness: Sync {
+    fn score(&self, stats: &TrialStats) -> f64;
+
+    /// A short description, shown in the window title.
+    fn name(&self) -> String;
+}
+
+/// Eating as much as possible, with a little credit for staying alive so the
+/// first generations have something to work with.
+pub struct Foraging;
+impl Fitness for Foraging {
+    fn score(&self, stats: &TrialStats) -> f64 {
+        stats.eaten as f64 * 100.0 + stats.ticks as f64 * 0.01
+    }
+
+    fn name(&self) -> String {
+        "Foraging".to_string()
+    }
+}
+
+/// Going as far as possible without crashing.
+pub struct Exploring;
+impl Fitness for Exploring {
+    fn score(&self, stats: &TrialStats) -> f64 {
+        stats.distance
+    }
+
+    fn name(&self) -> String {
+        "Exploring".to_string()
+    }
+}
+
+/// Lasting the whole episode while moving, and not so much how far.
+pub struct Survival;
+impl Fitness for Survival {
+    fn score(&self, stats: &TrialStats) -> f64 {
+        let crash = if stats.crashed { 0.5 } else { 1.0 };
+        (stats.ticks as f64 + stats.distance.sqrt()) * crash
+    }
+
+    fn name(&self) -> String {
+        "Survival".to_string()
+    }
+}
+
+/// One network driving one vehicle through an arena.
+#[derive(Debug, Clone)]
+pub struct Trial {
+    pub vehicle: Vehicle,
+    /// Which of the arena's food this vehicle has eaten.
+    pub eaten: Vec<bool>,
+    pub stats: TrialStats,
+    pub alive: bool,
+    // The way the vehicle faces, kept for when it stops.
+    heading: f64,
+}
+impl Trial {
+    pub fn new(arena: &Arena) -> Trial {
+        let mut vehicle = Vehicle::new(arena.start.x, arena.start.y, [0.2, 0.6, 1.0, 0.6]);
+        vehicle.r = 8.0;
+        vehicle.max_speed = 360.0;
+        vehicle.max_force = 2880.0;
+        vehicle.vel = Vector::from_angle(arena.heading) * (vehicle.max_speed / 2.0);
+        Trial {
+            vehicle,
+            eaten: vec![false; arena.food.len()],
+            stats: TrialStats::default(),
+            alive: true,
+            heading: arena.heading,
+        }
+    }
+
+    /// The network's inputs: for each ray how close the nearest obstacle and food
+    /// along it are, from 0 for nothing in range to 1 for touching, and then the
+    /// vehicle's speed as a fraction of its top speed.
+    pub fn sense(&self, arena: &Arena) -> Vec<f64> {
+        let pos = self.vehicle.pos;
+        let closeness = |d: f64| if d < SENSOR_RANGE { 1.0 - d / SENSOR_RANGE } else { 0.0 };
+        let mut inputs = Vec::with_capacity(LAYERS[0]);
+        for ray in 0..SENSOR_RAYS {
+            let dir = Vector::from_angle(self.heading + ray as f64 * 2.0 * PI / SENSOR_RAYS as f64);
+            let wall = arena
+                .obstacles
+                .iter()
+                .filter_map(|o| ray_circle(pos, dir, o.pos, o.r))
+                .fold(ray_edge(pos, dir), f64::min);
+            let food = arena
+                .food
+                .iter()
+                .zip(&self.eaten)
+                .filter(|(_, eaten)| !**eaten)
+                .filter_map(|(f, _)| ray_circle(pos, dir, *f, FOOD_RADIUS))
+                .fold(f64::INFINITY, f64::min);
+            inputs.push(closeness(wall));
+            inputs.push(closeness(food));
+        }
+        inputs.push(self.vehicle.vel.mag() / self.vehicle.max_speed);
+        inputs
+    }
+
+    /// Lets `network` steer for one tick, then eats whatever food the vehicle
+    /// reaches and stops it if it crashed.
+    pub fn step(&mut self, network: &Network, arena: &Arena) {
+        if !self.alive {
+            return;
+        }
+        let outputs = network.forward(&self.sense(arena));
+        let forward = Vector::from_angle(self.heading);
+        let side = Vector::new(-forward.y, forward.x);
+        let force = (forward * outputs[0] + side * outputs[1]) * self.vehicle.max_force;
+
+        let before = self.vehicle.pos;
+        self.vehicle.apply_force(force);
+        self.vehicle.update(1.0 / UPDATES_PER_SECOND as f64);
+        if self.vehicle.vel.mag() > 0.0 {
+            self.heading = self.vehicle.vel.heading().to_radians();
+        }
+        self.stats.distance += (self.vehicle.pos - before).mag();
+
+        let (pos, r) = (self.vehicle.pos, self.vehicle.r);
+        for (f, eaten) in arena.food.iter().zip(self.eaten.iter_mut()) {
+            if !*eaten && (*f - pos).mag() < r + FOOD_RADIUS {
+                *eaten = true;
+                self.stats.eaten += 1;
+            }
+        }
+        if arena.collides(pos, r) {
+            self.alive = false;
+            self.stats.crashed = true;
+        } else {
+            self.stats.ticks += 1;
+        }
+    }
+}
+
+/// Every network of a population tried in the same arena at once.
+pub struct Generation {
+    pub arena: Arena,
+    pub trials: Vec<Trial>,
+    pub tick: u32,
+}
+impl Generation {
+    pub fn new<R: Rng>(population: &Population, rng: &mut R) -> Generation {
+        let arena = Arena::random(rng);
+        Generation {
+            trials: population.networks.iter().map(|_| Trial::new(&arena)).collect(),
+            arena,
+            tick: 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.tick >= EPISODE_TICKS || self.trials.iter().all(|t| !t.alive)
+    }
+
+    /// Runs every trial for one tick.
+    pub fn step(&mut self, population: &Population) {
+        let arena = &self.arena;
+        self.trials
+            .par_iter_mut()
+            .zip(&population.networks)
+            .for_each(|(trial, network)| trial.step(network, arena));
+        self.tick += 1;
+    }
+
+    /// Runs every trial to the end of the episode, each on its own thread.
+    pub fn run(&mut self, population: &Population) {
+        let arena = &self.arena;
+        let ticks = EPISODE_TICKS - self.tick.min(EPISODE_TICKS);
+        self.trials
+            .par_iter_mut()
+            .zip(&population.networks)
+            .for_each(|(trial, network)| {
+                for _ in 0..ticks {
+                    if !trial.alive {
+                        break;
+                    }
+                    trial.step(network, arena);
+                }
+            });
+        self.tick = EPISODE_TICKS;
+    }
+
+    pub fn scores(&self, fitness: &dyn Fitness) -> Vec<f64> {
+        self.trials.iter().map(|t| fitness.score(&t.stats)).collect()
+    }
+
+    /// The trial doing best so far by `fitness`.
+    pub fn leader(&self, fitness: &dyn Fitness) -> Option<usize> {
+        let scores = self.scores(fitness);
+        (0..scores.len()).max_by(|&a, &b| scores[a].partial_cmp(&scores[b]).unwrap())
+    }
+
+    /// Draws the arena with the food the leader has left, every vehicle still going
+    /// and the leader's sensor rays.
+    pub fn draw(&self, fitness: &dyn Fitness, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let leader = self.leader(fitness);
+        let eaten = leader.map_or(vec![false; self.arena.food.len()], |i| self.trials[i].eaten.clone());
+        self.arena.draw(&eaten, transform, gl);
+        for trial in self.trials.iter().filter(|t| t.alive) {
+            trial.vehicle.draw(transform, gl);
+        }
+
+        if let Some(i) = leader {
+            let trial = &self.trials[i];
+            let pos = trial.vehicle.pos;
+            let inputs = trial.sense(&self.arena);
+            for ray in 0..SENSOR_RAYS {
+                let dir = Vector::from_angle(trial.heading + ray as f64 * 2.0 * PI / SENSOR_RAYS as f64);
+                let wall = inputs[ray * 2];
+                let end = pos + dir * (SENSOR_RANGE * (1.0 - wall));
+                line([1.0, 1.0, 1.0, 0.2], 0.5, [pos.x, pos.y, end.x, end.y], transform, gl);
+            }
+            let mut leader = trial.vehicle.clone();
+            leader.color = YELLOW;
+            leader.draw(transform, gl);
+        }
+    }
+}
+
+/// Tries every network of `population` in a new arena, without drawing anything,
+/// and breeds the next generation from how they did. Returns the best and mean
+/// scores of the generation that was tried.
+pub fn train_generation<R: Rng>(population: &mut Population, fitness: &dyn Fitness, rng: &mut R) -> (f64, f64) {
+    let mut generation = Generation::new(population, rng);
+    generation.run(population);
+    let scores = generation.scores(fitness);
+    population.evolve(&scores, rng);
+    let best = scores.iter().cloned().fold(f64::MIN, f64::max);
+    (best, scores.iter().sum::<f64>() / scores.len() as f64)
+}
@@ -0,0 +1,214 @@
+//! Boids: vehicles that flock using separation, alignment and cohesion.
+
+use crate::{
+    spatial::SpatialHash, vector::Vector, vehicle::Vehicle, BLUE, GREEN, RED, WINDOW_HEIGHT,
+    WINDOW_WIDTH,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use rand::Rng;
+
+/// The knobs of a flock, all of which can be changed while it runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlockSettings {
+    /// How far away a boid notices the others.
+    pub perception: f64,
+    /// How close others have to be before a boid moves away from them.
+    pub separation_radius: f64,
+    pub separation: f64,
+    pub alignment: f64,
+    pub cohesion: f64,
+}
+impl Default for FlockSettings {
+    fn default() -> FlockSettings {
+        FlockSettings {
+            perception: 50.0,
+            separation_radius: 25.0,
+            separation: 1.5,
+            alignment: 1.0,
+            cohesion: 1.0,
+        }
+    }
+}
+
+/// The three forces one boid felt in its last update, before weighting.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FlockForces {
+    pub separation: Vector,
+    pub alignment: Vector,
+    pub cohesion: Vector,
+}
+
+/// Which boids show their neighbourhood and steering forces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    Off,
+    /// Only the boid closest to the mouse.
+    Hovered,
+    All,
+}
+impl DebugView {
+    pub fn next(&self) -> DebugView {
+        match self {
+            DebugView::Off => DebugView::Hovered,
+            DebugView::Hovered => DebugView::All,
+            DebugView::All => DebugView::Off,
+        }
+    }
+}
+
+pub struct Flock {
+    pub boids: Vec<Vehicle>,
+    pub settings: FlockSettings,
+    /// What each boid felt last update, by index.
+    pub forces: Vec<FlockForces>,
+    grid: SpatialHash,
+}
+impl Flock {
+    pub fn new(count: usize) -> Flock {
+        let mut flock = Flock {
+            boids: vec![],
+            settings: FlockSettings::default(),
+            forces: vec![],
+            grid: SpatialHash::new(FlockSettings::default().perception),
+        };
+        flock.resize(count);
+        flock
+    }
+
+    /// A boid at a random place, going a random way.
+    pub fn boid() -> Vehicle {
+        let mut rng = rand::thread_rng();
+        let mut boid = Vehicle::new(
+            rng.gen_range(0.0..WINDOW_WIDTH as f64),
+            rng.gen_range(0.0..WINDOW_HEIGHT as f64),
+            [0.9, 0.9, 0.9, 1.0],
+        );
+        boid.r = 6.0;
+        boid.max_speed = 240.0;
+        boid.max_force = 430.0;
+        // Only a little drag, so a boid that strays from the flock glides on for a
+        // while instead of stopping.
+        boid.mass = 1.2;
+        boid.drag = 0.1;
+        boid.max_turn_rate = 4.0;
+        boid.vel = Vector::from_angle(rng.gen_range(0.0..std::f64::consts::PI * 2.0)) * boid.max_speed;
+        boid
+    }
+
+    pub fn resize(&mut self, count: usize) {
+        while self.boids.len() < count {
+            self.boids.push(Flock::boid());
+        }
+        self.boids.truncate(count);
+        self.forces.resize(count, FlockForces::default());
+        self.reindex();
+    }
+
+    // Rebuilds the spatial hash from where the boids are now.
+    fn reindex(&mut self) {
+        self.grid.cell = self.settings.perception.max(1.0);
+        self.grid.rebuild(self.boids.iter().map(|b| b.pos));
+    }
+
+    /// Indices of the other boids within `perception` of boid `i`.
+    pub fn neighbours(&self, i: usize) -> Vec<usize> {
+        let pos = self.boids[i].pos;
+        let perception = self.settings.perception;
+        self.grid
+            .query(pos, perception)
+            .into_iter()
+            .filter(|&j| j != i && (self.boids[j].pos - pos).mag() < perception)
+            .collect()
+    }
+
+    /// Moves the flock on by `dt` seconds.
+    pub fn update(&mut self, dt: f64) {
+        if self.grid.cell != self.settings.perception.max(1.0) {
+            self.reindex();
+        }
+
+        let settings = self.settings;
+        for i in 0..self.boids.len() {
+            let neighbours = self.neighbours(i);
+            let boid = &self.boids[i];
+            let others = || neighbours.iter().map(|&j| &self.boids[j]);
+            self.forces[i] = FlockForces {
+                separation: boid.separate(others(), settings.separation_radius),
+                alignment: boid.align(others(), settings.perception),
+                cohesion: boid.cohere(others(), settings.perception),
+            };
+        }
+
+        for (boid, forces) in self.boids.iter_mut().zip(&self.forces) {
+            // Unlike `combine_weighted`, the total isn't capped, so raising one
+            // weight makes that behavior stronger rather than crowding out the others.
+            boid.apply_force(
+                forces.separation * settings.separation
+                    + forces.alignment * settings.alignment
+                    + forces.cohesion * settings.cohesion,
+            );
+            boid.update(dt);
+            boid.edges();
+        }
+        self.reindex();
+    }
+
+    /// The boid closest to `pos`.
+    pub fn closest(&self, pos: Vector) -> Option<usize> {
+        (0..self.boids.len()).min_by(|&a, &b| {
+            let da = (self.boids[a].pos - pos).mag();
+            let db = (self.boids[b].pos - pos).mag();
+            da.partial_cmp(&db).unwrap()
+        })
+    }
+
+    /// Draws every boid, and the neighbourhood and forces of those `view` picks.
+    pub fn draw(&self, view: DebugView, mouse: Vector, transform: Matrix2d, gl: &mut GlGraphics) {
+        let debugged: Vec<usize> = match view {
+            DebugView::Off => vec![],
+            DebugView::Hovered => self.closest(mouse).into_iter().collect(),
+            DebugView::All => (0..self.boids.len()).collect(),
+        };
+        for &i in &debugged {
+            self.draw_debug(i, view == DebugView::Hovered, transform, gl);
+        }
+        for boid in &self.boids {
+            boid.draw(transform, gl);
+        }
+    }
+
+    // The perception circle, lines to each neighbour when `lines` is set, and the
+    // three weighted forces scaled up to be visible.
+    fn draw_debug(&self, i: usize, lines: bool, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let boid = &self.boids[i];
+        let p = self.settings.perception;
+        Ellipse::new_border([1.0, 1.0, 1.0, 0.2], 0.5).draw(
+            [boid.pos.x - p, boid.pos.y - p, 2.0 * p, 2.0 * p],
+            &Default::default(),
+            transform,
+            gl,
+        );
+        if lines {
+            for j in self.neighbours(i) {
+                let other = self.boids[j].pos;
+                line([1.0, 1.0, 0.0, 0.4], 0.5, [boid.pos.x, boid.pos.y, other.x, other.y], transform, gl);
+            }
+        }
+
+        let forces = self.forces[i];
+        let scale = 20.0 / boid.max_force;
+        for &(force, weight, color) in [
+            (forces.separation, self.settings.separation, RED),
+            (forces.alignment, self.settings.alignment, GREEN),
+            (forces.cohesion, self.settings.cohesion, BLUE),
+        ]
+        .iter()
+        {
+            let end = boid.pos + force * (weight * scale);
+            line(color, 1.0, [boid.pos.x, boid.pos.y, end.x, end.y], transform, gl);
+        }
+    }
+}
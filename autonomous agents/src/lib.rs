@@ -1,6 +1,15 @@
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+pub mod crowd;
+pub mod ecosystem;
+pub mod evolution;
+pub mod flock;
+pub mod navigation;
+pub mod neuro;
+pub mod playground;
+pub mod spatial;
+pub mod steering;
 pub mod vector;
 pub mod vehicle;
 
@@ -21,6 +30,9 @@ pub const LIGHT_RED: [f32; 4] = [1.0, 0.0, 0.5, 1.0];
 pub const WINDOW_WIDTH: u32 = 1920;
 pub const WINDOW_HEIGHT: u32 = 1080;
 
+/// Simulations step this many times a second, in the window and headless alike.
+pub const UPDATES_PER_SECOND: u64 = 120;
+
 #[derive(Debug)]
 pub struct FPSCounter {
     last_second_frames: VecDeque<Instant>
@@ -11,11 +11,133 @@ use piston::input::RenderEvent;
 use piston::window::WindowSettings;
 use piston::{AdvancedWindow, EventLoop, MouseCursorEvent, RenderArgs};
 use rand::Rng;
+use piston::UpdateEvent;
 use Autonomous_Agent::{
-    vector::Vector, vehicle::Vehicle, FPSCounter, BLACK, WHITE, WINDOW_HEIGHT, WINDOW_WIDTH,
+    crowd::Crowd,
+    ecosystem::{Ecosystem, Species},
+    evolution::{self, Exploring, Fitness, Foraging, Generation, Survival, LAYERS},
+    neuro::{self, GeneticSettings, Population},
+    flock::{DebugView, Flock},
+    playground::{Playground, Preset, TargetKind},
+    vector::Vector,
+    FPSCounter, BLACK, UPDATES_PER_SECOND, WHITE, WINDOW_HEIGHT, WINDOW_WIDTH,
 };
 
+// Which demo is running, picked with the number keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scene {
+    Playground,
+    Flocking,
+    Maze,
+    Ecosystem,
+    Evolution,
+}
+
+// Where S saves the best genomes in the evolution scene, and L loads them from.
+const GENOMES_PATH: &str = "genomes.json";
+
+// The fitness function picked by number, on the command line or with T.
+fn fitness_function(key: u32) -> Box<dyn Fitness> {
+    match key {
+        2 => Box::new(Exploring),
+        3 => Box::new(Survival),
+        _ => Box::new(Foraging),
+    }
+}
+
+// `Autonomous_Agent train <generations> [out.json] [fitness 1-3] [in.json]` evolves
+// networks without a window, starting from the genomes in `in.json` if given, and
+// saves the best it finds.
+fn train_headless(args: &[String]) {
+    let usage = "Usage: Autonomous_Agent train <generations> [out.json] [fitness 1-3] [in.json]";
+    let generations: u32 = match args.get(2).map(|g| g.parse()) {
+        Some(Ok(generations)) => generations,
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let fitness = match args.get(4).map_or(Ok(1), |k| k.parse()) {
+        Ok(key) => fitness_function(key),
+        Err(_) => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let mut rng = rand::thread_rng();
+    let mut population = match args.get(5) {
+        Some(path) => match neuro::load_genomes(path) {
+            Ok(genomes) => Population::from_genomes(genomes, &LAYERS, GeneticSettings::default(), &mut rng),
+            Err(err) => {
+                println!("Could not load {}: {}", path, err);
+                return;
+            }
+        },
+        None => Population::random(&LAYERS, GeneticSettings::default(), &mut rng),
+    };
+
+    println!("Training {} networks for {} generations on {}", population.networks.len(), generations, fitness.name());
+    for _ in 0..generations {
+        let (best, mean) = evolution::train_generation(&mut population, fitness.as_ref(), &mut rng);
+        println!("Generation {} | best {:.1} | mean {:.1}", population.generation, best, mean);
+    }
+    if let Some(out) = args.get(3) {
+        match neuro::save_genomes(out, &population.hall_of_fame) {
+            Ok(()) => println!("Saved {} genomes to {}", population.hall_of_fame.len(), out),
+            Err(err) => println!("Could not save {}: {}", out, err),
+        }
+    }
+}
+
+// Census lines are printed this often when running headless.
+const REPORT_EVERY: u64 = 10000;
+
+// `Autonomous_Agent ecosystem <ticks> [out.csv]` runs the ecosystem without a
+// window and saves its population history.
+fn ecosystem_headless(args: &[String]) {
+    let ticks: u64 = match args.get(2).map(|t| t.parse()) {
+        Some(Ok(ticks)) => ticks,
+        _ => {
+            println!("Usage: Autonomous_Agent ecosystem <ticks> [out.csv]");
+            return;
+        }
+    };
+    let mut rng = rand::thread_rng();
+    let mut ecosystem = Ecosystem::new(150, 15, &mut rng);
+    for _ in 0..ticks {
+        ecosystem.update(1.0 / UPDATES_PER_SECOND as f64, &mut rng);
+        if ecosystem.tick.is_multiple_of(REPORT_EVERY) {
+            let c = ecosystem.census();
+            println!(
+                "Tick {} | {} prey | {} predators | {} food | generation {}",
+                c.tick, c.prey, c.predators, c.food, c.generation
+            );
+        }
+    }
+    for &species in [Species::Prey, Species::Predator].iter() {
+        if let Some(g) = ecosystem.mean_genes(species) {
+            println!(
+                "{:?}: max speed {:.2}, max force {:.3}, perception {:.0}",
+                species, g.max_speed, g.max_force, g.perception
+            );
+        }
+    }
+    if let Some(out) = args.get(3) {
+        match std::fs::write(out, ecosystem.history_csv()) {
+            Ok(()) => println!("Saved {} censuses to {}", ecosystem.history.len(), out),
+            Err(err) => println!("Could not save {}: {}", out, err),
+        }
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        Some("ecosystem") => return ecosystem_headless(&args),
+        Some("train") => return train_headless(&args),
+        _ => (),
+    }
+
     let opengl = OpenGL::V3_2;
 
     let mut window: Window =
@@ -33,35 +155,229 @@ fn main() {
 
     let mut events = Events::new(EventSettings::new());
     events.set_max_fps(10000);
+    events.set_ups(UPDATES_PER_SECOND);
+
+    // In the playground left clicking a vehicle selects it to show what it's doing,
+    // and anywhere else places a seek target. Right clicking places a flee target,
+    // middle clicking or O an obstacle, and Delete clears them all. F1 to F6 pick
+    // the behavior preset. [ and ] halve and double the selected vehicle's mass,
+    // ; and ' lower and raise its drag, and , and . its turn rate.
+    let mut playground = Playground::new(20);
+
+    // In the flocking scene Equals/Minus double or halve the flock, Up/Down change
+    // how far boids can see, Z/X, C/V and B/N lower and raise the separation,
+    // alignment and cohesion weights, and G cycles the debug view.
+    let mut scene = Scene::Playground;
+    let mut flock = Flock::new(1000);
+    let mut debug_view = DebugView::Off;
+    let mut mouse = Vector::default();
+
+    // In the maze scene clicking sends the whole crowd to the mouse, M builds a new
+    // maze and R shows where each agent is headed.
+    let mut crowd = Crowd::new(300, &mut rng);
+    let mut show_routes = false;
+
+    // In the ecosystem scene F fast forwards, running ten ticks per update.
+    let mut ecosystem = Ecosystem::new(150, 15, &mut rng);
+    let mut fast_forward = false;
 
-    let mut persuer: Vehicle = Vehicle::new(100.0, 100.0, [0.0, 1.0, 0.0, 1.0]);
-    let mut target: Vehicle = Vehicle::new(200.0, 200.0, [1.0, 0.0, 0.0, 1.0]);
+    // In the evolution scene T cycles the fitness function, S saves the best genomes
+    // so far to GENOMES_PATH and L starts again from the ones saved there. F fast
+    // forwards here too.
+    let mut fitness_key = 1;
+    let mut fitness = fitness_function(fitness_key);
+    let mut population = Population::random(&LAYERS, GeneticSettings::default(), &mut rng);
+    let mut generation = Generation::new(&population, &mut rng);
 
     while let Some(e) = events.next(&mut window) {
         use graphics::*;
 
         if let Some(args) = e.render_args() {
+            if scene == Scene::Evolution {
+                gl.draw(args.viewport(), |c, gl| {
+                    clear(BLACK, gl);
+                    generation.draw(fitness.as_ref(), c.transform, gl);
+                });
+                window.set_title(format!(
+                    "Autonomous Agent Test | Evolution | {:03} fps | Generation {} | Tick {} | {} alive | {} | Best {:.1}",
+                    fps_counter.tick(),
+                    population.generation,
+                    generation.tick,
+                    generation.trials.iter().filter(|t| t.alive).count(),
+                    fitness.name(),
+                    population.best().map_or(0.0, |g| g.fitness)
+                ));
+                continue;
+            }
+            if scene == Scene::Ecosystem {
+                gl.draw(args.viewport(), |c, gl| {
+                    clear(BLACK, gl);
+                    ecosystem.draw(c.transform, gl);
+                    let graph = [WINDOW_WIDTH as f64 - 420.0, WINDOW_HEIGHT as f64 - 170.0, 400.0, 150.0];
+                    ecosystem.draw_graph(graph, c.transform, gl);
+                });
+                let genes = |species| {
+                    ecosystem.mean_genes(species).map_or("-".to_string(), |g| {
+                        format!("speed {:.2} force {:.3} perception {:.0}", g.max_speed, g.max_force, g.perception)
+                    })
+                };
+                window.set_title(format!(
+                    "Autonomous Agent Test | Ecosystem | {:03} fps | Tick {} | {} prey ({}) | {} predators ({}) | {} food",
+                    fps_counter.tick(),
+                    ecosystem.tick,
+                    ecosystem.count(Species::Prey),
+                    genes(Species::Prey),
+                    ecosystem.count(Species::Predator),
+                    genes(Species::Predator),
+                    ecosystem.food.len()
+                ));
+                continue;
+            }
+            if scene == Scene::Maze {
+                gl.draw(args.viewport(), |c, gl| {
+                    clear(BLACK, gl);
+                    crowd.draw(show_routes, c.transform, gl);
+                });
+                window.set_title(format!(
+                    "Autonomous Agent Test | Maze | {:03} fps | {} agents",
+                    fps_counter.tick(),
+                    crowd.agents.len()
+                ));
+                continue;
+            }
+            if scene == Scene::Flocking {
+                gl.draw(args.viewport(), |c, gl| {
+                    clear(BLACK, gl);
+                    flock.draw(debug_view, mouse, c.transform, gl);
+                });
+                let s = flock.settings;
+                window.set_title(format!(
+                    "Autonomous Agent Test | Flocking | {:03} fps | {} boids | Perception {:.0} | Separation {:.1} | Alignment {:.1} | Cohesion {:.1} | Debug {:?}",
+                    fps_counter.tick(),
+                    flock.boids.len(),
+                    s.perception,
+                    s.separation,
+                    s.alignment,
+                    s.cohesion,
+                    debug_view
+                ));
+                continue;
+            }
+
             gl.draw(args.viewport(), |c, gl| {
                 clear(BLACK, gl);
-
-
-                persuer.update();
-                persuer.show(gl, &args);
-                persuer.apply_force(persuer.arrive(target.pos));
-                persuer.edges();
-
-                target.update();
-                target.show(gl, &args);
-                //target.apply_force(target.evade(&persuer));
-                target.edges();
+                playground.draw(c.transform, gl);
             });
             window.set_title(format!(
-                "Autonomous Agent Test | {:03} fps",
-                fps_counter.tick()
+                "Autonomous Agent Test | Playground | {:03} fps | {} | {} targets | {} obstacles | {}",
+                fps_counter.tick(),
+                playground.preset.name(),
+                playground.targets.len(),
+                playground.obstacles.len(),
+                playground.describe_selected().unwrap_or_else(|| "Click a vehicle to inspect it".to_string())
             ));
         } else if let Some([x, y]) = e.mouse_cursor_args() {
-            // target.pos.x = x;
-            // target.pos.y = y;
+            mouse = Vector::new(x, y);
+            playground.mouse = mouse;
+        } else if let piston::Event::Input(i, _) = e {
+            if let piston::Input::Button(b) = i {
+                if let (piston::ButtonState::Press, piston::Button::Mouse(m)) = (b.state, b.button) {
+                    match (scene, m) {
+                        (Scene::Maze, piston::MouseButton::Left) => crowd.send_all(mouse),
+                        // Clicking a vehicle selects it, and clicking anywhere else places a target.
+                        (Scene::Playground, piston::MouseButton::Left) if !playground.select_at(mouse) => {
+                            playground.place_target(mouse, TargetKind::Seek)
+                        }
+                        (Scene::Playground, piston::MouseButton::Right) => playground.place_target(mouse, TargetKind::Flee),
+                        (Scene::Playground, piston::MouseButton::Middle) => playground.place_obstacle(mouse),
+                        _ => (),
+                    }
+                }
+                if let (piston::ButtonState::Release, piston::Button::Keyboard(k)) = (b.state, b.button) {
+                    let s = &mut flock.settings;
+                    match (scene, k) {
+                        (_, piston::Key::D1) => scene = Scene::Playground,
+                        (_, piston::Key::D2) => scene = Scene::Flocking,
+                        (_, piston::Key::D3) => scene = Scene::Maze,
+                        (_, piston::Key::D4) => scene = Scene::Ecosystem,
+                        (_, piston::Key::D5) => scene = Scene::Evolution,
+                        (Scene::Ecosystem, piston::Key::F) | (Scene::Evolution, piston::Key::F) => fast_forward = !fast_forward,
+                        (Scene::Evolution, piston::Key::T) => {
+                            fitness_key = fitness_key % 3 + 1;
+                            fitness = fitness_function(fitness_key);
+                        }
+                        (Scene::Evolution, piston::Key::S) => {
+                            if let Err(err) = neuro::save_genomes(GENOMES_PATH, &population.hall_of_fame) {
+                                println!("Could not save {}: {}", GENOMES_PATH, err);
+                            }
+                        }
+                        (Scene::Evolution, piston::Key::L) => match neuro::load_genomes(GENOMES_PATH) {
+                            Ok(genomes) => {
+                                population = Population::from_genomes(genomes, &LAYERS, GeneticSettings::default(), &mut rng);
+                                generation = Generation::new(&population, &mut rng);
+                            }
+                            Err(err) => println!("Could not load {}: {}", GENOMES_PATH, err),
+                        },
+                        (Scene::Maze, piston::Key::M) => crowd = Crowd::new(crowd.agents.len(), &mut rng),
+                        (Scene::Maze, piston::Key::R) => show_routes = !show_routes,
+                        (Scene::Flocking, piston::Key::Equals) => {
+                            let count = (flock.boids.len() * 2).min(16000);
+                            flock.resize(count);
+                        }
+                        (Scene::Flocking, piston::Key::Minus) => {
+                            let count = (flock.boids.len() / 2).max(1);
+                            flock.resize(count);
+                        }
+                        (Scene::Flocking, piston::Key::Up) => s.perception = (s.perception + 5.0).min(300.0),
+                        (Scene::Flocking, piston::Key::Down) => s.perception = (s.perception - 5.0).max(5.0),
+                        (Scene::Flocking, piston::Key::Z) => s.separation = (s.separation - 0.1).max(0.0),
+                        (Scene::Flocking, piston::Key::X) => s.separation += 0.1,
+                        (Scene::Flocking, piston::Key::C) => s.alignment = (s.alignment - 0.1).max(0.0),
+                        (Scene::Flocking, piston::Key::V) => s.alignment += 0.1,
+                        (Scene::Flocking, piston::Key::B) => s.cohesion = (s.cohesion - 0.1).max(0.0),
+                        (Scene::Flocking, piston::Key::N) => s.cohesion += 0.1,
+                        (Scene::Flocking, piston::Key::G) => debug_view = debug_view.next(),
+                        (Scene::Playground, piston::Key::O) => playground.place_obstacle(mouse),
+                        (Scene::Playground, piston::Key::Delete) | (Scene::Playground, piston::Key::Backspace) => playground.clear(),
+                        (Scene::Playground, piston::Key::F1) => playground.set_preset(Preset::Seek),
+                        (Scene::Playground, piston::Key::F2) => playground.set_preset(Preset::Arrive),
+                        (Scene::Playground, piston::Key::F3) => playground.set_preset(Preset::Wander),
+                        (Scene::Playground, piston::Key::F4) => playground.set_preset(Preset::Pursuit),
+                        (Scene::Playground, piston::Key::F5) => playground.set_preset(Preset::Flock),
+                        (Scene::Playground, piston::Key::F6) => playground.set_preset(Preset::Scatter),
+                        (Scene::Playground, piston::Key::LeftBracket) => playground.tune_selected(0.5, 0.0, 0.0),
+                        (Scene::Playground, piston::Key::RightBracket) => playground.tune_selected(2.0, 0.0, 0.0),
+                        (Scene::Playground, piston::Key::Semicolon) => playground.tune_selected(1.0, -0.25, 0.0),
+                        (Scene::Playground, piston::Key::Quote) => playground.tune_selected(1.0, 0.25, 0.0),
+                        (Scene::Playground, piston::Key::Comma) => playground.tune_selected(1.0, 0.0, -1.0),
+                        (Scene::Playground, piston::Key::Period) => playground.tune_selected(1.0, 0.0, 1.0),
+                        _ => (),
+                    }
+                }
+            }
+        } else if let Some(args) = e.update_args() {
+            // Updates come UPDATES_PER_SECOND times a second however fast frames are
+            // drawn, so `args.dt` is the same every time.
+            match scene {
+                Scene::Flocking => flock.update(args.dt),
+                Scene::Maze => crowd.update(args.dt, &mut rng),
+                Scene::Evolution => {
+                    for _ in 0..if fast_forward { 10 } else { 1 } {
+                        generation.step(&population);
+                        if generation.finished() {
+                            let scores = generation.scores(fitness.as_ref());
+                            population.evolve(&scores, &mut rng);
+                            generation = Generation::new(&population, &mut rng);
+                        }
+                    }
+                }
+                Scene::Ecosystem => {
+                    for _ in 0..if fast_forward { 10 } else { 1 } {
+                        ecosystem.update(args.dt, &mut rng);
+                    }
+                }
+                Scene::Playground => playground.update(args.dt),
+            }
         }
     }
 }
@@ -0,0 +1,537 @@
+//! Obstacle maps, A* pathfinding across them, and the routes it produces for
+//! vehicles to follow.
+
+use crate::{steering::closest_on_segment, vector::Vector};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// A* step costs, in tenths of a cell.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// Fraction of the walls left standing in a maze that are knocked down anyway, so
+// there's more than one way between most places.
+const MAZE_LOOPS: f64 = 0.1;
+
+/// A grid of square cells, each either open or blocked. Everything outside the
+/// grid counts as blocked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObstacleMap {
+    pub cols: usize,
+    pub rows: usize,
+    /// Side of a cell in pixels.
+    pub cell: f64,
+    pub blocked: Vec<bool>,
+}
+impl ObstacleMap {
+    pub fn new(cols: usize, rows: usize, cell: f64) -> ObstacleMap {
+        ObstacleMap {
+            cols,
+            rows,
+            cell,
+            blocked: vec![false; cols * rows],
+        }
+    }
+
+    /// Reads a map drawn in text, one line per row, with `#` for blocked cells and
+    /// anything else for open ones. Short lines are open past their end.
+    pub fn parse(text: &str, cell: f64) -> ObstacleMap {
+        let lines: Vec<&str> = text.lines().collect();
+        let cols = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut map = ObstacleMap::new(cols, lines.len(), cell);
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                map.blocked[x + y * cols] = c == '#';
+            }
+        }
+        map
+    }
+
+    /// A maze filling the grid, with corridors `corridor` cells wide between walls
+    /// one cell thick.
+    pub fn maze<R: Rng>(cols: usize, rows: usize, cell: f64, corridor: usize, rng: &mut R) -> ObstacleMap {
+        let mut map = ObstacleMap::new(cols, rows, cell);
+        for b in &mut map.blocked {
+            *b = true;
+        }
+
+        // Rooms are `corridor` square and sit `stride` apart, with a wall between each
+        // and the next. Carving a room, or the wall between two, opens its cells.
+        let stride = corridor + 1;
+        let (rooms_x, rooms_y) = ((cols - 1) / stride, (rows - 1) / stride);
+        if rooms_x == 0 || rooms_y == 0 {
+            return map;
+        }
+        let room = |(x, y): (usize, usize)| [1 + x * stride, 1 + y * stride, corridor, corridor];
+        let wall = |a: (usize, usize), b: (usize, usize)| {
+            let (x, y) = (a.0.min(b.0), a.1.min(b.1));
+            if a.1 == b.1 {
+                [1 + x * stride + corridor, 1 + y * stride, 1, corridor]
+            } else {
+                [1 + x * stride, 1 + y * stride + corridor, corridor, 1]
+            }
+        };
+
+        // A depth first walk through the rooms, opening the wall to each new one.
+        let mut visited = vec![false; rooms_x * rooms_y];
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        map.open(room((0, 0)));
+        while let Some(&(x, y)) = stack.last() {
+            let mut next: Vec<(usize, usize)> = neighbouring_rooms(x, y, rooms_x, rooms_y)
+                .into_iter()
+                .filter(|&(nx, ny)| !visited[nx + ny * rooms_x])
+                .collect();
+            next.shuffle(rng);
+            match next.first() {
+                Some(&(nx, ny)) => {
+                    visited[nx + ny * rooms_x] = true;
+                    map.open(room((nx, ny)));
+                    map.open(wall((x, y), (nx, ny)));
+                    stack.push((nx, ny));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+
+        for y in 0..rooms_y {
+            for x in 0..rooms_x {
+                for &(nx, ny) in &[(x + 1, y), (x, y + 1)] {
+                    if nx < rooms_x && ny < rooms_y && rng.gen_bool(MAZE_LOOPS) {
+                        map.open(wall((x, y), (nx, ny)));
+                    }
+                }
+            }
+        }
+        map
+    }
+
+    // Opens the cells in the rectangle `[x, y, width, height]`.
+    fn open(&mut self, rect: [usize; 4]) {
+        let [x0, y0, width, height] = rect;
+        for y in y0..y0 + height {
+            for x in x0..x0 + width {
+                self.blocked[x + y * self.cols] = false;
+            }
+        }
+    }
+
+    /// Blocks every cell whose center is inside the polygon `points`.
+    pub fn block_polygon(&mut self, points: &[Vector]) {
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                if contains(points, self.center(x as i64, y as i64)) {
+                    self.blocked[x + y * self.cols] = true;
+                }
+            }
+        }
+    }
+
+    pub fn is_blocked(&self, x: i64, y: i64) -> bool {
+        x < 0 || y < 0 || x >= self.cols as i64 || y >= self.rows as i64 || self.blocked[x as usize + y as usize * self.cols]
+    }
+
+    /// The cell `pos` is in, which may be off the grid.
+    pub fn cell_at(&self, pos: Vector) -> (i64, i64) {
+        ((pos.x / self.cell).floor() as i64, (pos.y / self.cell).floor() as i64)
+    }
+
+    pub fn center(&self, x: i64, y: i64) -> Vector {
+        Vector::new((x as f64 + 0.5) * self.cell, (y as f64 + 0.5) * self.cell)
+    }
+
+    pub fn blocked_at(&self, pos: Vector) -> bool {
+        let (x, y) = self.cell_at(pos);
+        self.is_blocked(x, y)
+    }
+
+    /// The center of a random open cell, if there are any.
+    pub fn random_open<R: Rng>(&self, rng: &mut R) -> Option<Vector> {
+        let open: Vec<usize> = (0..self.blocked.len()).filter(|&i| !self.blocked[i]).collect();
+        open.choose(rng)
+            .map(|&i| self.center((i % self.cols) as i64, (i / self.cols) as i64))
+    }
+
+    /// The closest point of each blocked cell within `radius` of `pos`, leaving out
+    /// any cell `pos` is inside.
+    pub fn walls_near(&self, pos: Vector, radius: f64) -> Vec<Vector> {
+        let (min_x, min_y) = self.cell_at(pos - Vector::new(radius, radius));
+        let (max_x, max_y) = self.cell_at(pos + Vector::new(radius, radius));
+        let mut walls = vec![];
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if !self.is_blocked(x, y) {
+                    continue;
+                }
+                let (left, top) = (x as f64 * self.cell, y as f64 * self.cell);
+                let closest = Vector::new(
+                    pos.x.max(left).min(left + self.cell),
+                    pos.y.max(top).min(top + self.cell),
+                );
+                let d = (pos - closest).mag();
+                if d > 0.0 && d < radius {
+                    walls.push(closest);
+                }
+            }
+        }
+        walls
+    }
+
+    /// `pos` moved just far enough that a circle of `radius` around it doesn't
+    /// overlap any wall.
+    pub fn push_out(&self, pos: Vector, radius: f64) -> Vector {
+        let mut pos = pos;
+        for wall in self.walls_near(pos, radius) {
+            let away = pos - wall;
+            let d = away.mag();
+            if d > 0.0 && d < radius {
+                pos += away * ((radius - d) / d);
+            }
+        }
+        pos
+    }
+
+    /// Whether there's a straight way from `a` to `b` that stays `clearance` away
+    /// from blocked cells on either side.
+    pub fn line_of_sight(&self, a: Vector, b: Vector, clearance: f64) -> bool {
+        let along = b - a;
+        let length = along.mag();
+        let side = if length > 0.0 {
+            Vector::new(-along.y, along.x) * (clearance / length)
+        } else {
+            Vector::default()
+        };
+        let steps = (length / (self.cell * 0.25)).ceil().max(1.0) as usize;
+        (0..=steps).all(|i| {
+            let p = a + along * (i as f64 / steps as f64);
+            !self.blocked_at(p) && !self.blocked_at(p + side) && !self.blocked_at(p - side)
+        })
+    }
+
+    /// The shortest way from `start` to `goal` through open cells, moving
+    /// diagonally only where neither side of the corner is blocked. The waypoints
+    /// run from `start` through the center of every cell on the way to `goal`.
+    pub fn find_path(&self, start: Vector, goal: Vector) -> Option<Vec<Vector>> {
+        let (sx, sy) = self.cell_at(start);
+        let (gx, gy) = self.cell_at(goal);
+        if self.is_blocked(sx, sy) || self.is_blocked(gx, gy) {
+            return None;
+        }
+        let index = |x: i64, y: i64| x as usize + y as usize * self.cols;
+        let heuristic = |x: i64, y: i64| {
+            let (dx, dy) = ((x - gx).unsigned_abs() as u32, (y - gy).unsigned_abs() as u32);
+            STRAIGHT_COST * (dx + dy) - (2 * STRAIGHT_COST - DIAGONAL_COST) * dx.min(dy)
+        };
+
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        cost[index(sx, sy)] = 0;
+        open.push(Reverse((heuristic(sx, sy), sx, sy)));
+
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            if (x, y) == (gx, gy) {
+                break;
+            }
+            let here = cost[index(x, y)];
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if (dx, dy) == (0, 0) || self.is_blocked(nx, ny) {
+                        continue;
+                    }
+                    let diagonal = dx != 0 && dy != 0;
+                    if diagonal && (self.is_blocked(x + dx, y) || self.is_blocked(x, y + dy)) {
+                        continue;
+                    }
+                    let step = if diagonal { DIAGONAL_COST } else { STRAIGHT_COST };
+                    let n = index(nx, ny);
+                    if here + step < cost[n] {
+                        cost[n] = here + step;
+                        came_from[n] = index(x, y);
+                        open.push(Reverse((here + step + heuristic(nx, ny), nx, ny)));
+                    }
+                }
+            }
+        }
+
+        let (start_index, goal_index) = (index(sx, sy), index(gx, gy));
+        if cost[goal_index] == u32::MAX {
+            return None;
+        }
+        let mut cells = vec![goal_index];
+        while *cells.last().unwrap() != start_index {
+            cells.push(came_from[*cells.last().unwrap()]);
+        }
+        let mut path = vec![goal];
+        for &i in cells.iter().take(cells.len() - 1).skip(1) {
+            path.push(self.center((i % self.cols) as i64, (i / self.cols) as i64));
+        }
+        path.push(start);
+        path.reverse();
+        Some(path)
+    }
+
+    /// Drops every waypoint that can be skipped by going straight from an earlier
+    /// one with `clearance` to spare, so the path hugs corners instead of zigzagging
+    /// from cell to cell.
+    pub fn smooth(&self, path: &[Vector], clearance: f64) -> Vec<Vector> {
+        if path.len() < 3 {
+            return path.to_vec();
+        }
+        let mut smoothed = vec![path[0]];
+        let mut i = 0;
+        while i < path.len() - 1 {
+            let mut j = path.len() - 1;
+            while j > i + 1 && !self.line_of_sight(path[i], path[j], clearance) {
+                j -= 1;
+            }
+            smoothed.push(path[j]);
+            i = j;
+        }
+        smoothed
+    }
+
+    /// A smoothed route from `start` to `goal` that keeps `clearance` from walls
+    /// wherever it cuts a corner.
+    pub fn route(&self, start: Vector, goal: Vector, clearance: f64) -> Option<Route> {
+        self.find_path(start, goal)
+            .map(|path| Route::new(self.smooth(&path, clearance)))
+    }
+
+    pub fn draw(&self, color: [f32; 4], transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                if self.blocked[x + y * self.cols] {
+                    let (left, top) = (x as f64 * self.cell, y as f64 * self.cell);
+                    rectangle(color, [left, top, self.cell, self.cell], transform, gl);
+                }
+            }
+        }
+    }
+}
+
+// The rooms next to room (x, y) in a maze `cols` by `rows` rooms big.
+fn neighbouring_rooms(x: usize, y: usize, cols: usize, rows: usize) -> Vec<(usize, usize)> {
+    let mut rooms = vec![];
+    if x > 0 {
+        rooms.push((x - 1, y));
+    }
+    if y > 0 {
+        rooms.push((x, y - 1));
+    }
+    if x + 1 < cols {
+        rooms.push((x + 1, y));
+    }
+    if y + 1 < rows {
+        rooms.push((x, y + 1));
+    }
+    rooms
+}
+
+// Whether `p` is inside the polygon `points`, by counting the edges a ray to its
+// right crosses.
+fn contains(points: &[Vector], p: Vector) -> bool {
+    let mut inside = false;
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Waypoints to go through in order, and how far along them a vehicle has got.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub waypoints: Vec<Vector>,
+    /// The waypoint being headed for. Once it's past the last one the route is done.
+    pub next: usize,
+}
+impl Route {
+    pub fn new(waypoints: Vec<Vector>) -> Route {
+        Route { waypoints, next: 1 }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.waypoints.len()
+    }
+
+    pub fn goal(&self) -> Option<Vector> {
+        self.waypoints.last().copied()
+    }
+
+    /// Moves on past every waypoint `pos` is within `reach` of, or has gone past
+    /// along the segment leading to it.
+    pub fn advance(&mut self, pos: Vector, reach: f64) {
+        while !self.finished() {
+            let b = self.waypoints[self.next];
+            let a = self.waypoints[self.next - 1];
+            let passed = (b - a).dot(&(pos - b)) > 0.0;
+            if (b - pos).mag() >= reach && !passed {
+                break;
+            }
+            self.next += 1;
+        }
+    }
+
+    /// The point `distance` further along the route from the closest point on the
+    /// segment being followed, stopping at the goal.
+    pub fn ahead(&self, pos: Vector, distance: f64) -> Vector {
+        if self.finished() {
+            return self.goal().unwrap_or(pos);
+        }
+        let mut from = closest_on_segment(pos, self.waypoints[self.next - 1], self.waypoints[self.next]);
+        let mut left = distance;
+        for &to in &self.waypoints[self.next..] {
+            let d = (to - from).mag();
+            if d >= left {
+                return from + (to - from) * (left / d);
+            }
+            left -= d;
+            from = to;
+        }
+        from
+    }
+
+    /// How far it is from `pos` to the goal along what's left of the route.
+    pub fn remaining(&self, pos: Vector) -> f64 {
+        let mut from = pos;
+        let mut total = 0.0;
+        for &to in self.waypoints.iter().skip(self.next) {
+            total += (to - from).mag();
+            from = to;
+        }
+        total
+    }
+
+    /// Draws what's left of the route from `pos`.
+    pub fn draw(&self, pos: Vector, color: [f32; 4], transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let mut from = pos;
+        for &to in self.waypoints.iter().skip(self.next) {
+            line(color, 0.5, [from.x, from.y, to.x, to.y], transform, gl);
+            from = to;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cells are 10 pixels, so cell (x, y) is centered on (10x + 5, 10y + 5).
+    fn map(text: &str) -> ObstacleMap {
+        ObstacleMap::parse(text, 10.0)
+    }
+
+    fn at(x: i64, y: i64) -> Vector {
+        Vector::new(x as f64 * 10.0 + 5.0, y as f64 * 10.0 + 5.0)
+    }
+
+    fn length(path: &[Vector]) -> f64 {
+        path.windows(2).map(|w| (w[1] - w[0]).mag()).sum()
+    }
+
+    #[test]
+    fn parse_reads_blocked_cells() {
+        let map = map("..#\n#\n");
+        assert_eq!((map.cols, map.rows), (3, 2));
+        assert_eq!(map.blocked, vec![false, false, true, true, false, false]);
+        assert!(map.is_blocked(-1, 0) && map.is_blocked(0, 2));
+    }
+
+    #[test]
+    fn find_path_takes_the_shortest_way() {
+        let open = map("....\n....\n....");
+        let path = open.find_path(at(0, 0), at(3, 2)).unwrap();
+        // Two diagonal steps and one straight one.
+        assert_eq!(path.len(), 4);
+        assert!((length(&path) - (20.0 * 2f64.sqrt() + 10.0)).abs() < 1e-9);
+
+        let wall = map(".....\n.###.\n.....");
+        let path = wall.find_path(at(0, 1), at(4, 1)).unwrap();
+        assert_eq!(path.first(), Some(&at(0, 1)));
+        assert_eq!(path.last(), Some(&at(4, 1)));
+        assert!((length(&path) - 60.0).abs() < 1e-9, "{:?}", path);
+    }
+
+    #[test]
+    fn find_path_does_not_cut_blocked_corners() {
+        let path = map(".#\n..").find_path(at(0, 0), at(1, 1)).unwrap();
+        assert_eq!(path, vec![at(0, 0), at(0, 1), at(1, 1)]);
+
+        assert_eq!(map(".#\n#.").find_path(at(0, 0), at(1, 1)), None);
+    }
+
+    #[test]
+    fn find_path_fails_for_unreachable_or_blocked_goals() {
+        let split = map("..#..\n..#..");
+        assert_eq!(split.find_path(at(0, 0), at(4, 1)), None);
+        assert_eq!(split.find_path(at(0, 0), at(2, 0)), None);
+        assert_eq!(split.find_path(at(2, 1), at(0, 0)), None);
+        assert_eq!(split.find_path(at(0, 0), at(-1, 0)), None);
+        assert_eq!(split.find_path(at(0, 0), at(1, 1)).map(|p| p.len()), Some(2));
+    }
+
+    #[test]
+    fn smooth_cuts_corners_with_clearance() {
+        let map = map(".......\n.......\n...#...\n.......\n.......");
+        let (start, goal) = (at(0, 2), at(6, 2));
+        let path = map.find_path(start, goal).unwrap();
+        let clearance = 3.0;
+        let smoothed = map.smooth(&path, clearance);
+
+        assert_eq!(smoothed.first(), Some(&start));
+        assert_eq!(smoothed.last(), Some(&goal));
+        assert!(smoothed.len() < path.len(), "{:?}", smoothed);
+        for w in smoothed.windows(2) {
+            assert!(map.line_of_sight(w[0], w[1], clearance), "{:?}", w);
+            // Nowhere along the way is closer than `clearance` to the blocked cell.
+            for i in 0..=100 {
+                let p = w[0] + (w[1] - w[0]) * (i as f64 / 100.0);
+                let closest = Vector::new(p.x.clamp(30.0, 40.0), p.y.clamp(20.0, 30.0));
+                assert!((p - closest).mag() >= clearance - 1e-9, "{:?} on {:?}", p, w);
+            }
+        }
+
+        // With nothing in the way it goes straight there.
+        let open = ObstacleMap::new(6, 6, 10.0);
+        let path = open.find_path(at(0, 0), at(5, 3)).unwrap();
+        assert_eq!(open.smooth(&path, clearance), vec![at(0, 0), at(5, 3)]);
+    }
+
+    #[test]
+    fn advance_skips_waypoints_that_have_been_passed() {
+        let waypoints = vec![
+            Vector::new(0.0, 0.0),
+            Vector::new(10.0, 0.0),
+            Vector::new(20.0, 0.0),
+            Vector::new(20.0, 10.0),
+        ];
+        let mut route = Route::new(waypoints);
+
+        route.advance(Vector::new(5.0, 0.0), 1.0);
+        assert_eq!(route.next, 1);
+        // Overshot the first waypoint without coming within reach of it.
+        route.advance(Vector::new(15.0, 3.0), 1.0);
+        assert_eq!(route.next, 2);
+        // Within reach of the next one.
+        route.advance(Vector::new(19.5, 0.0), 1.0);
+        assert_eq!(route.next, 3);
+        // Past everything at once.
+        route.advance(Vector::new(20.0, 30.0), 1.0);
+        assert!(route.finished());
+        assert_eq!(route.goal(), Some(Vector::new(20.0, 10.0)));
+    }
+}
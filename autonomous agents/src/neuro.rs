@@ -0,0 +1,306 @@
+//! Small feed-forward neural networks, and a genetic algorithm that evolves a
+//! population of them.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+// How many of the best genomes ever found are kept.
+const HALL_OF_FAME: usize = 10;
+
+/// A fully connected network with tanh activations. Its weights are its genome.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Network {
+    /// How many neurons each layer has, inputs first.
+    pub layers: Vec<usize>,
+    /// For each neuron after the inputs, layer by layer, its bias followed by the
+    /// weight of each neuron in the layer before.
+    pub weights: Vec<f64>,
+}
+impl Network {
+    pub fn weight_count(layers: &[usize]) -> usize {
+        layers.windows(2).map(|w| (w[0] + 1) * w[1]).sum()
+    }
+
+    pub fn random<R: Rng>(layers: &[usize], rng: &mut R) -> Network {
+        Network {
+            layers: layers.to_vec(),
+            weights: (0..Network::weight_count(layers)).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+        }
+    }
+
+    /// The outputs for `inputs`, each between -1 and 1.
+    pub fn forward(&self, inputs: &[f64]) -> Vec<f64> {
+        debug_assert_eq!(inputs.len(), self.layers[0]);
+        let mut values = inputs.to_vec();
+        let mut w = 0;
+        for &size in &self.layers[1..] {
+            let mut next = Vec::with_capacity(size);
+            for _ in 0..size {
+                let mut sum = self.weights[w];
+                w += 1;
+                for v in &values {
+                    sum += self.weights[w] * v;
+                    w += 1;
+                }
+                next.push(sum.tanh());
+            }
+            values = next;
+        }
+        values
+    }
+}
+
+/// A network and how well it did.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    pub network: Network,
+    pub fitness: f64,
+}
+
+/// Saves genomes as JSON.
+pub fn save_genomes<P: AsRef<Path>>(path: P, genomes: &[Genome]) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(genomes)?)
+}
+
+pub fn load_genomes<P: AsRef<Path>>(path: P) -> io::Result<Vec<Genome>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeneticSettings {
+    pub population: usize,
+    /// How many of the fittest are copied unchanged into the next generation.
+    pub elite: usize,
+    /// How many random networks compete to be picked as each parent.
+    pub tournament: usize,
+    /// Chance of each weight of a child being mutated.
+    pub mutation_rate: f64,
+    /// Standard deviation of the noise added to a mutated weight.
+    pub mutation_strength: f64,
+}
+impl Default for GeneticSettings {
+    fn default() -> GeneticSettings {
+        GeneticSettings {
+            population: 100,
+            elite: 4,
+            tournament: 3,
+            mutation_rate: 0.1,
+            mutation_strength: 0.3,
+        }
+    }
+}
+
+pub struct Population {
+    pub networks: Vec<Network>,
+    pub settings: GeneticSettings,
+    pub generation: u32,
+    /// The best genomes of any generation so far, best first.
+    pub hall_of_fame: Vec<Genome>,
+}
+impl Population {
+    pub fn random<R: Rng>(layers: &[usize], settings: GeneticSettings, rng: &mut R) -> Population {
+        Population {
+            networks: (0..settings.population).map(|_| Network::random(layers, rng)).collect(),
+            settings,
+            generation: 0,
+            hall_of_fame: vec![],
+        }
+    }
+
+    /// A population started from saved genomes, fittest first, topped up with
+    /// mutated copies of them. Genomes with a different shape to `layers` are left
+    /// out, and if none are left the population is random.
+    pub fn from_genomes<R: Rng>(
+        genomes: Vec<Genome>,
+        layers: &[usize],
+        settings: GeneticSettings,
+        rng: &mut R,
+    ) -> Population {
+        let mut genomes: Vec<Genome> = genomes
+            .into_iter()
+            .filter(|g| g.network.layers == layers && g.network.weights.len() == Network::weight_count(layers))
+            .collect();
+        if genomes.is_empty() {
+            return Population::random(layers, settings, rng);
+        }
+        genomes.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+        let mut networks: Vec<Network> = genomes.iter().take(settings.population).map(|g| g.network.clone()).collect();
+        while networks.len() < settings.population {
+            let parent = &genomes[rng.gen_range(0..genomes.len())].network;
+            networks.push(mutate(parent, &settings, rng));
+        }
+        let mut hall_of_fame = genomes;
+        hall_of_fame.truncate(HALL_OF_FAME);
+        Population {
+            networks,
+            settings,
+            generation: 0,
+            hall_of_fame,
+        }
+    }
+
+    pub fn best(&self) -> Option<&Genome> {
+        self.hall_of_fame.first()
+    }
+
+    /// Replaces the population with its children, given how fit each network was.
+    /// The fittest are kept as they are and the rest are bred from parents picked
+    /// by tournament.
+    pub fn evolve<R: Rng>(&mut self, fitness: &[f64], rng: &mut R) {
+        debug_assert_eq!(fitness.len(), self.networks.len());
+        let mut ranked: Vec<usize> = (0..self.networks.len()).collect();
+        ranked.sort_by(|&a, &b| fitness[b].partial_cmp(&fitness[a]).unwrap());
+
+        for &i in ranked.iter().take(HALL_OF_FAME) {
+            // Elites come back unchanged, so only their best score is kept.
+            let network = &self.networks[i];
+            if let Some(known) = self.hall_of_fame.iter_mut().find(|g| g.network == *network) {
+                known.fitness = known.fitness.max(fitness[i]);
+                continue;
+            }
+            self.hall_of_fame.push(Genome {
+                network: network.clone(),
+                fitness: fitness[i],
+            });
+        }
+        self.hall_of_fame.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+        self.hall_of_fame.truncate(HALL_OF_FAME);
+
+        let s = self.settings;
+        let pick = |rng: &mut R| {
+            (0..s.tournament.max(1))
+                .map(|_| rng.gen_range(0..self.networks.len()))
+                .max_by(|&a, &b| fitness[a].partial_cmp(&fitness[b]).unwrap())
+                .unwrap()
+        };
+        let mut next: Vec<Network> = ranked.iter().take(s.elite).map(|&i| self.networks[i].clone()).collect();
+        while next.len() < s.population {
+            let (a, b) = (&self.networks[pick(rng)], &self.networks[pick(rng)]);
+            let child = Network {
+                layers: a.layers.clone(),
+                weights: a
+                    .weights
+                    .iter()
+                    .zip(&b.weights)
+                    .map(|(&x, &y)| if rng.gen_bool(0.5) { x } else { y })
+                    .collect(),
+            };
+            next.push(mutate(&child, &s, rng));
+        }
+        self.networks = next;
+        self.generation += 1;
+    }
+}
+
+// A copy of `network` with some of its weights nudged.
+fn mutate<R: Rng>(network: &Network, settings: &GeneticSettings, rng: &mut R) -> Network {
+    let mut child = network.clone();
+    for w in &mut child.weights {
+        if rng.gen_bool(settings.mutation_rate) {
+            *w += gaussian(rng) * settings.mutation_strength;
+        }
+    }
+    child
+}
+
+// A normally distributed random number with mean 0 and standard deviation 1.
+fn gaussian<R: Rng>(rng: &mut R) -> f64 {
+    let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let v: f64 = rng.gen_range(0.0..1.0);
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn genome(layers: &[usize], fitness: f64, rng: &mut StdRng) -> Genome {
+        Genome {
+            network: Network::random(layers, rng),
+            fitness,
+        }
+    }
+
+    #[test]
+    fn weight_count_has_a_bias_per_neuron() {
+        assert_eq!(Network::weight_count(&[2, 1]), 3);
+        assert_eq!(Network::weight_count(&[2, 3, 1]), 3 * 3 + 4);
+        assert_eq!(Network::weight_count(&[4]), 0);
+    }
+
+    #[test]
+    fn forward_reads_each_bias_before_its_weights() {
+        let single = Network {
+            layers: vec![2, 1],
+            weights: vec![0.5, 1.0, -2.0],
+        };
+        assert_eq!(single.forward(&[0.3, 0.1]), vec![(0.5 + 0.3 - 0.2f64).tanh()]);
+
+        // Two hidden neurons, then the output, layer by layer.
+        let layered = Network {
+            layers: vec![1, 2, 1],
+            weights: vec![0.1, 2.0, -0.3, 1.0, 0.2, 0.5, -1.5],
+        };
+        let x = 0.4f64;
+        let (h0, h1) = ((0.1 + 2.0 * x).tanh(), (-0.3 + x).tanh());
+        assert_eq!(layered.forward(&[x]), vec![(0.2 + 0.5 * h0 - 1.5 * h1).tanh()]);
+    }
+
+    #[test]
+    fn genomes_round_trip_through_json() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let genomes = vec![genome(&[3, 4, 2], 12.5, &mut rng), genome(&[3, 4, 2], -0.25, &mut rng)];
+        let path = std::env::temp_dir().join(format!("genomes_{}.json", std::process::id()));
+        save_genomes(&path, &genomes).unwrap();
+        let loaded = load_genomes(&path);
+        fs::remove_file(&path).unwrap();
+        // serde_json may be off by an ulp on the way back in.
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.len(), genomes.len());
+        for (a, b) in loaded.iter().zip(&genomes) {
+            assert_eq!(a.fitness, b.fitness);
+            assert_eq!(a.network.layers, b.network.layers);
+            assert_eq!(a.network.weights.len(), b.network.weights.len());
+            assert!(a.network.weights.iter().zip(&b.network.weights).all(|(x, y)| (x - y).abs() < 1e-12));
+        }
+    }
+
+    #[test]
+    fn from_genomes_drops_genomes_of_the_wrong_shape() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let layers = [2, 3, 1];
+        let mut short = genome(&layers, 9.0, &mut rng);
+        short.network.weights.pop();
+        let genomes = vec![
+            genome(&[2, 4, 1], 10.0, &mut rng),
+            short,
+            genome(&layers, 1.0, &mut rng),
+        ];
+        let settings = GeneticSettings::default();
+
+        let population = Population::from_genomes(genomes.clone(), &layers, settings, &mut rng);
+        assert_eq!(population.hall_of_fame, vec![genomes[2].clone()]);
+        assert_eq!(population.networks.len(), settings.population);
+        assert!(population.networks.iter().all(|n| n.layers == layers));
+
+        let random = Population::from_genomes(genomes[..2].to_vec(), &layers, settings, &mut rng);
+        assert!(random.hall_of_fame.is_empty());
+        assert_eq!(random.networks.len(), settings.population);
+    }
+
+    #[test]
+    fn from_genomes_ranks_the_hall_of_fame() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let layers = [2, 1];
+        let genomes: Vec<Genome> = (0..15).map(|i| genome(&layers, ((i * 7) % 15) as f64, &mut rng)).collect();
+        let settings = GeneticSettings::default();
+
+        let population = Population::from_genomes(genomes, &layers, settings, &mut rng);
+        let fitness: Vec<f64> = population.hall_of_fame.iter().map(|g| g.fitness).collect();
+        assert_eq!(fitness, (5..15).rev().map(|f| f as f64).collect::<Vec<_>>());
+        assert_eq!(population.best().unwrap().fitness, 14.0);
+        assert_eq!(population.networks[0], population.best().unwrap().network);
+    }
+}
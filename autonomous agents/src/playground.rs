@@ -0,0 +1,401 @@
+//! A sandbox for trying out steering behaviors: targets and obstacles are placed
+//! with the mouse, presets pick which behaviors the vehicles use, and a selected
+//! vehicle shows what each of its behaviors is asking for.
+
+use crate::{
+    steering::Obstacle, vector::Vector, vehicle::Vehicle, BLUE, CYAN, GRAY, GREEN, LIGHT_BLUE,
+    LIGHT_GREEN, LIGHT_RED, MAGENTA, RED, WHITE, WINDOW_HEIGHT, WINDOW_WIDTH, YELLOW,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use rand::Rng;
+
+// Vehicles flee from flee targets closer than this, and the quarry in the pursuit
+// preset from pursuers closer than this.
+const FLEE_RADIUS: f64 = 200.0;
+
+// Neighbourhoods for the flocking behaviors.
+const SEPARATION_RADIUS: f64 = 30.0;
+const FLOCK_RADIUS: f64 = 80.0;
+
+// Containment turns vehicles back this far from the edge of the window.
+const CONTAIN_MARGIN: f64 = 50.0;
+
+// The ranges vehicles' mass, drag and turn rate are picked from, and can be tuned
+// within.
+const MASS: (f64, f64) = (0.25, 8.0);
+const DRAG: (f64, f64) = (0.0, 3.0);
+const TURN_RATE: (f64, f64) = (1.0, 12.0);
+
+// The inspector draws velocities as where they'd take the vehicle in this many
+// seconds, and forces as where they'd take its velocity.
+const VELOCITY_SCALE: f64 = 0.3;
+const FORCE_SCALE: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Behavior {
+    Seek,
+    Flee,
+    Arrive,
+    Pursue,
+    Evade,
+    Wander,
+    Avoid,
+    Contain,
+    Separate,
+    Align,
+    Cohere,
+}
+impl Behavior {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Behavior::Seek => "Seek",
+            Behavior::Flee => "Flee",
+            Behavior::Arrive => "Arrive",
+            Behavior::Pursue => "Pursue",
+            Behavior::Evade => "Evade",
+            Behavior::Wander => "Wander",
+            Behavior::Avoid => "Avoid",
+            Behavior::Contain => "Contain",
+            Behavior::Separate => "Separate",
+            Behavior::Align => "Align",
+            Behavior::Cohere => "Cohere",
+        }
+    }
+
+    /// The color the inspector draws this behavior's force in.
+    pub fn color(&self) -> [f32; 4] {
+        match self {
+            Behavior::Seek | Behavior::Arrive | Behavior::Pursue => GREEN,
+            Behavior::Flee | Behavior::Evade => LIGHT_RED,
+            Behavior::Wander => MAGENTA,
+            Behavior::Avoid => CYAN,
+            Behavior::Contain => GRAY,
+            Behavior::Separate => RED,
+            Behavior::Align => LIGHT_GREEN,
+            Behavior::Cohere => LIGHT_BLUE,
+        }
+    }
+}
+
+/// A set of weighted behaviors every vehicle uses, picked from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preset {
+    /// Head for the closest seek target, or the mouse when there are none.
+    Seek,
+    /// Like seek, but slowing down to stop on the target.
+    Arrive,
+    Wander,
+    /// The first vehicle wanders and runs, everyone else chases it.
+    Pursuit,
+    Flock,
+    /// The mouse is a flee target that follows the cursor.
+    Scatter,
+}
+impl Preset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Seek => "Seek",
+            Preset::Arrive => "Arrive",
+            Preset::Wander => "Wander",
+            Preset::Pursuit => "Pursuit",
+            Preset::Flock => "Flock",
+            Preset::Scatter => "Scatter",
+        }
+    }
+
+    /// The weighted behaviors of a vehicle, which for pursuit depend on whether it's
+    /// the `quarry`.
+    pub fn behaviors(&self, quarry: bool) -> Vec<(Behavior, f64)> {
+        use Behavior::*;
+        match self {
+            Preset::Seek => vec![(Avoid, 3.0), (Flee, 2.0), (Seek, 1.0), (Separate, 1.5), (Contain, 2.0)],
+            Preset::Arrive => vec![(Avoid, 3.0), (Flee, 2.0), (Arrive, 1.0), (Separate, 1.5), (Contain, 2.0)],
+            Preset::Wander => vec![(Avoid, 3.0), (Flee, 2.0), (Wander, 1.0), (Contain, 2.0)],
+            Preset::Pursuit if quarry => vec![(Avoid, 3.0), (Evade, 1.5), (Wander, 1.0), (Contain, 2.0)],
+            Preset::Pursuit => vec![(Avoid, 3.0), (Pursue, 1.0), (Separate, 1.5), (Contain, 2.0)],
+            Preset::Flock => vec![
+                (Avoid, 3.0),
+                (Flee, 2.0),
+                (Separate, 1.5),
+                (Align, 1.0),
+                (Cohere, 1.0),
+                (Wander, 0.3),
+                (Contain, 2.0),
+            ],
+            Preset::Scatter => vec![(Avoid, 3.0), (Flee, 2.0), (Wander, 1.0), (Separate, 1.5), (Contain, 2.0)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetKind {
+    Seek,
+    Flee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target {
+    pub pos: Vector,
+    pub kind: TargetKind,
+}
+
+/// What a vehicle's behaviors asked for in its last update.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SteeringReport {
+    /// Each behavior and its force before weighting.
+    pub behaviors: Vec<(Behavior, Vector)>,
+    /// The weighted total, capped at the vehicle's `max_force`.
+    pub force: Vector,
+    /// The velocity that total is steering towards.
+    pub desired: Vector,
+}
+
+pub struct Playground {
+    pub vehicles: Vec<Vehicle>,
+    pub targets: Vec<Target>,
+    pub obstacles: Vec<Obstacle>,
+    pub preset: Preset,
+    pub selected: Option<usize>,
+    /// What each vehicle did last update, by index.
+    pub reports: Vec<SteeringReport>,
+    /// Where the mouse is, which seek uses when there are no seek targets and
+    /// scatter flees from.
+    pub mouse: Vector,
+}
+impl Playground {
+    pub fn new(count: usize) -> Playground {
+        let mut rng = rand::thread_rng();
+        let vehicles: Vec<Vehicle> = (0..count)
+            .map(|_| {
+                let mut v = Vehicle::new(
+                    rng.gen_range(0.0..WINDOW_WIDTH as f64),
+                    rng.gen_range(0.0..WINDOW_HEIGHT as f64),
+                    LIGHT_BLUE,
+                );
+                v.max_force = 600.0;
+                v.mass = rng.gen_range(0.5..3.0);
+                v.drag = rng.gen_range(0.0..1.5);
+                v.max_turn_rate = rng.gen_range(2.0..8.0);
+                v.vel = Vector::from_angle(rng.gen_range(0.0..std::f64::consts::PI * 2.0)) * v.max_speed;
+                v
+            })
+            .collect();
+        let mut playground = Playground {
+            reports: vec![SteeringReport::default(); vehicles.len()],
+            vehicles,
+            targets: vec![],
+            obstacles: vec![],
+            preset: Preset::Seek,
+            selected: None,
+            mouse: Vector::new(WINDOW_WIDTH as f64 / 2.0, WINDOW_HEIGHT as f64 / 2.0),
+        };
+        playground.set_preset(Preset::Seek);
+        playground
+    }
+
+    pub fn set_preset(&mut self, preset: Preset) {
+        self.preset = preset;
+        for (i, v) in self.vehicles.iter_mut().enumerate() {
+            v.color = if preset == Preset::Pursuit && i == 0 { YELLOW } else { LIGHT_BLUE };
+        }
+    }
+
+    pub fn place_target(&mut self, pos: Vector, kind: TargetKind) {
+        self.targets.push(Target { pos, kind });
+    }
+
+    pub fn place_obstacle(&mut self, pos: Vector) {
+        let r = rand::thread_rng().gen_range(30.0..70.0);
+        self.obstacles.push(Obstacle::new(pos.x, pos.y, r));
+    }
+
+    /// Removes every target and obstacle.
+    pub fn clear(&mut self) {
+        self.targets.clear();
+        self.obstacles.clear();
+    }
+
+    /// Selects the vehicle under `pos`, or deselects it if it was already
+    /// selected. Returns whether there was a vehicle there.
+    pub fn select_at(&mut self, pos: Vector) -> bool {
+        let hit = self
+            .vehicles
+            .iter()
+            .position(|v| (v.pos - pos).mag() < v.r * 1.5);
+        match hit {
+            Some(i) if self.selected == Some(i) => self.selected = None,
+            Some(i) => self.selected = Some(i),
+            None => return false,
+        }
+        true
+    }
+
+    /// Changes the selected vehicle's physics, multiplying its mass by `mass` and
+    /// adding `drag` and `turn_rate` to its drag and turn rate.
+    pub fn tune_selected(&mut self, mass: f64, drag: f64, turn_rate: f64) {
+        if let Some(i) = self.selected {
+            let v = &mut self.vehicles[i];
+            v.mass = (v.mass * mass).max(MASS.0).min(MASS.1);
+            v.drag = (v.drag + drag).max(DRAG.0).min(DRAG.1);
+            v.max_turn_rate = (v.max_turn_rate + turn_rate).max(TURN_RATE.0).min(TURN_RATE.1);
+        }
+    }
+
+    // The closest target of `kind` to `pos`.
+    fn closest_target(&self, pos: Vector, kind: TargetKind) -> Option<Vector> {
+        self.targets
+            .iter()
+            .filter(|t| t.kind == kind)
+            .map(|t| t.pos)
+            .min_by(|a, b| (*a - pos).mag().partial_cmp(&(*b - pos).mag()).unwrap())
+    }
+
+    // The force `behavior` asks vehicle `i` for. `wander` is worked out beforehand
+    // since wandering changes the vehicle.
+    fn force(&self, i: usize, behavior: Behavior, wander: Vector) -> Vector {
+        let v = &self.vehicles[i];
+        let seek_target = || self.closest_target(v.pos, TargetKind::Seek).unwrap_or(self.mouse);
+        match behavior {
+            Behavior::Seek => v.seek(seek_target()),
+            Behavior::Arrive => v.arrive(seek_target()),
+            Behavior::Flee => {
+                let mut threats: Vec<Vector> = self
+                    .targets
+                    .iter()
+                    .filter(|t| t.kind == TargetKind::Flee)
+                    .map(|t| t.pos)
+                    .collect();
+                if self.preset == Preset::Scatter {
+                    threats.push(self.mouse);
+                }
+                let mut force = Vector::default();
+                for threat in threats {
+                    if (threat - v.pos).mag() < FLEE_RADIUS {
+                        force += v.flee(threat);
+                    }
+                }
+                force
+            }
+            Behavior::Pursue if i != 0 => v.persue(&self.vehicles[0]),
+            Behavior::Evade => self.vehicles[1..]
+                .iter()
+                .filter(|other| (other.pos - v.pos).mag() < FLEE_RADIUS)
+                .min_by(|a, b| (a.pos - v.pos).mag().partial_cmp(&(b.pos - v.pos).mag()).unwrap())
+                .map_or(Vector::default(), |pursuer| v.evade(pursuer)),
+            Behavior::Pursue => Vector::default(),
+            Behavior::Wander => wander,
+            Behavior::Avoid => v.avoid_obstacles(&self.obstacles),
+            Behavior::Contain => v.contain(
+                [0.0, 0.0, WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64],
+                CONTAIN_MARGIN,
+            ),
+            Behavior::Separate => v.separate(&self.vehicles, SEPARATION_RADIUS),
+            Behavior::Align => v.align(&self.vehicles, FLOCK_RADIUS),
+            Behavior::Cohere => v.cohere(&self.vehicles, FLOCK_RADIUS),
+        }
+    }
+
+    /// Moves everything on by `dt` seconds.
+    pub fn update(&mut self, dt: f64) {
+        for i in 0..self.vehicles.len() {
+            let behaviors = self.preset.behaviors(self.preset == Preset::Pursuit && i == 0);
+            let wander = if behaviors.iter().any(|(b, _)| *b == Behavior::Wander) {
+                self.vehicles[i].wander(dt)
+            } else {
+                Vector::default()
+            };
+            let forces: Vec<(Behavior, f64, Vector)> = behaviors
+                .iter()
+                .map(|&(b, weight)| (b, weight, self.force(i, b, wander)))
+                .collect();
+            let v = &self.vehicles[i];
+            let weighted: Vec<(f64, Vector)> = forces.iter().map(|&(_, w, f)| (w, f)).collect();
+            let force = v.combine_weighted(&weighted);
+            self.reports[i] = SteeringReport {
+                behaviors: forces.iter().map(|&(b, _, f)| (b, f)).collect(),
+                force,
+                desired: v.desired_velocity(force),
+            };
+        }
+        for (v, report) in self.vehicles.iter_mut().zip(&self.reports) {
+            v.apply_force(report.force);
+            v.update(dt);
+            v.edges();
+        }
+    }
+
+    /// A line about the selected vehicle, for the window title.
+    pub fn describe_selected(&self) -> Option<String> {
+        let i = self.selected?;
+        let (v, report) = (&self.vehicles[i], &self.reports[i]);
+        let active: Vec<String> = report
+            .behaviors
+            .iter()
+            .filter(|(_, f)| f.mag() > 0.0)
+            .map(|(b, f)| format!("{} {:.0}", b.name(), f.mag()))
+            .collect();
+        Some(format!(
+            "Vehicle {} | speed {:.0}/{:.0} | desired {:.0} | steering {:.0}/{:.0} | mass {:.2} | drag {:.2} | turn {:.0} deg/s | {}",
+            i,
+            v.vel.mag(),
+            v.max_speed,
+            report.desired.mag(),
+            report.force.mag(),
+            v.max_force,
+            v.mass,
+            v.drag,
+            v.max_turn_rate.to_degrees(),
+            if active.is_empty() { "idle".to_string() } else { active.join(", ") }
+        ))
+    }
+
+    pub fn draw(&self, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        for o in &self.obstacles {
+            ellipse(GRAY, [o.pos.x - o.r, o.pos.y - o.r, 2.0 * o.r, 2.0 * o.r], transform, gl);
+        }
+        for t in &self.targets {
+            let color = match t.kind {
+                TargetKind::Seek => GREEN,
+                TargetKind::Flee => RED,
+            };
+            Ellipse::new_border(color, 1.5).draw([t.pos.x - 10.0, t.pos.y - 10.0, 20.0, 20.0], &Default::default(), transform, gl);
+            line(color, 1.0, [t.pos.x - 14.0, t.pos.y, t.pos.x + 14.0, t.pos.y], transform, gl);
+            line(color, 1.0, [t.pos.x, t.pos.y - 14.0, t.pos.x, t.pos.y + 14.0], transform, gl);
+        }
+        for v in &self.vehicles {
+            v.draw(transform, gl);
+        }
+        if let Some(i) = self.selected {
+            self.draw_inspector(i, transform, gl);
+        }
+    }
+
+    // The selected vehicle's velocity in white, the velocity it wants in yellow,
+    // its total steering force in blue and each active behavior's force in that
+    // behavior's color.
+    fn draw_inspector(&self, i: usize, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let (v, report) = (&self.vehicles[i], &self.reports[i]);
+        let p = v.pos;
+        let r = v.r * 1.8;
+        Ellipse::new_border(YELLOW, 1.0).draw([p.x - r, p.y - r, 2.0 * r, 2.0 * r], &Default::default(), transform, gl);
+
+        let arrow = |color: [f32; 4], radius: f64, to: Vector, gl: &mut GlGraphics| {
+            let end = p + to;
+            line(color, radius, [p.x, p.y, end.x, end.y], transform, gl);
+            ellipse(color, [end.x - 2.5, end.y - 2.5, 5.0, 5.0], transform, gl);
+        };
+        for (behavior, force) in &report.behaviors {
+            if force.mag() > 0.0 {
+                arrow(behavior.color(), 0.75, *force * FORCE_SCALE, gl);
+            }
+        }
+        arrow(WHITE, 1.0, v.vel * VELOCITY_SCALE, gl);
+        arrow(YELLOW, 1.0, report.desired * VELOCITY_SCALE, gl);
+        arrow(BLUE, 1.5, report.force * FORCE_SCALE, gl);
+    }
+}
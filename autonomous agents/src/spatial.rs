@@ -0,0 +1,76 @@
+//! A spatial hash for finding nearby vehicles without checking every pair.
+
+use crate::vector::Vector;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+// Hashes cell coordinates with a multiply and rotate, which is all they need and
+// much quicker than the default hasher for the many lookups a query makes.
+#[derive(Debug, Clone, Copy, Default)]
+struct CellHasher(u64);
+impl Hasher for CellHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(b as u64);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write_u64(n as u64);
+    }
+}
+
+/// Buckets points by the square cell of side `cell` they fall in, so a query
+/// only looks at the cells its radius overlaps.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialHash {
+    pub cell: f64,
+    cells: HashMap<(i64, i64), Vec<usize>, BuildHasherDefault<CellHasher>>,
+}
+impl SpatialHash {
+    pub fn new(cell: f64) -> SpatialHash {
+        SpatialHash {
+            cell,
+            cells: HashMap::default(),
+        }
+    }
+
+    fn key(&self, pos: Vector) -> (i64, i64) {
+        ((pos.x / self.cell).floor() as i64, (pos.y / self.cell).floor() as i64)
+    }
+
+    /// Replaces everything in the hash with `points`, stored by their index.
+    pub fn rebuild<I: IntoIterator<Item = Vector>>(&mut self, points: I) {
+        for bucket in self.cells.values_mut() {
+            bucket.clear();
+        }
+        for (i, pos) in points.into_iter().enumerate() {
+            let key = self.key(pos);
+            self.cells.entry(key).or_default().push(i);
+        }
+    }
+
+    /// Indices of every stored point in the cells within `radius` of `pos`. Some
+    /// may be a little further than `radius`, so callers still check distances.
+    pub fn query(&self, pos: Vector, radius: f64) -> Vec<usize> {
+        let (min_x, min_y) = self.key(pos - Vector::new(radius, radius));
+        let (max_x, max_y) = self.key(pos + Vector::new(radius, radius));
+        let mut found = vec![];
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if let Some(bucket) = self.cells.get(&(x, y)) {
+                    found.extend_from_slice(bucket);
+                }
+            }
+        }
+        found
+    }
+}
@@ -0,0 +1,170 @@
+//! The things steering behaviors react to, and ways of combining several
+//! behaviors into one steering force.
+
+use crate::vector::Vector;
+
+/// A route made of straight segments, with a width vehicles should stay within.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub points: Vec<Vector>,
+    /// How far from the center line still counts as being on the path.
+    pub radius: f64,
+    /// Whether the last point joins back up with the first.
+    pub looped: bool,
+}
+impl Path {
+    pub fn new(points: Vec<Vector>, radius: f64, looped: bool) -> Path {
+        Path {
+            points,
+            radius,
+            looped,
+        }
+    }
+
+    /// Every segment of the path, in order.
+    pub fn segments(&self) -> Vec<(Vector, Vector)> {
+        let mut segments: Vec<(Vector, Vector)> = self.points.windows(2).map(|w| (w[0], w[1])).collect();
+        if self.looped && self.points.len() > 2 {
+            segments.push((self.points[self.points.len() - 1], self.points[0]));
+        }
+        segments
+    }
+}
+
+/// The point on segment `a`-`b` closest to `p`.
+pub fn closest_on_segment(p: Vector, a: Vector, b: Vector) -> Vector {
+    let ab = b - a;
+    let len_sq = ab.dot(&ab);
+    if len_sq == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(&ab) / len_sq).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+/// A round obstacle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    pub pos: Vector,
+    pub r: f64,
+}
+impl Obstacle {
+    pub fn new(x: f64, y: f64, r: f64) -> Obstacle {
+        Obstacle {
+            pos: Vector { x, y },
+            r,
+        }
+    }
+}
+
+/// A grid of desired directions, one for every `resolution` by `resolution` cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowField {
+    pub cols: usize,
+    pub rows: usize,
+    pub resolution: f64,
+    pub vectors: Vec<Vector>,
+}
+impl FlowField {
+    /// A field covering `width` by `height` pixels, with each cell pointing the way
+    /// `f` gives for the cell's center.
+    pub fn from_fn<F: Fn(Vector) -> Vector>(width: f64, height: f64, resolution: f64, f: F) -> FlowField {
+        let cols = (width / resolution).ceil() as usize;
+        let rows = (height / resolution).ceil() as usize;
+        let mut vectors = Vec::with_capacity(cols * rows);
+        for y in 0..rows {
+            for x in 0..cols {
+                let center = Vector::new((x as f64 + 0.5) * resolution, (y as f64 + 0.5) * resolution);
+                vectors.push(f(center));
+            }
+        }
+        FlowField {
+            cols,
+            rows,
+            resolution,
+            vectors,
+        }
+    }
+
+    /// The direction of the cell containing `pos`, using the nearest cell on the
+    /// edge for positions outside the field.
+    pub fn lookup(&self, pos: Vector) -> Vector {
+        let x = ((pos.x / self.resolution).floor() as i64).max(0).min(self.cols as i64 - 1);
+        let y = ((pos.y / self.resolution).floor() as i64).max(0).min(self.rows as i64 - 1);
+        self.vectors[x as usize + y as usize * self.cols]
+    }
+}
+
+/// Adds up weighted steering forces and caps the total at `max_force`.
+pub fn weighted_sum(forces: &[(f64, Vector)], max_force: f64) -> Vector {
+    let mut total = Vector::default();
+    for (weight, force) in forces {
+        total += *force * *weight;
+    }
+    total.limit_mag(max_force);
+    total
+}
+
+/// Adds up steering forces in order of priority until `max_force` is used up, so
+/// lower priority behaviors only get whatever the more important ones leave over.
+/// The force that runs out the budget is cut short and the rest are dropped.
+pub fn prioritized(forces: &[Vector], max_force: f64) -> Vector {
+    let mut total = Vector::default();
+    let mut left = max_force;
+    for force in forces {
+        let mag = force.mag();
+        if mag == 0.0 {
+            continue;
+        }
+        if mag >= left {
+            total += *force * (left / mag);
+            break;
+        }
+        total += *force;
+        left -= mag;
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_sum_weights_and_caps_forces() {
+        let forces = [
+            (2.0, Vector::new(1.0, 0.0)),
+            (0.5, Vector::new(0.0, 4.0)),
+            (0.0, Vector::new(50.0, 50.0)),
+        ];
+        assert_eq!(weighted_sum(&forces, 10.0), Vector::new(2.0, 2.0));
+
+        let capped = weighted_sum(&[(3.0, Vector::new(3.0, 4.0))], 5.0);
+        assert!((capped.mag() - 5.0).abs() < 1e-9);
+        assert!((capped.x / capped.y - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn prioritized_fills_the_budget_in_order() {
+        let forces = [Vector::new(3.0, 0.0), Vector::new(0.0, 4.0), Vector::new(-100.0, 0.0)];
+        // Everything fits.
+        assert_eq!(prioritized(&forces[..2], 10.0), Vector::new(3.0, 4.0));
+        // The second force runs out the budget and is cut short, and the third is
+        // dropped.
+        assert_eq!(prioritized(&forces, 5.0), Vector::new(3.0, 2.0));
+        // The first force takes it all.
+        assert_eq!(prioritized(&forces, 2.0), Vector::new(2.0, 0.0));
+    }
+
+    #[test]
+    fn prioritized_skips_zero_forces() {
+        let zero = Vector::default();
+        let forces = [zero, Vector::new(0.0, 2.0), zero, Vector::new(1.0, 0.0)];
+        assert_eq!(prioritized(&forces, 10.0), Vector::new(1.0, 2.0));
+
+        // A zero force after the budget is used up doesn't divide by zero.
+        let total = prioritized(&[Vector::new(2.0, 0.0), zero], 2.0);
+        assert_eq!(total, Vector::new(2.0, 0.0));
+        assert_eq!(prioritized(&[zero], 0.0), zero);
+    }
+}
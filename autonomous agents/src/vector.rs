@@ -1,11 +1,15 @@
 
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
 pub struct Vector {
     pub x: f64,
     pub y: f64,
 }
 impl Vector {
+    pub fn new(x: f64, y: f64) -> Vector {
+        Vector { x, y }
+    }
+
     // A function that normalizes this vector.
     pub fn normalize(&mut self) {
         let len = ((self.x * self.x) + (self.y * self.y)).sqrt();
@@ -47,6 +51,28 @@ impl Vector {
         }
     }
 
+    // A unit vector pointing at `angle` radians.
+    pub fn from_angle(angle: f64) -> Vector {
+        Vector {
+            x: angle.cos(),
+            y: angle.sin(),
+        }
+    }
+
+    pub fn dot(&self, other: &Vector) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    // A copy of this vector with length 1, or the zero vector if it has no direction.
+    pub fn normalized(&self) -> Vector {
+        let len = self.mag();
+        if len == 0.0 {
+            Vector::default()
+        } else {
+            *self * (1.0 / len)
+        }
+    }
+
     pub fn random2D() -> Vector {
         let mut rng = rand::thread_rng();
 
@@ -1,24 +1,82 @@
-use crate::{vector::Vector, WINDOW_WIDTH, WINDOW_HEIGHT};
+use crate::{
+    navigation::{ObstacleMap, Route},
+    steering::{self, closest_on_segment, FlowField, Obstacle, Path},
+    vector::Vector,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
 use glutin_window::map_key;
+use graphics::math::Matrix2d;
 use graphics::types::Triangle;
 use opengl_graphics::{GlGraphics};
 use piston::RenderArgs;
 use rand::Rng;
 
+// Steering asks for the gap between the velocity a behavior wants and the actual
+// velocity to close at this rate per second, before `max_force` caps it.
+const STEERING_RATE: f64 = 8.0;
+
+// Pursuit aims for where the target will be this many seconds from now.
+const PURSUE_AHEAD: f64 = 0.25;
+
+// Wandering aims at a point on a circle ahead of the vehicle, which drifts around
+// the circle by a random walk of WANDER_JITTER radians after a second.
+const WANDER_DISTANCE: f64 = 80.0;
+const WANDER_RADIUS: f64 = 40.0;
+const WANDER_JITTER: f64 = 3.3;
+
+// Path following looks this far ahead of the vehicle for the path, and aims this
+// far further along it.
+const PATH_PREDICT: f64 = 50.0;
+const PATH_AHEAD: f64 = 25.0;
+
+// Route following aims ROUTE_AHEAD further along the route than the vehicle is,
+// and counts a waypoint as reached within ROUTE_REACH of it.
+const ROUTE_AHEAD: f64 = 30.0;
+const ROUTE_REACH: f64 = 15.0;
+
+// Walls push back on vehicles once they're this much closer than their radius.
+const WALL_DISTANCE: f64 = 10.0;
+
+// How far ahead obstacles are noticed.
+const AVOID_DISTANCE: f64 = 120.0;
+
+// Followers keep this far behind the leader, and get out of the way when they're
+// this close to the spot the same distance ahead of it.
+const LEADER_BEHIND: f64 = 60.0;
+const LEADER_SIGHT: f64 = 50.0;
+
+// A queueing vehicle brakes when someone is within QUEUE_RADIUS of the point
+// QUEUE_AHEAD in front of it.
+const QUEUE_AHEAD: f64 = 40.0;
+const QUEUE_RADIUS: f64 = 20.0;
+
+/// Positions are in pixels and time in seconds, so velocities are in pixels per
+/// second and forces in pixels per second squared for a vehicle of mass 1.
+#[derive(Debug, Clone)]
 pub struct Vehicle {
     pub pos: Vector,
     pub vel: Vector,
+    /// The forces applied since the last update.
     pub acc: Vector,
     pub r: f64,
     pub color : [f32; 4],
     pub max_speed : f64,
     pub max_force : f64,
+    /// How much the vehicle resists being pushed around.
+    pub mass: f64,
+    /// The rate velocity decays at, per second. With a drag of 1 a coasting vehicle
+    /// loses about 63% of its speed each second.
+    pub drag: f64,
+    /// How fast the vehicle can change direction, in radians per second.
+    pub max_turn_rate: f64,
+    /// Where on the wander circle the vehicle is aiming, relative to its heading.
+    pub wander_angle: f64,
 }
 impl Vehicle {
     pub fn new(x: f64, y: f64, color : [f32; 4]) -> Vehicle {
         let mut rng = rand::thread_rng();
 
-        let max_speed = rng.gen_range(0.05..0.2);
+        let max_speed = rng.gen_range(100.0..400.0);
 
         Vehicle {
             pos: Vector { x, y },
@@ -27,28 +85,48 @@ impl Vehicle {
             r: 16.0,
             color : color,
             max_speed: max_speed,
-            max_force: 0.0001,
+            max_force: 300.0,
+            mass: 1.0,
+            drag: 0.0,
+            max_turn_rate: f64::INFINITY,
+            wander_angle: 0.0,
         }
     }
 
+    // The steering force that changes the velocity to `desired`.
+    fn correct_to(&self, desired: Vector) -> Vector {
+        let mut steering = (desired - self.vel) * STEERING_RATE;
+        steering.limit_mag(self.max_force);
+        steering
+    }
+
+    // The steering force that turns the velocity towards `direction` at full speed.
+    fn steer_towards(&self, direction: Vector) -> Vector {
+        if direction.mag() == 0.0 {
+            return Vector::default();
+        }
+        self.correct_to(direction.normalized() * self.max_speed)
+    }
+
     pub fn arrive(&self, target: Vector) -> Vector {
         let mut force = target - self.pos;
         let r = 100.0;
         let d = force.mag();
         if d < r {
-            let m = crate::map_range((0.0, r), (0.0, self.max_speed), d);
-            force *= m;
+            // Already on the target the offset is zero and has no direction.
+            if d > 0.0 {
+                let m = crate::map_range((0.0, r), (0.0, self.max_speed), d);
+                force.set_mag(m);
+            }
         } else {
             force.set_mag(self.max_speed);
         }
-        force -= self.vel;
-        force.limit_mag(self.max_force);
-        force
+        self.correct_to(force)
     }
 
     pub fn persue(&self, vehicle: &Vehicle) -> Vector {
         let mut target = vehicle.pos;
-        target += vehicle.vel * 10.0;
+        target += vehicle.vel * PURSUE_AHEAD;
         self.seek(target)
     }
 
@@ -57,26 +135,252 @@ impl Vehicle {
     }
 
     pub fn seek(&self, target: Vector) -> Vector {
-        let mut desired = target - self.pos;
-        desired.set_mag(self.max_speed);
-        let mut steering = desired - self.vel;
-        steering.limit_mag(self.max_force);
-        steering
+        self.steer_towards(target - self.pos)
     }
 
     pub fn flee(&self, target: Vector) -> Vector {
         -self.seek(target)
     }
 
+    /// Meanders around by seeking a point that drifts randomly around a circle
+    /// in front of the vehicle, `dt` being the time since it last wandered.
+    pub fn wander(&mut self, dt: f64) -> Vector {
+        let jitter = WANDER_JITTER * dt.sqrt();
+        if jitter > 0.0 {
+            self.wander_angle += rand::thread_rng().gen_range(-jitter..jitter);
+        }
+        let heading = self.vel.y.atan2(self.vel.x);
+        let center = self.pos + Vector::from_angle(heading) * WANDER_DISTANCE;
+        self.seek(center + Vector::from_angle(heading + self.wander_angle) * WANDER_RADIUS)
+    }
+
+    /// Steers back onto `path` when the vehicle is about to leave it, aiming a
+    /// little further along the segment it is closest to.
+    pub fn follow_path(&self, path: &Path) -> Vector {
+        let predict = self.pos + self.vel.normalized() * PATH_PREDICT;
+        let mut closest: Option<(f64, Vector, Vector)> = None;
+        for (a, b) in path.segments() {
+            let normal = closest_on_segment(predict, a, b);
+            let d = (predict - normal).mag();
+            if closest.is_none_or(|(best, _, _)| d < best) {
+                closest = Some((d, normal, (b - a).normalized()));
+            }
+        }
+        match closest {
+            Some((d, normal, dir)) if d > path.radius => self.seek(normal + dir * PATH_AHEAD),
+            _ => Vector::default(),
+        }
+    }
+
+    /// Follows `route` by seeking a point a little further along it, which rounds
+    /// off its corners, and arrives at the end. Moves `route` on past the
+    /// waypoints the vehicle has reached.
+    pub fn follow_route(&self, route: &mut Route) -> Vector {
+        route.advance(self.pos, ROUTE_REACH);
+        match route.goal() {
+            Some(goal) if route.finished() || route.remaining(self.pos) < ROUTE_AHEAD => self.arrive(goal),
+            Some(_) => self.seek(route.ahead(self.pos, ROUTE_AHEAD)),
+            None => Vector::default(),
+        }
+    }
+
+    /// Moves away from the walls of `map` that are close, harder the closer they are.
+    pub fn avoid_walls(&self, map: &ObstacleMap) -> Vector {
+        let mut away = Vector::default();
+        for wall in map.walls_near(self.pos, self.r + WALL_DISTANCE) {
+            let diff = self.pos - wall;
+            away += diff.normalized() * (1.0 / diff.mag());
+        }
+        self.steer_towards(away)
+    }
+
+    /// Veers sideways around the nearest obstacle in the vehicle's way, harder the
+    /// closer it is, and pushes straight out of any obstacle it is already inside.
+    pub fn avoid_obstacles(&self, obstacles: &[Obstacle]) -> Vector {
+        let forward = self.vel.normalized();
+        let side = Vector::new(-forward.y, forward.x);
+
+        let mut threat: Option<(f64, f64, f64)> = None;
+        for obstacle in obstacles {
+            let to = obstacle.pos - self.pos;
+            let clearance = obstacle.r + self.r;
+            if to.mag() < clearance {
+                return self.flee(obstacle.pos);
+            }
+            let (ahead, lateral) = (to.dot(&forward), to.dot(&side));
+            let reach = AVOID_DISTANCE + obstacle.r;
+            let in_the_way = ahead > 0.0 && ahead < reach && lateral.abs() < clearance;
+            if in_the_way && threat.is_none_or(|(closest, _, _)| ahead < closest) {
+                threat = Some((ahead, lateral, reach));
+            }
+        }
+
+        match threat {
+            Some((ahead, lateral, reach)) => {
+                let away = if lateral > 0.0 { -side } else { side };
+                let urgency = 1.0 - ahead / reach;
+                self.steer_towards(away * urgency + forward * (1.0 - urgency))
+            }
+            None => Vector::default(),
+        }
+    }
+
+    /// Turns back from the edges of the rectangle `[x, y, width, height]` once
+    /// the vehicle is within `margin` of them.
+    pub fn contain(&self, bounds: [f64; 4], margin: f64) -> Vector {
+        let [x, y, width, height] = bounds;
+        let mut desired = self.vel;
+        let mut outside = false;
+        if self.pos.x < x + margin {
+            desired.x = self.max_speed;
+            outside = true;
+        } else if self.pos.x > x + width - margin {
+            desired.x = -self.max_speed;
+            outside = true;
+        }
+        if self.pos.y < y + margin {
+            desired.y = self.max_speed;
+            outside = true;
+        } else if self.pos.y > y + height - margin {
+            desired.y = -self.max_speed;
+            outside = true;
+        }
+        if outside {
+            self.correct_to(desired.normalized() * self.max_speed)
+        } else {
+            Vector::default()
+        }
+    }
+
+    /// Moves away from the other vehicles within `radius`, more strongly from
+    /// closer ones.
+    pub fn separate<'a, I: IntoIterator<Item = &'a Vehicle>>(&self, others: I, radius: f64) -> Vector {
+        let mut away = Vector::default();
+        for other in others {
+            let diff = self.pos - other.pos;
+            let d = diff.mag();
+            if !std::ptr::eq(self, other) && d > 0.0 && d < radius {
+                away += diff.normalized() * (1.0 / d);
+            }
+        }
+        self.steer_towards(away)
+    }
+
+    /// Matches the average heading of the other vehicles within `radius`.
+    pub fn align<'a, I: IntoIterator<Item = &'a Vehicle>>(&self, others: I, radius: f64) -> Vector {
+        let mut heading = Vector::default();
+        for other in others {
+            if !std::ptr::eq(self, other) && (other.pos - self.pos).mag() < radius {
+                heading += other.vel;
+            }
+        }
+        self.steer_towards(heading)
+    }
+
+    /// Heads for the average position of the other vehicles within `radius`.
+    pub fn cohere<'a, I: IntoIterator<Item = &'a Vehicle>>(&self, others: I, radius: f64) -> Vector {
+        let mut sum = Vector::default();
+        let mut count = 0;
+        for other in others {
+            if !std::ptr::eq(self, other) && (other.pos - self.pos).mag() < radius {
+                sum += other.pos;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            Vector::default()
+        } else {
+            self.seek(sum * (1.0 / count as f64))
+        }
+    }
+
+    /// Arrives at a spot behind `leader`, getting out of its way when in front of
+    /// it and keeping `separation` from the other followers.
+    pub fn follow_leader<'a, I: IntoIterator<Item = &'a Vehicle>>(
+        &self,
+        leader: &Vehicle,
+        others: I,
+        separation: f64,
+    ) -> Vector {
+        let dir = leader.vel.normalized();
+        let behind = leader.pos - dir * LEADER_BEHIND;
+        let ahead = leader.pos + dir * LEADER_BEHIND;
+
+        let mut force = self.arrive(behind);
+        if (ahead - self.pos).mag() < LEADER_SIGHT || (leader.pos - self.pos).mag() < LEADER_SIGHT {
+            force += self.evade(leader);
+        }
+        force += self.separate(others, separation);
+        force.limit_mag(self.max_force);
+        force
+    }
+
+    /// Brakes when another vehicle is just in front, so vehicles heading the same
+    /// way line up instead of pushing through each other.
+    pub fn queue<'a, I: IntoIterator<Item = &'a Vehicle>>(&self, others: I) -> Vector {
+        let forward = self.vel.normalized();
+        let ahead = self.pos + forward * QUEUE_AHEAD;
+        for other in others {
+            let in_front = (other.pos - self.pos).dot(&forward) > 0.0;
+            if !std::ptr::eq(self, other) && in_front && (other.pos - ahead).mag() < QUEUE_RADIUS {
+                return self.correct_to(Vector::default());
+            }
+        }
+        Vector::default()
+    }
+
+    /// Turns to go the way `field` points at the vehicle's position.
+    pub fn follow_field(&self, field: &FlowField) -> Vector {
+        self.steer_towards(field.lookup(self.pos))
+    }
+
+    /// Adds up weighted behaviors, capped at this vehicle's `max_force`.
+    pub fn combine_weighted(&self, forces: &[(f64, Vector)]) -> Vector {
+        steering::weighted_sum(forces, self.max_force)
+    }
+
+    /// Gives each behavior, most important first, whatever is left of this
+    /// vehicle's `max_force`.
+    pub fn combine_prioritized(&self, forces: &[Vector]) -> Vector {
+        steering::prioritized(forces, self.max_force)
+    }
+
+    /// The velocity `steering` is turning the vehicle towards, the reverse of how
+    /// behaviors work out their force from the velocity they want.
+    pub fn desired_velocity(&self, steering: Vector) -> Vector {
+        self.vel + steering * (1.0 / STEERING_RATE)
+    }
+
     pub fn apply_force(&mut self, force: Vector) {
         self.acc += force
     }
 
-    pub fn update(&mut self) {
-        self.vel += self.acc;
-        self.pos += self.vel;
-        self.acc *= 0.0;
+    /// Moves the vehicle on by `dt` seconds under the forces applied since the last
+    /// update. Velocity is updated first and then moves the vehicle (semi-implicit
+    /// Euler), which keeps the motion stable and close to the same whatever `dt` is.
+    pub fn update(&mut self, dt: f64) {
+        let before = self.vel;
+        self.vel += self.acc * (dt / self.mass);
+        self.vel *= (-self.drag * dt).exp();
+        self.limit_turn(before, dt);
         self.vel.limit_mag(self.max_speed);
+        self.pos += self.vel * dt;
+        self.acc *= 0.0;
+    }
+
+    // Turns the velocity back towards `before` if it has swung round further than
+    // `max_turn_rate` allows in `dt`, keeping its new speed.
+    fn limit_turn(&mut self, before: Vector, dt: f64) {
+        let (speed, max_turn) = (self.vel.mag(), self.max_turn_rate * dt);
+        if before.mag() == 0.0 || speed == 0.0 || max_turn >= std::f64::consts::PI {
+            return;
+        }
+        let cross = before.x * self.vel.y - before.y * self.vel.x;
+        let turn = cross.atan2(before.dot(&self.vel));
+        if turn.abs() > max_turn {
+            let heading = before.y.atan2(before.x) + max_turn * turn.signum();
+            self.vel = Vector::from_angle(heading) * speed;
+        }
     }
 
     pub fn edges(&mut self) {
@@ -95,21 +399,25 @@ impl Vehicle {
     }
 
     pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
-        use graphics::*;
-
         if self.on_screen() {
-            gl.draw(args.viewport(), |c, gl| {
-                let triangle = [[-self.r, -self.r / 2.0], [-self.r, self.r / 2.0], [self.r, 0.0]];
-                polygon(
-                    self.color,
-                    &triangle,
-                    c.transform.trans(self.pos.x, self.pos.y).rot_deg(self.vel.heading()),
-                    gl,
-                );
-            });
+            gl.draw(args.viewport(), |c, gl| self.draw(c.transform, gl));
         }
     }
 
+    /// Draws the vehicle as a triangle pointing the way it's going, for use inside
+    /// an existing `gl.draw`.
+    pub fn draw(&self, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let triangle = [[-self.r, -self.r / 2.0], [-self.r, self.r / 2.0], [self.r, 0.0]];
+        polygon(
+            self.color,
+            &triangle,
+            transform.trans(self.pos.x, self.pos.y).rot_deg(self.vel.heading()),
+            gl,
+        );
+    }
+
     pub fn on_screen(&self) -> bool {
         self.pos.x + self.r > 0.0
             && self.pos.x - self.r <= WINDOW_WIDTH as f64
@@ -117,3 +425,109 @@ impl Vehicle {
             && self.pos.y - self.r <= WINDOW_HEIGHT as f64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A heavy, draggy vehicle that turns slowly, heading up and steering for a
+    // point off to its right.
+    fn sluggish() -> Vehicle {
+        let mut v = Vehicle::new(100.0, 500.0, [1.0; 4]);
+        v.vel = Vector::new(0.0, -150.0);
+        v.max_speed = 250.0;
+        v.max_force = 400.0;
+        v.mass = 2.0;
+        v.drag = 0.8;
+        v.max_turn_rate = 1.5;
+        v
+    }
+
+    // Where the vehicle gets to after `seconds` of seeking `target` in steps of `dt`.
+    fn seek_for(mut v: Vehicle, target: Vector, seconds: f64, dt: f64) -> Vehicle {
+        let steps = (seconds / dt).round() as usize;
+        for _ in 0..steps {
+            let force = v.seek(target);
+            v.apply_force(force);
+            v.update(dt);
+        }
+        v
+    }
+
+    #[test]
+    fn update_is_frame_rate_independent() {
+        let target = Vector::new(700.0, 200.0);
+        let coarse = seek_for(sluggish(), target, 3.0, 1.0 / 30.0);
+        let fine = seek_for(sluggish(), target, 3.0, 1.0 / 1000.0);
+
+        let travelled = (fine.pos - Vector::new(100.0, 500.0)).mag();
+        assert!(travelled > 300.0, "only went {}", travelled);
+        let gap = (coarse.pos - fine.pos).mag();
+        assert!(gap < 0.01 * travelled, "{:?} vs {:?}", coarse.pos, fine.pos);
+        assert!((coarse.vel - fine.vel).mag() < 0.05 * fine.vel.mag());
+    }
+
+    #[test]
+    fn coasting_follows_drag() {
+        for &dt in [1.0 / 30.0, 1.0 / 120.0, 1.0 / 1000.0f64].iter() {
+            let mut v = sluggish();
+            for _ in 0..(1.0 / dt).round() as usize {
+                v.update(dt);
+            }
+            // With no forces the speed decays exactly exponentially.
+            assert!((v.vel.mag() - 150.0 * (-0.8f64).exp()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn turn_rate_limits_turning() {
+        for &dt in [1.0 / 30.0, 1.0 / 1000.0f64].iter() {
+            let mut v = sluggish();
+            v.drag = 0.0;
+            // Steering straight backwards for half a second can only turn it by
+            // 1.5 * 0.5 radians, whatever the step.
+            for _ in 0..(0.5 / dt).round() as usize {
+                v.apply_force(Vector::new(0.0, 400.0));
+                v.update(dt);
+            }
+            let turned = v.vel.y.atan2(v.vel.x) + std::f64::consts::FRAC_PI_2;
+            assert!(turned.abs() <= 0.75 + 1e-9, "turned {}", turned);
+        }
+    }
+
+    #[test]
+    fn mass_scales_acceleration() {
+        let push = |mass: f64| {
+            let mut v = sluggish();
+            v.vel = Vector::default();
+            v.drag = 0.0;
+            v.mass = mass;
+            v.apply_force(Vector::new(100.0, 0.0));
+            v.update(0.1);
+            v.vel.x
+        };
+        assert!((push(1.0) - 10.0).abs() < 1e-9);
+        assert!((push(4.0) - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wander_survives_a_zero_time_step() {
+        let mut v = sluggish();
+        let angle = v.wander_angle;
+        let force = v.wander(0.0);
+        assert_eq!(v.wander_angle, angle);
+        assert!(force.mag().is_finite());
+    }
+
+    #[test]
+    fn arrive_slows_linearly_inside_the_radius() {
+        let mut v = sluggish();
+        v.vel = Vector::default();
+        v.max_force = f64::INFINITY;
+        let speed = |d: f64| v.desired_velocity(v.arrive(v.pos + Vector::new(d, 0.0))).mag();
+        assert!((speed(50.0) - 125.0).abs() < 1e-9);
+        assert!((speed(90.0) - 225.0).abs() < 1e-9);
+        assert!((speed(200.0) - 250.0).abs() < 1e-9);
+        assert_eq!(speed(0.0), 0.0);
+    }
+}
@@ -0,0 +1,175 @@
+//! A floating point image fireworks are drawn into when rendering without a
+//! window. Light adds up like it does in the window, so everything drawn is
+//! added onto what's already there, with colors premultiplied by their alpha.
+
+use crate::vector::Vector;
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub background: [f32; 3],
+    pixels: Vec<[f32; 3]>,
+}
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width,
+            height,
+            background: [0.0; 3],
+            pixels: vec![[0.0; 3]; (width * height) as usize],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let background = self.background;
+        for p in &mut self.pixels {
+            *p = background;
+        }
+    }
+
+    fn add(&mut self, x: i64, y: i64, color: [f32; 4], coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let p = &mut self.pixels[(x + y * self.width as i64) as usize];
+        for c in 0..3 {
+            p[c] += color[c] * coverage;
+        }
+    }
+
+    /// Fills a circle, with pixels on its edge covered by how much of them is
+    /// inside it.
+    pub fn dot(&mut self, center: Vector, radius: f64, color: [f32; 4]) {
+        let (x0, x1) = ((center.x - radius).floor() as i64, (center.x + radius).ceil() as i64);
+        let (y0, y1) = ((center.y - radius).floor() as i64, (center.y + radius).ceil() as i64);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let d = Vector {
+                    x: x as f64 + 0.5 - center.x,
+                    y: y as f64 + 0.5 - center.y,
+                }
+                .mag();
+                let coverage = (radius + 0.5 - d).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.add(x, y, color, coverage as f32);
+                }
+            }
+        }
+    }
+
+    /// Draws an antialiased, one pixel wide line (Xiaolin Wu's algorithm).
+    pub fn line(&mut self, from: Vector, to: Vector, color: [f32; 4]) {
+        let (mut x0, mut y0) = (from.x, from.y);
+        let (mut x1, mut y1) = (to.x, to.y);
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+
+        let plot = |canvas: &mut Canvas, x: f64, y: f64, coverage: f64| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            canvas.add(x as i64, y as i64, color, coverage as f32);
+        };
+
+        // Sub-pixel segments still leave a faint mark, weighted by their length.
+        let length = dx.max(1e-9);
+        let x_start = x0.round();
+        let x_end = x1.round();
+        let mut y = y0 + gradient * (x_start - x0);
+        let mut x = x_start;
+        while x <= x_end {
+            let weight = if x_start == x_end { length.min(1.0) } else { 1.0 };
+            let frac = y - y.floor();
+            plot(self, x, y.floor(), (1.0 - frac) * weight);
+            plot(self, x, y.floor() + 1.0, frac * weight);
+            y += gradient;
+            x += 1.0;
+        }
+    }
+
+    /// Adds a blurred copy of the canvas onto itself, `strength` times as bright,
+    /// so bright things glow. `radius` is roughly how far the glow spreads, in
+    /// pixels.
+    pub fn bloom(&mut self, radius: f64, strength: f32) {
+        // Three box blurs in a row come close to a gaussian blur, and a box blur
+        // of width 2r + 1 spreads things out by sqrt(r(r + 1) / 3).
+        let r = ((radius * radius + 0.25).sqrt() - 0.5).round().max(1.0) as usize;
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut glow = self.pixels.clone();
+        for _ in 0..3 {
+            box_blur(&mut glow, w, h, 1, w, r);
+            box_blur(&mut glow, h, w, w, 1, r);
+        }
+        for (p, g) in self.pixels.iter_mut().zip(&glow) {
+            for c in 0..3 {
+                p[c] += g[c] * strength;
+            }
+        }
+    }
+
+    pub fn to_image(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        for (i, p) in self.pixels.iter().enumerate() {
+            let to_byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            image.put_pixel(
+                i as u32 % self.width,
+                i as u32 / self.width,
+                Rgba([to_byte(p[0]), to_byte(p[1]), to_byte(p[2]), 255]),
+            );
+        }
+        image
+    }
+
+    /// Writes the canvas to an image file, in a format chosen by the extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> image::ImageResult<()> {
+        self.to_image().save(path)
+    }
+}
+
+// Blurs each of `lines` lines of `length` pixels with a box `2r + 1` pixels wide,
+// where pixels along a line are `step` apart and lines start `stride` apart.
+// Pixels off the ends count as black.
+fn box_blur(pixels: &mut [[f32; 3]], length: usize, lines: usize, step: usize, stride: usize, r: usize) {
+    let scale = 1.0 / (2 * r + 1) as f32;
+    let mut line = vec![[0.0f32; 3]; length];
+    for l in 0..lines {
+        let start = l * stride;
+        for (i, p) in line.iter_mut().enumerate() {
+            *p = pixels[start + i * step];
+        }
+        let mut sum = [0.0f32; 3];
+        for p in line.iter().take(r) {
+            for c in 0..3 {
+                sum[c] += p[c];
+            }
+        }
+        for i in 0..length {
+            if i + r < length {
+                for c in 0..3 {
+                    sum[c] += line[i + r][c];
+                }
+            }
+            if i > r {
+                for c in 0..3 {
+                    sum[c] -= line[i - r - 1][c];
+                }
+            }
+            let out = &mut pixels[start + i * step];
+            for c in 0..3 {
+                out[c] = sum[c] * scale;
+            }
+        }
+    }
+}
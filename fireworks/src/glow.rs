@@ -0,0 +1,179 @@
+//! A bloom for the window: the scene is drawn to an offscreen target, which is
+//! shrunk, blurred and added back on top of it so bright things glow.
+
+use crate::{BLACK, GLOW_RADIUS, WINDOW_HEIGHT, WINDOW_WIDTH};
+use gl::types::GLuint;
+use graphics::math::Matrix2d;
+use graphics::{Context, Viewport};
+use opengl_graphics::{GlGraphics, Texture, TextureSettings};
+
+// The glow is blurred at this fraction of the window's size. Any smaller and
+// shrinking the scene would skip over some of the stars.
+const DOWNSAMPLE: u32 = 2;
+
+// Each blur pass samples five glow pixels `spacing` apart, weighted like a
+// gaussian, which spreads the glow by another `spacing` pixels.
+const BLUR_WEIGHTS: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+// The spacing of each blur pass, in glow pixels, so that the glow spreads by
+// `GLOW_RADIUS` like `Canvas::bloom`. Sampling further apart than the glow has
+// already spread would leave copies of things instead of blurring them, so it
+// starts a pixel apart and grows with the glow.
+fn blur_spacings() -> Vec<f64> {
+    // Blurs add up like variances, so the spread is kept squared.
+    let target = (GLOW_RADIUS / DOWNSAMPLE as f64).powi(2);
+    let mut spread = 0.0;
+    let mut spacings = vec![];
+    while spread < target - 1e-9 {
+        let spacing = spread.sqrt().max(1.0).min((target - spread).sqrt());
+        spacings.push(spacing);
+        spread += spacing * spacing;
+    }
+    spacings
+}
+
+/// A texture that can be drawn into like the window.
+pub struct RenderTarget {
+    pub texture: Texture,
+    pub width: u32,
+    pub height: u32,
+    fbo: GLuint,
+}
+impl RenderTarget {
+    pub fn new(width: u32, height: u32) -> RenderTarget {
+        let texture = Texture::from_image(&image::ImageBuffer::new(width, height), &TextureSettings::new());
+
+        let mut fbo: GLuint = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture.get_id(),
+                0,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        RenderTarget {
+            texture,
+            width,
+            height,
+            fbo,
+        }
+    }
+
+    /// Draws into the target with window coordinates, stretched to fit it.
+    pub fn draw<F: FnOnce(Context, &mut GlGraphics)>(&self, gl: &mut GlGraphics, f: F) {
+        let viewport = Viewport {
+            rect: [0, 0, self.width as i32, self.height as i32],
+            draw_size: [self.width, self.height],
+            window_size: [WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64],
+        };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        }
+        gl.draw(viewport, f);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // Adds the target's texture over the whole window, shifted by `offset` and
+    // tinted by `tint`. What's drawn into a target comes out upside down, so it's
+    // flipped back.
+    fn add_to(&self, offset: [f64; 2], tint: [f32; 4], c: &Context, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let (w, h) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+        Image::new_color(tint)
+            .rect([offset[0], offset[1], w, h])
+            .draw(
+                &self.texture,
+                &c.draw_state.blend(Blend::Add),
+                c.transform.trans(0.0, h).flip_v(),
+                gl,
+            );
+    }
+}
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+        }
+    }
+}
+
+pub struct Glow {
+    /// How bright the glow is next to what it's glowing around.
+    pub strength: f32,
+    scene: RenderTarget,
+    blur: [RenderTarget; 2],
+    spacings: Vec<f64>,
+}
+impl Glow {
+    pub fn new(strength: f32) -> Glow {
+        let small = || RenderTarget::new(WINDOW_WIDTH / DOWNSAMPLE, WINDOW_HEIGHT / DOWNSAMPLE);
+        Glow {
+            strength,
+            scene: RenderTarget::new(WINDOW_WIDTH, WINDOW_HEIGHT),
+            blur: [small(), small()],
+            spacings: blur_spacings(),
+        }
+    }
+
+    /// Draws the scene `f` draws to the window, glowing.
+    pub fn draw<F: FnOnce(Matrix2d, &mut GlGraphics)>(&self, viewport: Viewport, gl: &mut GlGraphics, f: F) {
+        use graphics::*;
+
+        self.scene.draw(gl, |c, gl| {
+            clear(BLACK, gl);
+            f(c.transform, gl);
+        });
+        self.blur[0].draw(gl, |c, gl| {
+            clear(BLACK, gl);
+            self.scene.add_to([0.0, 0.0], [1.0; 4], &c, gl);
+        });
+
+        // Blurring across and then down with the same spacing is the same as
+        // blurring both ways at once. Targets are drawn into with window
+        // coordinates, so a glow pixel is `DOWNSAMPLE` of them.
+        for &spacing in &self.spacings {
+            for (from, to, across) in [(0, 1, true), (1, 0, false)].iter() {
+                self.blur[*to].draw(gl, |c, gl| {
+                    clear(BLACK, gl);
+                    for (i, &weight) in BLUR_WEIGHTS.iter().enumerate() {
+                        let d = (i as f64 - 2.0) * spacing * DOWNSAMPLE as f64;
+                        let offset = if *across { [d, 0.0] } else { [0.0, d] };
+                        self.blur[*from].add_to(offset, [weight, weight, weight, 1.0], &c, gl);
+                    }
+                });
+            }
+        }
+
+        gl.draw(viewport, |c, gl| {
+            clear(BLACK, gl);
+            let s = self.strength;
+            self.scene.add_to([0.0, 0.0], [1.0; 4], &c, gl);
+            self.blur[0].add_to([0.0, 0.0], [s, s, s, 1.0], &c, gl);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blur_spreads_like_bloom_without_gaps() {
+        let mut spread: f64 = 0.0;
+        for &spacing in &blur_spacings() {
+            assert!(spacing <= spread.sqrt().max(1.0) + 1e-9, "{} after {}", spacing, spread);
+            spread += spacing * spacing;
+        }
+        let radius = spread.sqrt() * DOWNSAMPLE as f64;
+        assert!((radius - GLOW_RADIUS).abs() < 1e-6, "{}", radius);
+    }
+}
@@ -1,8 +1,14 @@
+pub mod canvas;
+pub mod glow;
+pub mod shell;
+pub mod show;
 pub mod vector;
 
-use lazy_static::lazy_static;
+use canvas::Canvas;
+use graphics::math::Matrix2d;
 use opengl_graphics::{GlGraphics, Texture};
-use piston::RenderArgs;
+use rand::Rng;
+use shell::{Shell, BUILT_IN};
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use vector::Vector;
@@ -12,6 +18,26 @@ pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
 pub const WINDOW_WIDTH: u32 = 1920;
 pub const WINDOW_HEIGHT: u32 = 1080;
 
+/// Fireworks move on this many times a second. Speeds are in pixels per update.
+pub const UPDATES_PER_SECOND: u64 = 120;
+
+/// Pulls everything down, in pixels per update per update.
+pub const GRAVITY: Vector = Vector {
+    x: 0.0,
+    y: WINDOW_HEIGHT as f64 / 6000.0,
+};
+
+/// How far the glow around bright things spreads, in pixels.
+pub const GLOW_RADIUS: f64 = 12.0;
+/// How bright the glow is next to what it's glowing around.
+pub const GLOW_STRENGTH: f32 = 0.9;
+
+// How many past positions a rocket's trail goes through.
+const ROCKET_TRAIL: usize = 12;
+
+// How bright the newest part of a trail is next to its particle.
+const TRAIL_BRIGHTNESS: f32 = 0.5;
+
 /// Measures Frames Per Second (FPS).
 #[derive(Debug)]
 pub struct FPSCounter {
@@ -51,14 +77,34 @@ impl FPSCounter {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Particle {
     pub pos: Vector,
     pub vel: Vector,
     pub acc: Vector,
     pub color: [f32; 4],
-    pub lifespan: i16,
+    /// How many updates the particle has been burning for.
+    pub age: u32,
+    /// How many updates it burns for before going out. Rockets never go out.
+    pub lifetime: u32,
     pub firework: bool,
+    /// The fraction of its speed the particle loses every update.
+    pub drag: f64,
+    /// How many updates it spends blinking on and then off, or 0 to burn steadily.
+    pub strobe: u32,
+    /// Which of its firework's stages the particle belongs to.
+    pub stage: usize,
+    /// Where the particle has been, oldest first.
+    pub trail: VecDeque<Vector>,
+    /// How many past positions the trail goes through.
+    pub trail_length: usize,
+    /// How much its brightness wavers from update to update, from 0 to 1.
+    pub flicker: f32,
+    /// The chance each update of it glinting white.
+    pub sparkle: f32,
+    /// Picks when the particle flickers and sparkles, so particles don't all do it
+    /// together but a seeded show does it the same way every time.
+    pub seed: u32,
 }
 impl Particle {
     pub fn new(x: f64, y: f64, firework: bool, color: [f32; 4]) -> Particle {
@@ -76,38 +122,132 @@ impl Particle {
             },
             acc: Vector { x: 0.0, y: 0.0 },
             color: color,
-            lifespan: rand::Rng::gen_range(&mut rng, 200..255),
+            age: 0,
+            lifetime: rand::Rng::gen_range(&mut rng, 67..85),
             firework: firework,
+            drag: 0.0,
+            strobe: 0,
+            stage: 0,
+            trail: VecDeque::new(),
+            trail_length: if firework { ROCKET_TRAIL } else { 0 },
+            flicker: 0.0,
+            sparkle: 0.0,
+            seed: rng.gen(),
+        }
+    }
+
+    /// A star of `shell`, which is stage `stage` of its firework.
+    pub fn star<R: Rng>(pos: Vector, vel: Vector, shell: &Shell, stage: usize, rng: &mut R) -> Particle {
+        Particle {
+            pos,
+            vel,
+            acc: Vector { x: 0.0, y: 0.0 },
+            color: shell.color(rng),
+            age: 0,
+            lifetime: shell.lifetime(rng),
+            firework: false,
+            drag: shell.drag,
+            strobe: shell.strobe,
+            stage,
+            trail: VecDeque::with_capacity(shell.trail + 1),
+            trail_length: shell.trail,
+            flicker: shell.flicker,
+            sparkle: shell.sparkle,
+            seed: rng.gen(),
         }
     }
 
     pub fn update(&mut self) {
+        if self.trail_length > 0 {
+            self.trail.push_back(self.pos);
+            if self.trail.len() > self.trail_length {
+                self.trail.pop_front();
+            }
+        }
         self.vel += self.acc;
+        self.vel *= 1.0 - self.drag;
         self.pos += self.vel;
         self.acc *= 0.0;
         if !self.firework {
-            self.lifespan -= 3;
-            self.color[3] = map_range((0.0, 255.0), (0.0, 1.0), self.lifespan as f64) as f32;
+            self.age += 1;
         }
     }
 
+    pub fn burned_out(&self) -> bool {
+        !self.firework && self.age >= self.lifetime
+    }
+
+    /// How brightly the particle is burning, from 1 when it's lit to 0 when it's
+    /// burned out or strobed off.
+    pub fn brightness(&self) -> f32 {
+        if self.firework {
+            return 1.0;
+        }
+        if self.strobe > 0 && (self.age / self.strobe) % 2 == 1 {
+            return 0.0;
+        }
+        let fade = map_range((0.0, self.lifetime as f64), (1.0, 0.0), self.age as f64).max(0.0) as f32;
+        fade * (1.0 - self.flicker * noise(self.seed, self.age))
+    }
+
+    /// Whether the particle is glinting this update.
+    pub fn sparkling(&self) -> bool {
+        self.sparkle > 0.0 && noise(self.seed ^ 0x9e37_79b9, self.age) < self.sparkle
+    }
+
+    // The color the particle is drawn in, premultiplied by how brightly it's
+    // burning since it's added onto what's behind it.
+    fn glow_color(&self) -> [f32; 4] {
+        let (color, brightness) = if self.sparkling() && self.brightness() > 0.0 {
+            (WHITE, 1.0)
+        } else {
+            (self.color, self.color[3] * self.brightness())
+        };
+        [color[0] * brightness, color[1] * brightness, color[2] * brightness, 1.0]
+    }
+
+    // Each step of the trail, newest first, and how bright it is next to the
+    // particle.
+    fn trail_steps(&self) -> impl Iterator<Item = (Vector, Vector, f32)> + '_ {
+        let n = self.trail.len() as f32;
+        let mut to = self.pos;
+        self.trail.iter().rev().enumerate().map(move |(i, &from)| {
+            let step = (from, to, TRAIL_BRIGHTNESS * (1.0 - i as f32 / n));
+            to = from;
+            step
+        })
+    }
+
     pub fn applyForce(&mut self, force: Vector) {
         self.acc += force;
     }
 
-    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+    /// Draws the particle and its trail, adding their light to what's behind them.
+    pub fn draw(&self, transform: Matrix2d, gl: &mut GlGraphics) {
         use graphics::*;
 
-        if self.on_screen() {
-            gl.draw(args.viewport(), |c, gl| {
-                ellipse(
-                    self.color,
-                    [self.pos.x, self.pos.y, 4.0, 4.0],
-                    c.transform,
-                    gl,
-                );
-            });
+        let color = self.glow_color();
+        if !self.on_screen() || color[..3].iter().all(|&c| c <= 0.0) {
+            return;
         }
+        let ds = DrawState::default().blend(Blend::Add);
+        let dim = |f: f32| [color[0] * f, color[1] * f, color[2] * f, 1.0];
+        for (from, to, f) in self.trail_steps() {
+            Line::new(dim(f), 0.75).draw_from_to([from.x, from.y], [to.x, to.y], &ds, transform, gl);
+        }
+        Ellipse::new(color).draw([self.pos.x - 2.0, self.pos.y - 2.0, 4.0, 4.0], &ds, transform, gl);
+    }
+
+    /// Draws the particle onto `canvas` the way `draw` draws it in the window.
+    pub fn paint(&self, canvas: &mut Canvas) {
+        let color = self.glow_color();
+        if !self.on_screen() || color[..3].iter().all(|&c| c <= 0.0) {
+            return;
+        }
+        for (from, to, f) in self.trail_steps() {
+            canvas.line(from, to, [color[0] * f, color[1] * f, color[2] * f, 1.0]);
+        }
+        canvas.dot(self.pos, 2.0, color);
     }
 
     pub fn on_screen(&self) -> bool {
@@ -118,75 +258,115 @@ impl Particle {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Firework {
     pub firework: Particle,
     pub exploded: bool,
     pub particles: Vec<Particle>,
-    pub highest_particle: Option<usize>,
+    /// The shell it bursts into, then the shell each of those stars bursts into,
+    /// and so on.
+    pub stages: Vec<Shell>,
 }
 impl Firework {
+    /// A random built-in shell launched from anywhere along the bottom of the
+    /// window.
     pub fn new() -> Firework {
         let mut rng = rand::thread_rng();
 
-        let firework = Particle::new(
-            rand::Rng::gen_range(&mut rng, 0.0..WINDOW_WIDTH as f64),
-            WINDOW_HEIGHT as f64,
-            true,
-            WHITE,
-        );
+        let x = rand::Rng::gen_range(&mut rng, 0.0..WINDOW_WIDTH as f64);
+        Firework::launch(x, BUILT_IN.stages(&BUILT_IN.random(&mut rng).name))
+    }
 
+    /// A shell launched from the bottom of the window at `x`, whose `stages` are
+    /// from `ShellLibrary::stages`.
+    pub fn launch(x: f64, stages: Vec<Shell>) -> Firework {
         Firework {
-            firework: firework,
+            firework: Particle::new(x, WINDOW_HEIGHT as f64, true, WHITE),
             exploded: false,
             particles: vec![],
-            highest_particle: None,
+            stages,
         }
     }
 
-    pub fn update(&mut self, gravity: Vector) {
+    /// A shell launched from the bottom of the window at `x`, `angle` degrees to
+    /// the right of straight up, that bursts `height` pixels above the bottom of
+    /// the window.
+    pub fn aimed(x: f64, angle: f64, height: f64, stages: Vec<Shell>) -> Firework {
+        let mut firework = Firework::launch(x, stages);
+        let climb = (2.0 * GRAVITY.y * height.max(0.0)).sqrt();
+        firework.firework.vel = Vector {
+            x: climb * angle.to_radians().tan(),
+            y: -climb,
+        };
+        firework
+    }
+
+    pub fn update<R: Rng>(&mut self, gravity: Vector, rng: &mut R) {
         if !self.exploded {
             self.firework.applyForce(gravity);
             self.firework.update();
             if self.firework.vel.y >= 1.0 {
                 self.exploded = true;
-                self.explode();
+                self.explode(rng);
             }
         } else {
+            let mut bursts = vec![];
             for p in &mut self.particles {
                 p.applyForce(gravity);
                 p.update();
+                if p.burned_out() && p.stage + 1 < self.stages.len() {
+                    bursts.push((p.pos, p.vel, p.stage + 1));
+                }
+            }
+            self.particles
+                .retain(|p| !p.burned_out() && p.pos.y <= WINDOW_HEIGHT as f64 + 5.0);
+            for (pos, vel, stage) in bursts {
+                self.burst(stage, pos, vel, rng);
             }
         }
     }
 
-    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+    /// Whether it has exploded and every star has burned out or fallen out of the
+    /// window.
+    pub fn done(&self) -> bool {
+        self.exploded && self.particles.is_empty()
+    }
+
+    pub fn draw(&self, transform: Matrix2d, gl: &mut GlGraphics) {
         if !self.exploded {
-            self.firework.show(gl, args);
+            self.firework.draw(transform, gl);
         } else {
             for p in &self.particles {
-                p.show(gl, args);
+                p.draw(transform, gl);
             }
         }
     }
 
-    pub fn explode(&mut self) {
-        for i in 0..100 {
-            let p = Particle::new(
-                self.firework.pos.x,
-                self.firework.pos.y,
-                false,
-                random_color(),
-            );
-            self.particles.push(p);
-            match self.highest_particle {
-                Some(index) => if p.vel.y < self.particles.get(index).unwrap().vel.y {
-                    self.highest_particle = Some(i);
-                }
-                None => self.highest_particle = Some(i),
+    /// Draws the firework onto `canvas` the way `draw` draws it in the window.
+    pub fn paint(&self, canvas: &mut Canvas) {
+        if !self.exploded {
+            self.firework.paint(canvas);
+        } else {
+            for p in &self.particles {
+                p.paint(canvas);
             }
         }
     }
+
+    pub fn explode<R: Rng>(&mut self, rng: &mut R) {
+        if !self.stages.is_empty() {
+            self.burst(0, self.firework.pos, self.firework.vel, rng);
+        }
+    }
+
+    // Bursts a shell of stage `stage` at `pos`, its stars carrying on with `vel`.
+    fn burst<R: Rng>(&mut self, stage: usize, pos: Vector, vel: Vector, rng: &mut R) {
+        let shell = &self.stages[stage];
+        for v in shell.velocities(rng) {
+            self.particles
+                .push(Particle::star(pos, vel + v, shell, stage, rng));
+        }
+    }
 }
 
 pub fn random_color() -> [f32; 4] {
@@ -208,3 +388,13 @@ fn from_rgba(pack: [f32; 4]) -> [f32; 4] {
 fn map_range(from_range: (f64, f64), to_range: (f64, f64), s: f64) -> f64 {
     to_range.0 + (s - from_range.0) * (to_range.1 - to_range.0) / (from_range.1 - from_range.0)
 }
+
+// A number from 0 to 1 that jumps about as `tick` goes up, always the same for
+// the same `seed` and `tick`.
+fn noise(seed: u32, tick: u32) -> f32 {
+    let mut h = seed ^ tick.wrapping_mul(0x27d4_eb2d);
+    h = (h ^ (h >> 15)).wrapping_mul(0x85eb_ca6b);
+    h = (h ^ (h >> 13)).wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32
+}
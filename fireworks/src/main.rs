@@ -10,12 +10,131 @@ use opengl_graphics::{GlGraphics, OpenGL, Texture, TextureSettings};
 use piston::event_loop::{EventSettings, Events};
 use piston::input::RenderEvent;
 use piston::window::WindowSettings;
-use piston::{AdvancedWindow, EventLoop, MouseCursorEvent, RenderArgs};
+use piston::{AdvancedWindow, EventLoop, MouseCursorEvent, RenderArgs, UpdateEvent};
 use rand::Rng;
+use Fireworks::glow::Glow;
+use Fireworks::shell::{ShellLibrary, BUILT_IN};
+use Fireworks::show::{Show, ShowPlayer};
 use Fireworks::Firework;
-use Fireworks::{vector::Vector, FPSCounter, Particle, BLACK, WHITE, WINDOW_HEIGHT, WINDOW_WIDTH};
+use Fireworks::{
+    FPSCounter, Particle, BLACK, GLOW_STRENGTH, GRAVITY, UPDATES_PER_SECOND, WHITE, WINDOW_HEIGHT,
+    WINDOW_WIDTH,
+};
 
+// Shows are always played with this seed, so the window and rendered frames match.
+const SHOW_SEED: u64 = 0;
+
+// How far Left and Right seek in a show, in seconds.
+const SEEK_STEP: f64 = 5.0;
+
+// The shell launched by each of the number keys, 1 launching the first shell.
+fn shell_key(key: piston::Key) -> Option<usize> {
+    use piston::Key::*;
+    [D1, D2, D3, D4, D5, D6, D7, D8, D9].iter().position(|&k| k == key)
+}
+
+// The built-in shells, along with any in `path`.
+fn load_library(path: Option<&String>) -> Option<ShellLibrary> {
+    match path {
+        Some(path) => match ShellLibrary::load(path) {
+            Ok(library) => Some(library),
+            Err(err) => {
+                println!("Could not load {}: {}", path, err);
+                None
+            }
+        },
+        None => Some(BUILT_IN.clone()),
+    }
+}
+
+fn load_show(path: &str, library: &ShellLibrary) -> Option<Show> {
+    let show = match Show::load(path) {
+        Ok(show) => show,
+        Err(err) => {
+            println!("Could not load {}: {}", path, err);
+            return None;
+        }
+    };
+    let missing = show.missing_shells(library);
+    if !missing.is_empty() {
+        println!("{} launches unknown shells: {}", path, missing.join(", "));
+        return None;
+    }
+    Some(show)
+}
+
+// `Fireworks render <show.txt> <out dir> [fps] [shells.json]` plays a show without
+// a window, saving it as a numbered sequence of PNG frames.
+fn render_headless(args: &[String]) {
+    let usage = "Usage: Fireworks render <show.txt> <out dir> [fps] [shells.json]";
+    let (path, out) = match (args.get(2), args.get(3)) {
+        (Some(path), Some(out)) => (path, out),
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let fps: u32 = match args.get(4).map_or(Ok(30), |f| f.parse()) {
+        Ok(fps) if fps > 0 => fps,
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let library = match load_library(args.get(5)) {
+        Some(library) => library,
+        None => return,
+    };
+    let show = match load_show(path, &library) {
+        Some(show) => show,
+        None => return,
+    };
+
+    println!("Rendering {} launches at {} fps to {}", show.launches.len(), fps, out);
+    let mut player = ShowPlayer::new(show, library, SHOW_SEED);
+    match player.render_frames(out, fps) {
+        Ok(frames) => println!("Saved {} frames ({:.1} s) to {}", frames, player.time(), out),
+        Err(err) => println!("Could not render to {}: {}", out, err),
+    }
+}
+
+// `Fireworks [shells.json]` launches random shells from the built-in ones and any
+// in `shells.json`, and the number keys launch a particular one at the mouse.
+// `Fireworks show <show.txt> [shells.json]` plays a show instead, where Space
+// pauses, Left and Right seek and Home starts again. G turns the glow on and off.
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (library, show) = match args.get(1).map(|a| a.as_str()) {
+        Some("render") => return render_headless(&args),
+        Some("show") => {
+            let path = match args.get(2) {
+                Some(path) => path,
+                None => {
+                    println!("Usage: Fireworks show <show.txt> [shells.json]");
+                    return;
+                }
+            };
+            let library = match load_library(args.get(3)) {
+                Some(library) => library,
+                None => return,
+            };
+            match load_show(path, &library) {
+                Some(show) => (library, Some(show)),
+                None => return,
+            }
+        }
+        _ => match load_library(args.get(1)) {
+            Some(library) => (library, None),
+            None => return,
+        },
+    };
+    for (i, shell) in library.shells.iter().enumerate() {
+        match i {
+            0..=8 => println!("{}: {}", i + 1, shell.name),
+            _ => println!("   {}", shell.name),
+        }
+    }
+
     let opengl = OpenGL::V3_2;
 
     let mut window: Window = WindowSettings::new("Fireworks", [WINDOW_WIDTH, WINDOW_HEIGHT])
@@ -32,25 +151,32 @@ fn main() {
 
     let mut events = Events::new(EventSettings::new());
     events.set_max_fps(144);
+    events.set_ups(UPDATES_PER_SECOND);
 
-    let mut fireworks = vec![Firework::new()];
-
-    let gravity = Vector {
-        x: 0.0,
-        y: WINDOW_HEIGHT as f64 / 6000.0,
-    };
+    let mut fireworks: Vec<Firework> = vec![];
+    let mut mouse_x = WINDOW_WIDTH as f64 / 2.0;
+    let mut player = show.map(|show| ShowPlayer::new(show, library.clone(), SHOW_SEED));
+    let glow = Glow::new(GLOW_STRENGTH);
+    let mut glowing = true;
 
     while let Some(e) = events.next(&mut window) {
         use graphics::*;
 
         if let Some(args) = e.render_args() {
-            gl.draw(args.viewport(), |_c, gl| {
-                clear(BLACK, gl);
-
-                for firework in &fireworks {
-                    firework.show(gl, &args);
+            let fireworks = player.as_ref().map_or(&fireworks, |p| &p.fireworks);
+            let draw_fireworks = |transform, gl: &mut GlGraphics| {
+                for firework in fireworks {
+                    firework.draw(transform, gl);
                 }
-            });
+            };
+            if glowing {
+                glow.draw(args.viewport(), &mut gl, draw_fireworks);
+            } else {
+                gl.draw(args.viewport(), |c, gl| {
+                    clear(BLACK, gl);
+                    draw_fireworks(c.transform, gl);
+                });
+            }
 
             let mut total_particles = 0;
 
@@ -64,31 +190,62 @@ fn main() {
                 };
             }
 
+            let timeline = player.as_ref().map_or(String::new(), |p| {
+                format!(
+                    " | Show {:.1} / {:.1} s{}",
+                    p.time(),
+                    p.show.last_launch(),
+                    if p.paused { " (paused)" } else { "" }
+                )
+            });
             window.set_title(format!(
-                "Fireworks | {:03} fps | Active Fireworks {:02} | Total Particles On Screen {:04}",
+                "Fireworks | {:03} fps | Active Fireworks {:02} | Total Particles On Screen {:04} | Glow {}{}",
                 fps_counter.tick(),
                 fireworks.len(),
-                total_particles
+                total_particles,
+                if glowing { "on" } else { "off" },
+                timeline
             ));
-        } else if let Some([x, y]) = e.mouse_cursor_args() {}
-        else {
+        } else if let Some([x, _]) = e.mouse_cursor_args() {
+            mouse_x = x;
+        } else if let piston::Event::Input(piston::Input::Button(b), _) = &e {
+            if let (piston::ButtonState::Release, piston::Button::Keyboard(k)) = (b.state, b.button) {
+                if k == piston::Key::G {
+                    glowing = !glowing;
+                }
+                match &mut player {
+                    Some(p) => match k {
+                        piston::Key::Space => p.paused = !p.paused,
+                        piston::Key::Left => p.seek(p.time() - SEEK_STEP),
+                        piston::Key::Right => p.seek(p.time() + SEEK_STEP),
+                        piston::Key::Home => p.restart(),
+                        _ => (),
+                    },
+                    None => {
+                        if let Some(shell) = shell_key(k).and_then(|i| library.shells.get(i)) {
+                            fireworks.push(Firework::launch(mouse_x, library.stages(&shell.name)));
+                        }
+                    }
+                }
+            }
+        } else if e.update_args().is_some() {
+            if let Some(p) = &mut player {
+                if !p.paused {
+                    p.step();
+                }
+                continue;
+            }
+
             if rng.gen_range(0.0..1.0) < 0.08 && fireworks.len() < 12 {
-                fireworks.push(Firework::new());
+                let x = rng.gen_range(0.0..WINDOW_WIDTH as f64);
+                fireworks.push(Firework::launch(x, library.stages(&library.random(&mut rng).name)));
             }
 
             for firework in &mut fireworks {
-                firework.update(gravity);
+                firework.update(GRAVITY, &mut rng);
             }
 
-            fireworks = fireworks
-                .iter()
-                .filter(|firework| {
-                    !(firework.exploded
-                        && firework.particles.get(firework
-                            .highest_particle.unwrap()).unwrap().pos.y > WINDOW_HEIGHT as f64 + 5.0)
-                })
-                .cloned()
-                .collect();
+            fireworks.retain(|firework| !firework.done());
         }
     }
 }
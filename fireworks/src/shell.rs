@@ -0,0 +1,335 @@
+//! Descriptions of firework shells: how their stars burst out, what they look
+//! like and what, if anything, each star bursts into when it burns out.
+
+use crate::{from_rgba, vector::Vector};
+use lazy_static::lazy_static;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// How many times stars can burst into more stars.
+const MAX_STAGES: usize = 4;
+
+// The most stars a single firework can end up with, counting every stage.
+const MAX_STARS: usize = 20_000;
+
+lazy_static! {
+    /// The shells that are always there, whatever else is loaded.
+    pub static ref BUILT_IN: ShellLibrary = ShellLibrary::built_in();
+}
+
+/// The directions stars burst out in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    /// A ball of stars, seen from the side so they're thickest in the middle.
+    Peony,
+    /// Stars evenly spaced around a circle.
+    Ring,
+    /// Stars thrown anywhere in a square, like the original fireworks.
+    Random,
+}
+
+/// A kind of firework shell, stored as JSON. Every field can be left out, in which
+/// case it's the same as the default peony:
+///
+/// ```json
+/// { "name": "green ring", "pattern": "Ring", "count": 40, "colors": [[40, 255, 90, 1.0]] }
+/// ```
+///
+/// Speeds are in pixels per update and times in updates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shell {
+    pub name: String,
+    pub pattern: Pattern,
+    /// How many stars it bursts into.
+    pub count: usize,
+    /// How fast the stars fly out.
+    pub speed: f64,
+    /// How much each star's speed can differ from `speed`, as a fraction of it.
+    pub spread: f64,
+    /// The colors stars are picked from, as `[r, g, b, a]` with red, green and blue
+    /// from 0 to 255 and alpha from 0 to 1. With no colors every star is random.
+    pub colors: Vec<[f32; 4]>,
+    /// The fraction of their speed stars lose every update.
+    pub drag: f64,
+    /// The shortest and longest a star burns for.
+    pub lifetime: [u32; 2],
+    /// How many updates each star spends blinking on and then off, or 0 for stars
+    /// that burn steadily.
+    pub strobe: u32,
+    /// The shell each star bursts into when it burns out.
+    pub burst: Option<String>,
+    /// How many of its past positions each star leaves a trail through.
+    pub trail: usize,
+    /// How much stars' brightness wavers from update to update, from 0 to 1.
+    pub flicker: f32,
+    /// The chance each update of a star glinting white.
+    pub sparkle: f32,
+}
+impl Default for Shell {
+    fn default() -> Shell {
+        Shell {
+            name: "peony".to_string(),
+            pattern: Pattern::Peony,
+            count: 120,
+            speed: 6.0,
+            spread: 0.1,
+            colors: vec![],
+            drag: 0.02,
+            lifetime: [70, 90],
+            strobe: 0,
+            burst: None,
+            trail: 5,
+            flicker: 0.2,
+            sparkle: 0.0,
+        }
+    }
+}
+impl Shell {
+    /// The velocity of every star in a burst, relative to the shell.
+    pub fn velocities<R: Rng>(&self, rng: &mut R) -> Vec<Vector> {
+        let turn = rng.gen_range(0.0..2.0 * PI);
+        (0..self.count)
+            .map(|i| {
+                let speed = self.speed * (1.0 + rng.gen_range(-1.0..=1.0) * self.spread);
+                match self.pattern {
+                    Pattern::Peony => {
+                        // A random direction in 3D, flattened onto the screen.
+                        let z: f64 = rng.gen_range(-1.0..1.0);
+                        Vector::from_angle(rng.gen_range(0.0..2.0 * PI)) * ((1.0 - z * z).sqrt() * speed)
+                    }
+                    Pattern::Ring => Vector::from_angle(turn + 2.0 * PI * i as f64 / self.count as f64) * speed,
+                    Pattern::Random => Vector {
+                        x: rng.gen_range(-1.0..1.0),
+                        y: rng.gen_range(-1.0..1.0),
+                    } * speed,
+                }
+            })
+            .collect()
+    }
+
+    /// The color of a new star.
+    pub fn color<R: Rng>(&self, rng: &mut R) -> [f32; 4] {
+        if self.colors.is_empty() {
+            [rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), 1.0]
+        } else {
+            from_rgba(self.colors[rng.gen_range(0..self.colors.len())])
+        }
+    }
+
+    /// How long a new star burns for.
+    pub fn lifetime<R: Rng>(&self, rng: &mut R) -> u32 {
+        let [a, b] = self.lifetime;
+        rng.gen_range(a.min(b)..=a.max(b))
+    }
+}
+
+/// A set of shells, looked up by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellLibrary {
+    pub shells: Vec<Shell>,
+}
+impl ShellLibrary {
+    pub fn built_in() -> ShellLibrary {
+        const GOLD: [f32; 4] = [255.0, 190.0, 70.0, 1.0];
+        const WHITE: [f32; 4] = [255.0, 255.0, 255.0, 1.0];
+        let shell = |name: &str| Shell {
+            name: name.to_string(),
+            ..Shell::default()
+        };
+        ShellLibrary {
+            shells: vec![
+                shell("peony"),
+                Shell {
+                    pattern: Pattern::Ring,
+                    count: 60,
+                    speed: 5.0,
+                    spread: 0.02,
+                    colors: vec![[60.0, 140.0, 255.0, 1.0], [150.0, 220.0, 255.0, 1.0]],
+                    ..shell("ring")
+                },
+                Shell {
+                    count: 150,
+                    speed: 7.0,
+                    spread: 0.2,
+                    colors: vec![GOLD, [230.0, 150.0, 40.0, 1.0]],
+                    drag: 0.05,
+                    lifetime: [180, 240],
+                    trail: 14,
+                    flicker: 0.4,
+                    sparkle: 0.03,
+                    ..shell("willow")
+                },
+                Shell {
+                    count: 16,
+                    speed: 5.0,
+                    colors: vec![GOLD],
+                    lifetime: [30, 36],
+                    burst: Some("cross".to_string()),
+                    trail: 8,
+                    ..shell("crossette")
+                },
+                Shell {
+                    pattern: Pattern::Ring,
+                    count: 4,
+                    speed: 3.0,
+                    spread: 0.0,
+                    colors: vec![GOLD, WHITE],
+                    lifetime: [40, 50],
+                    trail: 8,
+                    sparkle: 0.05,
+                    ..shell("cross")
+                },
+                Shell {
+                    count: 80,
+                    colors: vec![WHITE],
+                    lifetime: [90, 120],
+                    strobe: 4,
+                    trail: 0,
+                    flicker: 0.0,
+                    ..shell("strobe")
+                },
+                Shell {
+                    count: 10,
+                    speed: 4.0,
+                    colors: vec![[255.0, 60.0, 60.0, 1.0]],
+                    lifetime: [35, 40],
+                    burst: Some("pistil".to_string()),
+                    trail: 10,
+                    ..shell("multi-stage")
+                },
+                Shell {
+                    count: 30,
+                    speed: 2.5,
+                    colors: vec![[255.0, 230.0, 90.0, 1.0], [90.0, 255.0, 120.0, 1.0]],
+                    lifetime: [40, 60],
+                    ..shell("pistil")
+                },
+            ],
+        }
+    }
+
+    /// The built-in shells along with the shells in a JSON file, which replace any
+    /// built-in shells with the same name.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ShellLibrary> {
+        let shells: Vec<Shell> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut library = ShellLibrary::built_in();
+        for shell in shells {
+            match library.shells.iter_mut().find(|s| s.name == shell.name) {
+                Some(known) => *known = shell,
+                None => library.shells.push(shell),
+            }
+        }
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        for shell in &library.shells {
+            if let Some(burst) = &shell.burst {
+                if library.get(burst).is_none() {
+                    return invalid(format!("shell {} bursts into unknown shell {}", shell.name, burst));
+                }
+            }
+        }
+        for shell in &library.shells {
+            // Follow the shell's bursts until they run out, which they must do
+            // without coming back to a shell already burst.
+            let mut seen = vec![shell.name.as_str()];
+            let mut next = shell.burst.as_deref();
+            while let Some(burst) = next {
+                if seen.contains(&burst) {
+                    return invalid(format!("shell {} bursts into itself", shell.name));
+                }
+                seen.push(burst);
+                next = library.get(burst).and_then(|s| s.burst.as_deref());
+            }
+
+            // Every star in one stage bursts into a whole shell of the next.
+            let (mut stars, mut layer) = (0usize, 1usize);
+            for stage in library.stages(&shell.name) {
+                layer = layer.saturating_mul(stage.count);
+                stars = stars.saturating_add(layer);
+            }
+            if stars > MAX_STARS {
+                return invalid(format!(
+                    "shell {} bursts into {} stars, more than the {} allowed",
+                    shell.name, stars, MAX_STARS
+                ));
+            }
+        }
+        Ok(library)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Shell> {
+        self.shells.iter().find(|s| s.name == name)
+    }
+
+    pub fn random<R: Rng>(&self, rng: &mut R) -> &Shell {
+        &self.shells[rng.gen_range(0..self.shells.len())]
+    }
+
+    /// The shell called `name`, followed by the shell its stars burst into, and so
+    /// on. Empty if there's no such shell.
+    pub fn stages(&self, name: &str) -> Vec<Shell> {
+        let mut stages: Vec<Shell> = vec![];
+        let mut next = self.get(name);
+        while let Some(shell) = next {
+            if stages.len() == MAX_STAGES {
+                break;
+            }
+            stages.push(shell.clone());
+            next = shell.burst.as_ref().and_then(|burst| self.get(burst));
+        }
+        stages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Loads `json` as a shell file, each from a file of its own as tests run at
+    // the same time.
+    fn load(json: &str) -> io::Result<ShellLibrary> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let file = FILES.fetch_add(1, Ordering::SeqCst);
+        let path = std::env::temp_dir().join(format!("shells_{}_{}.json", std::process::id(), file));
+        fs::write(&path, json).unwrap();
+        let library = ShellLibrary::load(&path);
+        fs::remove_file(&path).unwrap();
+        library
+    }
+
+    #[test]
+    fn built_in_shells_load() {
+        assert_eq!(load("[]").unwrap(), ShellLibrary::built_in());
+    }
+
+    #[test]
+    fn load_rejects_burst_cycles() {
+        for json in [
+            r#"[{"name": "x", "burst": "x"}]"#,
+            r#"[{"name": "a", "count": 2, "burst": "b"}, {"name": "b", "count": 2, "burst": "a"}]"#,
+            r#"[{"name": "cross", "count": 2, "burst": "crossette"}]"#,
+        ]
+        .iter()
+        {
+            let err = load(json).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", json);
+            assert!(err.to_string().contains("bursts into itself"), "{}: {}", json, err);
+        }
+    }
+
+    #[test]
+    fn load_caps_stars_per_firework() {
+        let json = r#"[{"name": "a", "count": 200, "burst": "b"}, {"name": "b", "count": 200}]"#;
+        let err = load(json).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("40200 stars"), "{}", err);
+
+        let json = r#"[{"name": "a", "count": 100, "burst": "b"}, {"name": "b", "count": 100}]"#;
+        assert_eq!(load(json).unwrap().stages("a").len(), 2);
+    }
+}
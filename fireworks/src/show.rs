@@ -0,0 +1,251 @@
+//! Scripted firework shows, and a player that runs them the same way every time,
+//! in the window or rendered to a sequence of frames.
+
+use crate::{
+    canvas::Canvas, shell::ShellLibrary, Firework, GLOW_RADIUS, GLOW_STRENGTH, GRAVITY, UPDATES_PER_SECOND,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// One shell going up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Launch {
+    /// Seconds from the start of the show.
+    pub time: f64,
+    /// Where along the bottom of the window it goes up from.
+    pub x: f64,
+    pub shell: String,
+    /// Degrees to the right of straight up.
+    pub angle: f64,
+    /// How far above the bottom of the window it bursts.
+    pub height: f64,
+}
+
+/// A list of launches, stored as text, one per line with their fields separated
+/// by commas:
+///
+/// ```text
+/// # time, x, shell, angle, height
+/// 0.0, 960, peony, 0, 700
+/// 1.5, 400, green ring, -10, 600
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Show {
+    /// Launches in time order.
+    pub launches: Vec<Launch>,
+}
+impl Show {
+    pub fn new(mut launches: Vec<Launch>) -> Show {
+        launches.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        Show { launches }
+    }
+
+    /// When the last shell goes up, in seconds.
+    pub fn last_launch(&self) -> f64 {
+        self.launches.last().map_or(0.0, |l| l.time)
+    }
+
+    /// The names of shells launched in the show that aren't in `library`.
+    pub fn missing_shells(&self, library: &ShellLibrary) -> Vec<&str> {
+        let mut missing: Vec<&str> = vec![];
+        for launch in &self.launches {
+            if library.get(&launch.shell).is_none() && !missing.contains(&launch.shell.as_str()) {
+                missing.push(&launch.shell);
+            }
+        }
+        missing
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, "# time, x, shell, angle, height")?;
+        for l in &self.launches {
+            writeln!(w, "{}, {}, {}, {}, {}", l.time, l.x, l.shell, l.angle, l.height)?;
+        }
+        w.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Show> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut launches = vec![];
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some(launch) => launches.push(launch),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid launch on line {}: {}", number + 1, line),
+                    ))
+                }
+            }
+        }
+        Ok(Show::new(launches))
+    }
+}
+
+fn parse_line(line: &str) -> Option<Launch> {
+    let parts: Vec<&str> = line.split(',').map(|p| p.trim()).collect();
+    if parts.len() != 5 || parts[2].is_empty() {
+        return None;
+    }
+    // Launches are sorted by time, so NaN can't be let through, and neither can
+    // anything infinite.
+    let number = |part: &str| part.parse::<f64>().ok().filter(|n| n.is_finite());
+    Some(Launch {
+        time: number(parts[0])?,
+        x: number(parts[1])?,
+        shell: parts[2].to_string(),
+        angle: number(parts[3])?,
+        height: number(parts[4])?,
+    })
+}
+
+/// Plays a show update by update. Shows are seeded, so playing one again, or
+/// seeking back and forth in it, always gives the same fireworks.
+pub struct ShowPlayer {
+    pub show: Show,
+    pub library: ShellLibrary,
+    pub seed: u64,
+    /// How many updates into the show it is.
+    pub tick: u64,
+    pub paused: bool,
+    pub fireworks: Vec<Firework>,
+    // The first launch that hasn't gone up yet.
+    next: usize,
+    rng: StdRng,
+}
+impl ShowPlayer {
+    pub fn new(show: Show, library: ShellLibrary, seed: u64) -> ShowPlayer {
+        ShowPlayer {
+            show,
+            library,
+            seed,
+            tick: 0,
+            paused: false,
+            fireworks: vec![],
+            next: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// How far into the show it is, in seconds.
+    pub fn time(&self) -> f64 {
+        self.tick as f64 / UPDATES_PER_SECOND as f64
+    }
+
+    /// Whether every shell has gone up and every star burned out.
+    pub fn finished(&self) -> bool {
+        self.next == self.show.launches.len() && self.fireworks.is_empty()
+    }
+
+    /// Goes back to the start of the show.
+    pub fn restart(&mut self) {
+        self.tick = 0;
+        self.fireworks.clear();
+        self.next = 0;
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    /// Launches whatever is due and moves everything on by one update, paused
+    /// or not.
+    pub fn step(&mut self) {
+        let now = self.time();
+        while let Some(launch) = self.show.launches.get(self.next) {
+            if launch.time > now {
+                break;
+            }
+            let stages = self.library.stages(&launch.shell);
+            self.fireworks
+                .push(Firework::aimed(launch.x, launch.angle, launch.height, stages));
+            self.next += 1;
+        }
+        for firework in &mut self.fireworks {
+            firework.update(GRAVITY, &mut self.rng);
+        }
+        self.fireworks.retain(|firework| !firework.done());
+        self.tick += 1;
+    }
+
+    /// Jumps to `time` seconds into the show, replaying it from the start when
+    /// going backwards.
+    pub fn seek(&mut self, time: f64) {
+        let target = (time.max(0.0) * UPDATES_PER_SECOND as f64).round() as u64;
+        if target < self.tick {
+            self.restart();
+        }
+        while self.tick < target {
+            self.step();
+        }
+    }
+
+    /// Plays the show from the start to the end, saving `fps` frames a second to
+    /// `dir` as `frame_00000.png`, `frame_00001.png` and so on. Returns how many
+    /// frames were saved.
+    pub fn render_frames<P: AsRef<Path>>(&mut self, dir: P, fps: u32) -> image::ImageResult<u32> {
+        fs::create_dir_all(&dir)?;
+        self.restart();
+        let mut canvas = Canvas::new(WINDOW_WIDTH, WINDOW_HEIGHT);
+        let mut frame = 0;
+        loop {
+            canvas.clear();
+            for firework in &self.fireworks {
+                firework.paint(&mut canvas);
+            }
+            canvas.bloom(GLOW_RADIUS, GLOW_STRENGTH);
+            canvas.save(dir.as_ref().join(format!("frame_{:05}.png", frame)))?;
+            frame += 1;
+
+            if self.finished() && self.tick > 0 {
+                return Ok(frame);
+            }
+            let seconds = frame as f64 / fps.max(1) as f64;
+            self.seek(seconds);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_reads_launches() {
+        assert_eq!(
+            parse_line("1.5, 400, green ring, -10, 600"),
+            Some(Launch {
+                time: 1.5,
+                x: 400.0,
+                shell: "green ring".to_string(),
+                angle: -10.0,
+                height: 600.0,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_line_rejects_bad_launches() {
+        for line in [
+            "1.5, 400, peony, -10",
+            "1.5, 400, , -10, 600",
+            "soon, 400, peony, -10, 600",
+            "NaN, 400, peony, -10, 600",
+            "1.5, inf, peony, -10, 600",
+            "1.5, 400, peony, -infinity, 600",
+            "1.5, 400, peony, -10, 1e400",
+        ]
+        .iter()
+        {
+            assert_eq!(parse_line(line), None, "{}", line);
+        }
+    }
+}
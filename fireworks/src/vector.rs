@@ -22,6 +22,17 @@ impl Vector {
         return sqrt_diff;
     }
 
+    pub fn from_angle(angle: f64) -> Vector {
+        Vector {
+            x: angle.cos(),
+            y: angle.sin(),
+        }
+    }
+
+    pub fn mag(&self) -> f64 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
     pub fn random2D() -> Vector {
         let mut rng = rand::thread_rng();
 
@@ -0,0 +1,121 @@
+//! A floating point image that particle trails are accumulated into.
+
+use crate::vector::Vector;
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+
+/// An RGB image kept in floating point, so very faint lines still add up and
+/// fading never gets stuck at a dim level the way 8-bit colors would.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    /// Canvas pixels per window pixel. Higher-resolution canvases are used for export.
+    pub scale: f64,
+    pub background: [f32; 3],
+    pixels: Vec<[f32; 3]>,
+}
+impl Canvas {
+    pub fn new(width: u32, height: u32, scale: f64) -> Canvas {
+        let (width, height) = (
+            (width as f64 * scale).round() as u32,
+            (height as f64 * scale).round() as u32,
+        );
+        Canvas {
+            width,
+            height,
+            scale,
+            background: [0.0; 3],
+            pixels: vec![[0.0; 3]; (width * height) as usize],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let background = self.background;
+        for p in &mut self.pixels {
+            *p = background;
+        }
+    }
+
+    /// Moves every pixel `rate` of the way back towards the background.
+    pub fn fade(&mut self, rate: f32) {
+        if rate <= 0.0 {
+            return;
+        }
+        let background = self.background;
+        for p in &mut self.pixels {
+            for c in 0..3 {
+                p[c] += (background[c] - p[c]) * rate;
+            }
+        }
+    }
+
+    fn blend(&mut self, x: i64, y: i64, color: [f32; 4], coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let a = color[3] * coverage;
+        let p = &mut self.pixels[(x + y * self.width as i64) as usize];
+        for c in 0..3 {
+            p[c] += (color[c] - p[c]) * a;
+        }
+    }
+
+    /// Draws an antialiased, one canvas pixel wide line between two points given
+    /// in window coordinates (Xiaolin Wu's algorithm).
+    pub fn line(&mut self, from: Vector, to: Vector, color: [f32; 4]) {
+        let (mut x0, mut y0) = (from.x * self.scale, from.y * self.scale);
+        let (mut x1, mut y1) = (to.x * self.scale, to.y * self.scale);
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+
+        let plot = |canvas: &mut Canvas, x: f64, y: f64, coverage: f64| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            canvas.blend(x as i64, y as i64, color, coverage as f32);
+        };
+
+        // Sub-pixel segments still leave a faint mark, weighted by their length.
+        let length = dx.max(1e-9);
+        let x_start = x0.round();
+        let x_end = x1.round();
+        let mut y = y0 + gradient * (x_start - x0);
+        let mut x = x_start;
+        while x <= x_end {
+            let weight = if x_start == x_end { length.min(1.0) } else { 1.0 };
+            let frac = y - y.floor();
+            plot(self, x, y.floor(), (1.0 - frac) * weight);
+            plot(self, x, y.floor() + 1.0, frac * weight);
+            y += gradient;
+            x += 1.0;
+        }
+    }
+
+    pub fn to_image(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        for (i, p) in self.pixels.iter().enumerate() {
+            let to_byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            image.put_pixel(
+                i as u32 % self.width,
+                i as u32 / self.width,
+                Rgba([to_byte(p[0]), to_byte(p[1]), to_byte(p[2]), 255]),
+            );
+        }
+        image
+    }
+
+    /// Writes the canvas to an image file, in a format chosen by the extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> image::ImageResult<()> {
+        self.to_image().save(path)
+    }
+}
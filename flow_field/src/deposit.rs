@@ -0,0 +1,97 @@
+//! A second field that particles write their direction into as they move, so they
+//! steer each other the way slime molds follow each other's trails.
+
+use crate::{field::FlowField, particles::Particles, vector::Vector};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deposit {
+    /// What has been laid down so far, on the same kind of grid as a flow field.
+    pub field: FlowField,
+    /// Length of the direction each particle lays down per step.
+    pub amount: f64,
+    /// Fraction of each grid point that is swapped with its neighbours per step.
+    pub diffusion: f64,
+    /// Fraction of the deposit that fades away per step.
+    pub decay: f64,
+    /// Force the deposit adds to the field at full strength.
+    pub weight: f64,
+}
+impl Deposit {
+    pub fn new(width: u32, height: u32, scl: f64) -> Deposit {
+        Deposit {
+            field: FlowField::new(width, height, scl),
+            amount: 0.02,
+            diffusion: 0.2,
+            decay: 0.02,
+            weight: 0.1,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for v in &mut self.field.vectors {
+            *v = Vector::default();
+        }
+    }
+
+    /// Adds the direction every moving particle is heading in, shared between the
+    /// four grid points around it.
+    pub fn deposit(&mut self, particles: &Particles) {
+        let (cols, rows, scl) = (self.field.cols as i64, self.field.rows as i64, self.field.scl);
+        for (pos, vel) in particles.pos.iter().zip(&particles.vel) {
+            let speed = vel.mag();
+            if speed == 0.0 {
+                continue;
+            }
+            let dir = *vel * (self.amount / speed);
+
+            let (gx, gy) = (pos.x / scl, pos.y / scl);
+            let (x, y) = (gx.floor() as i64, gy.floor() as i64);
+            let (tx, ty) = (gx - gx.floor(), gy - gy.floor());
+            for &(dx, dy, w) in [
+                (0, 0, (1.0 - tx) * (1.0 - ty)),
+                (1, 0, tx * (1.0 - ty)),
+                (0, 1, (1.0 - tx) * ty),
+                (1, 1, tx * ty),
+            ]
+            .iter()
+            {
+                let (px, py) = (x + dx, y + dy);
+                if px >= 0 && px < cols && py >= 0 && py < rows {
+                    self.field.vectors[(px + py * cols) as usize] += dir * w;
+                }
+            }
+        }
+    }
+
+    /// Blurs the deposit into neighbouring grid points and fades it.
+    pub fn spread(&mut self) {
+        let (cols, rows) = (self.field.cols as i64, self.field.rows as i64);
+        let old = self.field.vectors.clone();
+        let at = |x: i64, y: i64| old[(x.max(0).min(cols - 1) + y.max(0).min(rows - 1) * cols) as usize];
+        for y in 0..rows {
+            for x in 0..cols {
+                let neighbours = (at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1)) * 0.25;
+                let blurred = at(x, y) * (1.0 - self.diffusion) + neighbours * self.diffusion;
+                self.field.vectors[(x + y * cols) as usize] = blurred * (1.0 - self.decay);
+            }
+        }
+    }
+
+    /// Adds the deposit to `field`, which must have the same grid. Each grid point
+    /// of the deposit counts for at most `weight`.
+    pub fn apply(&self, field: &mut FlowField) {
+        debug_assert_eq!((field.cols, field.rows), (self.field.cols, self.field.rows));
+        for (v, d) in field.vectors.iter_mut().zip(&self.field.vectors) {
+            let mut d = *d;
+            d.limit_mag(1.0);
+            *v += d * self.weight;
+        }
+    }
+
+    /// Lays down this step's deposit, spreads it and adds it to `field`.
+    pub fn couple(&mut self, particles: &Particles, field: &mut FlowField) {
+        self.deposit(particles);
+        self.spread();
+        self.apply(field);
+    }
+}
@@ -0,0 +1,109 @@
+//! Shapes that particles are spawned from.
+
+use crate::{vector::Vector, WINDOW_HEIGHT, WINDOW_WIDTH};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use std::f64::consts::PI;
+use std::path::Path;
+
+// Particles from a point emitter land this far from it at most, so they don't
+// all follow exactly the same path.
+const POINT_JITTER: f64 = 5.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Emitter {
+    Point(Vector),
+    /// Anywhere along the line between two points.
+    Line(Vector, Vector),
+    /// Anywhere on the edge of a circle.
+    Circle { center: Vector, radius: f64 },
+    /// The bright pixels of an image stretched over the window, each as likely as
+    /// any other. `points` are the pixels' top left corners and `pixel` their size.
+    Mask {
+        name: String,
+        points: Vec<Vector>,
+        pixel: Vector,
+    },
+}
+impl Emitter {
+    /// Loads an image and keeps the pixels brighter than half as spawn points.
+    pub fn mask<P: AsRef<Path>>(path: P) -> image::ImageResult<Emitter> {
+        let image = image::open(path.as_ref())?.to_luma8();
+        let sx = WINDOW_WIDTH as f64 / image.width() as f64;
+        let sy = WINDOW_HEIGHT as f64 / image.height() as f64;
+        let points = image
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[0] > 127)
+            .map(|(x, y, _)| Vector {
+                x: x as f64 * sx,
+                y: y as f64 * sy,
+            })
+            .collect();
+        let name = path
+            .as_ref()
+            .file_name()
+            .map_or("Mask".to_string(), |n| n.to_string_lossy().to_string());
+        Ok(Emitter::Mask {
+            name,
+            points,
+            pixel: Vector { x: sx, y: sy },
+        })
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Emitter::Point(_) => "Point".to_string(),
+            Emitter::Line(..) => "Line".to_string(),
+            Emitter::Circle { .. } => "Circle".to_string(),
+            Emitter::Mask { name, .. } => name.clone(),
+        }
+    }
+
+    /// A spawn point picked by two numbers between 0 and 1. Evenly spread numbers
+    /// give points evenly spread over the shape.
+    pub fn sample(&self, u: f64, v: f64) -> Vector {
+        match self {
+            Emitter::Point(p) => *p + Vector::from_angle(u * 2.0 * PI) * (v.sqrt() * POINT_JITTER),
+            Emitter::Line(a, b) => *a + (*b - *a) * u,
+            Emitter::Circle { center, radius } => *center + Vector::from_angle(u * 2.0 * PI) * *radius,
+            Emitter::Mask { points, pixel, .. } => {
+                if points.is_empty() {
+                    return Vector {
+                        x: u * WINDOW_WIDTH as f64,
+                        y: v * WINDOW_HEIGHT as f64,
+                    };
+                }
+                // What's left of `u` after picking a pixel places the point across it.
+                let scaled = u * points.len() as f64;
+                let i = (scaled as usize).min(points.len() - 1);
+                points[i] + Vector {
+                    x: scaled.fract() * pixel.x,
+                    y: v * pixel.y,
+                }
+            }
+        }
+    }
+
+    pub fn draw(&self, color: [f32; 4], transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        match self {
+            Emitter::Point(p) => {
+                ellipse(color, [p.x - 6.0, p.y - 6.0, 12.0, 12.0], transform, gl);
+            }
+            Emitter::Line(a, b) => {
+                line(color, 1.5, [a.x, a.y, b.x, b.y], transform, gl);
+            }
+            Emitter::Circle { center, radius } => {
+                Ellipse::new_border(color, 1.0).draw(
+                    [center.x - radius, center.y - radius, 2.0 * radius, 2.0 * radius],
+                    &Default::default(),
+                    transform,
+                    gl,
+                );
+            }
+            // Drawing every pixel of a mask would hide the particles.
+            Emitter::Mask { .. } => (),
+        }
+    }
+}
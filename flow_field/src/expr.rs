@@ -0,0 +1,428 @@
+//! A small parser and evaluator for the math expressions used by analytic fields.
+//!
+//! Expressions may use numbers, the variables `x`, `y`, `t`, `r` (distance from the
+//! origin) and `a` (angle around the origin), the constants `pi` and `e`, the
+//! operators `+ - * / ^`, parentheses and the functions `sin cos tan asin acos atan
+//! sinh cosh tanh sqrt abs exp ln floor ceil sign`, plus `atan2(y, x)`, `min(a, b)`
+//! and `max(a, b)`.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Var(char),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Byte offset into the expression where the error was found.
+    pub position: usize,
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+impl std::error::Error for ParseError {}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, ParseError> {
+        let mut parser = Parser { text, pos: 0 };
+        let expr = parser.sum()?;
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(expr)
+    }
+
+    pub fn eval(&self, x: f64, y: f64, t: f64) -> f64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Var('x') => x,
+            Expr::Var('y') => y,
+            Expr::Var('t') => t,
+            Expr::Var('r') => (x * x + y * y).sqrt(),
+            Expr::Var('a') => y.atan2(x),
+            Expr::Var(_) => 0.0,
+            Expr::Neg(e) => -e.eval(x, y, t),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(x, y, t), b.eval(x, y, t));
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    '^' => a.powf(b),
+                    _ => 0.0,
+                }
+            }
+            Expr::Call(name, args) => {
+                let args: Vec<f64> = args.iter().map(|a| a.eval(x, y, t)).collect();
+                match (name.as_str(), args.as_slice()) {
+                    ("sin", [a]) => a.sin(),
+                    ("cos", [a]) => a.cos(),
+                    ("tan", [a]) => a.tan(),
+                    ("asin", [a]) => a.asin(),
+                    ("acos", [a]) => a.acos(),
+                    ("atan", [a]) => a.atan(),
+                    ("sinh", [a]) => a.sinh(),
+                    ("cosh", [a]) => a.cosh(),
+                    ("tanh", [a]) => a.tanh(),
+                    ("sqrt", [a]) => a.sqrt(),
+                    ("abs", [a]) => a.abs(),
+                    ("exp", [a]) => a.exp(),
+                    ("ln", [a]) => a.ln(),
+                    ("floor", [a]) => a.floor(),
+                    ("ceil", [a]) => a.ceil(),
+                    ("sign", [a]) => a.signum(),
+                    ("atan2", [a, b]) => a.atan2(*b),
+                    ("min", [a, b]) => a.min(*b),
+                    ("max", [a, b]) => a.max(*b),
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+}
+
+const FUNCTIONS: [(&str, usize); 19] = [
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("asin", 1),
+    ("acos", 1),
+    ("atan", 1),
+    ("sinh", 1),
+    ("cosh", 1),
+    ("tanh", 1),
+    ("sqrt", 1),
+    ("abs", 1),
+    ("exp", 1),
+    ("ln", 1),
+    ("floor", 1),
+    ("ceil", 1),
+    ("sign", 1),
+    ("atan2", 2),
+    ("min", 2),
+    ("max", 2),
+];
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            message: message.to_string(),
+            position: self.pos,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    // sum = product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(left);
+            };
+            let right = self.product()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    // product = unary (('*' | '/') unary)*
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else {
+                return Ok(left);
+            };
+            let right = self.unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    // unary = '-' unary | power
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    // power = atom ('^' unary)?, so `2^-x` works and `-2^2` is `-(2^2)`.
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            Ok(Expr::Binary('^', Box::new(base), Box::new(exponent)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.sum()?;
+                if !self.eat(')') {
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                // An exponent, as in `1e-3`.
+                let rest = &self.text[self.pos..];
+                if rest.starts_with('e') || rest.starts_with('E') {
+                    let digits = rest[1..].strip_prefix(|c| c == '+' || c == '-').unwrap_or(&rest[1..]);
+                    if digits.starts_with(|c: char| c.is_ascii_digit()) {
+                        self.pos += rest.len() - digits.len();
+                        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                            self.pos += 1;
+                        }
+                    }
+                }
+                self.text[start..self.pos]
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| ParseError {
+                        message: "invalid number".to_string(),
+                        position: start,
+                    })
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                    self.pos += 1;
+                }
+                let name = &self.text[start..self.pos];
+                match name {
+                    "x" | "y" | "t" | "r" | "a" => return Ok(Expr::Var(name.chars().next().unwrap())),
+                    "pi" => return Ok(Expr::Number(std::f64::consts::PI)),
+                    "e" => return Ok(Expr::Number(std::f64::consts::E)),
+                    _ => (),
+                }
+
+                let arity = match FUNCTIONS.iter().find(|(f, _)| *f == name) {
+                    Some((_, arity)) => *arity,
+                    None => {
+                        return Err(ParseError {
+                            message: format!("unknown name '{}'", name),
+                            position: start,
+                        })
+                    }
+                };
+                if !self.eat('(') {
+                    return Err(self.error("expected '('"));
+                }
+                let mut args = vec![self.sum()?];
+                while self.eat(',') {
+                    args.push(self.sum()?);
+                }
+                if !self.eat(')') {
+                    return Err(self.error("expected ')'"));
+                }
+                if args.len() != arity {
+                    return Err(ParseError {
+                        message: format!("{} takes {} argument(s)", name, arity),
+                        position: start,
+                    });
+                }
+                Ok(Expr::Call(name.to_string(), args))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+}
+
+/// Splits `(a, b)` into `a` and `b` at its top-level comma.
+pub fn parse_pair(text: &str) -> Result<(Expr, Expr), ParseError> {
+    let trimmed = text.trim();
+    let inner = if trimmed.starts_with('(') && closing_paren(trimmed) == Some(trimmed.len() - 1) {
+        &trimmed[1..trimmed.len() - 1]
+    } else {
+        trimmed
+    };
+    let offset = text.find(inner).unwrap_or(0);
+
+    let mut depth = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                let first = Expr::parse(&inner[..i]).map_err(|e| ParseError {
+                    position: e.position + offset,
+                    ..e
+                })?;
+                let second = Expr::parse(&inner[i + 1..]).map_err(|e| ParseError {
+                    position: e.position + offset + i + 1,
+                    ..e
+                })?;
+                return Ok((first, second));
+            }
+            _ => (),
+        }
+    }
+    Err(ParseError {
+        message: "expected two components, as in (sin(y), cos(x))".to_string(),
+        position: 0,
+    })
+}
+
+// Index of the parenthesis closing the one `text` starts with.
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, x: f64) -> f64 {
+        Expr::parse(text).unwrap().eval(x, 0.0, 0.0)
+    }
+
+    fn error(text: &str) -> ParseError {
+        Expr::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(eval("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(eval("8 - 4 - 2", 0.0), 2.0);
+        assert_eq!(eval("8 / 4 / 2", 0.0), 1.0);
+        assert_eq!(eval("2 * 3 ^ 2", 0.0), 18.0);
+        assert_eq!(eval("2 ^ 3 ^ 2", 0.0), 512.0);
+    }
+
+    #[test]
+    fn power_binds_tighter_than_unary_minus() {
+        assert_eq!(eval("-x^2", 3.0), -9.0);
+        assert_eq!(eval("(-x)^2", 3.0), 9.0);
+        assert_eq!(eval("2^-x", 1.0), 0.5);
+        assert_eq!(eval("--x", 3.0), 3.0);
+    }
+
+    #[test]
+    fn numbers_can_have_exponents() {
+        assert_eq!(eval("1e3", 0.0), 1000.0);
+        assert_eq!(eval("2.5E-1", 0.0), 0.25);
+        assert_eq!(eval("1e+2", 0.0), 100.0);
+        assert_eq!(eval(".5", 0.0), 0.5);
+        // Without digits after it, `e` isn't part of the number.
+        assert_eq!(error("2e").message, "unexpected input");
+        assert_eq!(error("1.2.3").message, "invalid number");
+    }
+
+    #[test]
+    fn unknown_names_are_rejected_where_they_start() {
+        let err = error("x + foo(y)");
+        assert_eq!(err.message, "unknown name 'foo'");
+        assert_eq!(err.position, 4);
+        assert_eq!(error("z").message, "unknown name 'z'");
+    }
+
+    #[test]
+    fn functions_check_their_arity() {
+        assert_eq!(eval("max(x, 2)", 1.0), 2.0);
+        assert_eq!(eval("atan2(0, x)", -1.0), std::f64::consts::PI);
+        assert_eq!(error("sin(x, y)").message, "sin takes 1 argument(s)");
+        assert_eq!(error("min(x)").message, "min takes 2 argument(s)");
+        assert_eq!(error("sin x").message, "expected '('");
+        assert_eq!(error("cos(x").message, "expected ')'");
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        assert_eq!(error("").message, "unexpected end of expression");
+        assert_eq!(error("x +").message, "unexpected end of expression");
+        assert_eq!(error("x y").message, "unexpected input");
+        assert_eq!(error("x # y").message, "unexpected input");
+        assert_eq!(error("(x").message, "expected ')'");
+    }
+
+    #[test]
+    fn parse_pair_splits_at_the_top_level_comma() {
+        let (a, b) = parse_pair(" (max(x, 1), -y) ").unwrap();
+        assert_eq!(a.eval(3.0, 2.0, 0.0), 3.0);
+        assert_eq!(b.eval(3.0, 2.0, 0.0), -2.0);
+
+        let (a, b) = parse_pair("x, y").unwrap();
+        assert_eq!((a, b), (Expr::Var('x'), Expr::Var('y')));
+    }
+
+    #[test]
+    fn parse_pair_rejects_malformed_pairs() {
+        // No comma, or only ones inside a call.
+        assert!(parse_pair("(sin(y) cos(x))").is_err());
+        assert!(parse_pair("(max(x, y))").is_err());
+        // Unbalanced parentheses.
+        assert!(parse_pair("(sin(y), cos(x)").is_err());
+        assert!(parse_pair("sin(y), cos(x))").is_err());
+        assert!(parse_pair("(x, y, t)").is_err());
+
+        // Errors point into the component they came from.
+        let err = parse_pair("(x, y +)").unwrap_err();
+        assert_eq!(err.message, "unexpected end of expression");
+        assert_eq!(err.position, 7);
+    }
+}
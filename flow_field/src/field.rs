@@ -0,0 +1,349 @@
+//! Sources that a flow field can be sampled from.
+//!
+//! Every source maps a position in pixels and a time in seconds to a force. Noise
+//! sources return unit vectors, and placed sources like attractors fall off from
+//! roughly unit strength, so sources of different kinds can be summed.
+
+use crate::{
+    expr::{self, Expr, ParseError},
+    vector::Vector,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use noise::{NoiseFn, OpenSimplex, Perlin, Seedable, Worley};
+use serde::{Deserialize, Serialize};
+
+const TWO_PI: f64 = 2.0 * std::f64::consts::PI;
+
+pub trait FieldSource {
+    fn sample(&self, x: f64, y: f64, t: f64) -> Vector;
+
+    /// A short description, shown in the window title.
+    fn name(&self) -> String;
+}
+
+/// Angles taken from a noise function, as in the classic Perlin noise flow field.
+pub struct NoiseAngle<N: NoiseFn<[f64; 3]>> {
+    pub noise: N,
+    pub label: &'static str,
+    /// Noise coordinates per pixel.
+    pub scale: f64,
+    /// Noise coordinates per second along the time axis.
+    pub speed: f64,
+}
+impl<N: NoiseFn<[f64; 3]>> FieldSource for NoiseAngle<N> {
+    fn sample(&self, x: f64, y: f64, t: f64) -> Vector {
+        let angle = self.noise.get([x * self.scale, y * self.scale, t * self.speed]) * TWO_PI;
+        Vector::from_angle(angle)
+    }
+
+    fn name(&self) -> String {
+        self.label.to_string()
+    }
+}
+
+pub fn perlin(seed: u32) -> NoiseAngle<Perlin> {
+    NoiseAngle {
+        noise: Perlin::default().set_seed(seed),
+        label: "Perlin",
+        scale: 0.0005,
+        speed: 0.1,
+    }
+}
+
+pub fn simplex(seed: u32) -> NoiseAngle<OpenSimplex> {
+    NoiseAngle {
+        noise: OpenSimplex::default().set_seed(seed),
+        label: "Simplex",
+        scale: 0.001,
+        speed: 0.1,
+    }
+}
+
+/// Worley noise gives every cell a constant angle, breaking the field into patches.
+pub fn worley(seed: u32) -> NoiseAngle<Worley> {
+    NoiseAngle {
+        noise: Worley::default().set_seed(seed),
+        label: "Worley",
+        scale: 0.005,
+        speed: 0.1,
+    }
+}
+
+/// The curl of a noise potential. Curl noise is divergence free, so particles
+/// swirl around without bunching up in sinks.
+pub struct Curl<N: NoiseFn<[f64; 3]>> {
+    pub noise: N,
+    pub scale: f64,
+    pub speed: f64,
+}
+impl Curl<Perlin> {
+    pub fn new(seed: u32) -> Curl<Perlin> {
+        Curl {
+            noise: Perlin::default().set_seed(seed),
+            scale: 0.002,
+            speed: 0.1,
+        }
+    }
+}
+impl<N: NoiseFn<[f64; 3]>> FieldSource for Curl<N> {
+    fn sample(&self, x: f64, y: f64, t: f64) -> Vector {
+        let (nx, ny, nt) = (x * self.scale, y * self.scale, t * self.speed);
+        let h = 1e-4;
+        let dx = (self.noise.get([nx + h, ny, nt]) - self.noise.get([nx - h, ny, nt])) / (2.0 * h);
+        let dy = (self.noise.get([nx, ny + h, nt]) - self.noise.get([nx, ny - h, nt])) / (2.0 * h);
+        // Gradients of Perlin noise are mostly below 2, so this keeps forces near unit size.
+        Vector { x: dy, y: -dx } * 0.5
+    }
+
+    fn name(&self) -> String {
+        "Curl".to_string()
+    }
+}
+
+/// A field given by an expression for each component, such as `(sin(y), cos(x))`.
+///
+/// `x` and `y` are measured in units of `unit` pixels from the center of the
+/// window, with y pointing down, and `t` is in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analytic {
+    pub text: String,
+    pub x: Expr,
+    pub y: Expr,
+    pub unit: f64,
+}
+impl Analytic {
+    pub fn parse(text: &str) -> Result<Analytic, ParseError> {
+        let (x, y) = expr::parse_pair(text)?;
+        Ok(Analytic {
+            text: text.trim().to_string(),
+            x,
+            y,
+            unit: 100.0,
+        })
+    }
+}
+impl FieldSource for Analytic {
+    fn sample(&self, x: f64, y: f64, t: f64) -> Vector {
+        let x = (x - WINDOW_WIDTH as f64 / 2.0) / self.unit;
+        let y = (y - WINDOW_HEIGHT as f64 / 2.0) / self.unit;
+        Vector {
+            x: self.x.eval(x, y, t),
+            y: self.y.eval(x, y, t),
+        }
+    }
+
+    fn name(&self) -> String {
+        self.text.clone()
+    }
+}
+
+/// Pulls towards a point, or pushes away from it for negative strengths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attractor {
+    pub pos: Vector,
+    pub strength: f64,
+    /// Distance at which the pull has fallen to half strength.
+    pub radius: f64,
+}
+impl FieldSource for Attractor {
+    fn sample(&self, x: f64, y: f64, _t: f64) -> Vector {
+        let mut to = self.pos - Vector { x, y };
+        let d = to.mag();
+        if d < 1e-9 {
+            return Vector::default();
+        }
+        to.set_mag(self.strength * falloff(d, self.radius));
+        to
+    }
+
+    fn name(&self) -> String {
+        if self.strength >= 0.0 {
+            "Attractor".to_string()
+        } else {
+            "Repeller".to_string()
+        }
+    }
+}
+
+/// Swirls around a point, clockwise on screen for positive strengths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vortex {
+    pub pos: Vector,
+    pub strength: f64,
+    pub radius: f64,
+}
+impl FieldSource for Vortex {
+    fn sample(&self, x: f64, y: f64, _t: f64) -> Vector {
+        let to = self.pos - Vector { x, y };
+        let d = to.mag();
+        if d < 1e-9 {
+            return Vector::default();
+        }
+        let mut around = Vector { x: to.y, y: -to.x };
+        around.set_mag(self.strength * falloff(d, self.radius));
+        around
+    }
+
+    fn name(&self) -> String {
+        "Vortex".to_string()
+    }
+}
+
+fn falloff(d: f64, radius: f64) -> f64 {
+    1.0 / (1.0 + (d / radius) * (d / radius))
+}
+
+/// A weighted sum of other sources.
+pub struct FieldSum {
+    pub sources: Vec<(f64, Box<dyn FieldSource>)>,
+}
+impl Default for FieldSum {
+    fn default() -> Self {
+        FieldSum::new()
+    }
+}
+impl FieldSum {
+    pub fn new() -> FieldSum {
+        FieldSum { sources: vec![] }
+    }
+
+    pub fn add<S: FieldSource + 'static>(&mut self, weight: f64, source: S) {
+        self.sources.push((weight, Box::new(source)));
+    }
+
+    pub fn with<S: FieldSource + 'static>(mut self, weight: f64, source: S) -> FieldSum {
+        self.add(weight, source);
+        self
+    }
+}
+impl FieldSource for FieldSum {
+    fn sample(&self, x: f64, y: f64, t: f64) -> Vector {
+        let mut sum = Vector::default();
+        for (weight, source) in &self.sources {
+            sum += source.sample(x, y, t) * *weight;
+        }
+        sum
+    }
+
+    fn name(&self) -> String {
+        let names: Vec<String> = self.sources.iter().map(|(_, s)| s.name()).collect();
+        names.join(" + ")
+    }
+}
+
+/// A grid of forces sampled from a source, one for every `scl` by `scl` pixel cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowField {
+    pub cols: u32,
+    pub rows: u32,
+    pub scl: f64,
+    pub vectors: Vec<Vector>,
+}
+impl FlowField {
+    pub fn new(width: u32, height: u32, scl: f64) -> FlowField {
+        let cols = (width as f64 / scl).floor() as u32;
+        let rows = (height as f64 / scl).floor() as u32;
+        FlowField {
+            cols,
+            rows,
+            scl,
+            vectors: vec![Vector::default(); (cols * rows) as usize],
+        }
+    }
+
+    /// Resamples every cell from `source` at time `t`. Forces are capped at unit
+    /// strength and then scaled by `strength`.
+    pub fn update(&mut self, source: &dyn FieldSource, t: f64, strength: f64) {
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                let index: usize = (x + y * self.cols) as usize;
+                let mut v = source.sample(x as f64 * self.scl, y as f64 * self.scl, t);
+                v.limit_mag(1.0);
+                self.vectors[index] = v * strength;
+            }
+        }
+    }
+
+    /// The force in the cell containing `pos`. Positions outside the grid use the
+    /// nearest cell on its edge.
+    pub fn force_at(&self, pos: Vector) -> Vector {
+        self.vectors[cell_index(pos, self.scl, self.cols, self.rows)]
+    }
+
+    // The force at grid point (x, y), clamped to the grid.
+    fn at(&self, x: i64, y: i64) -> Vector {
+        let x = x.max(0).min(self.cols as i64 - 1);
+        let y = y.max(0).min(self.rows as i64 - 1);
+        self.vectors[(x + y * self.cols as i64) as usize]
+    }
+
+    /// The force at `pos`, interpolated between the grid points around it. Each
+    /// grid point holds the force sampled at its top left corner.
+    pub fn sample(&self, pos: Vector, sampling: Sampling) -> Vector {
+        let (gx, gy) = (pos.x / self.scl, pos.y / self.scl);
+        let (x, y) = (gx.floor() as i64, gy.floor() as i64);
+        let (tx, ty) = (gx - gx.floor(), gy - gy.floor());
+        match sampling {
+            Sampling::Nearest => self.force_at(pos),
+            Sampling::Bilinear => {
+                let top = self.at(x, y) * (1.0 - tx) + self.at(x + 1, y) * tx;
+                let bottom = self.at(x, y + 1) * (1.0 - tx) + self.at(x + 1, y + 1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+            Sampling::Bicubic => {
+                let (wx, wy) = (catmull_rom(tx), catmull_rom(ty));
+                let mut sum = Vector::default();
+                for (j, wy) in wy.iter().enumerate() {
+                    let mut row = Vector::default();
+                    for (i, wx) in wx.iter().enumerate() {
+                        row += self.at(x + i as i64 - 1, y + j as i64 - 1) * *wx;
+                    }
+                    sum += row * *wy;
+                }
+                sum
+            }
+        }
+    }
+}
+
+/// How a flow field is read between its grid points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Sampling {
+    /// The force of the cell a position is in, which makes motion blocky.
+    Nearest,
+    /// A blend of the four surrounding grid points.
+    #[default]
+    Bilinear,
+    /// A Catmull-Rom spline through the sixteen surrounding grid points, which is
+    /// also smooth across cell edges.
+    Bicubic,
+}
+impl Sampling {
+    pub fn next(&self) -> Sampling {
+        match self {
+            Sampling::Nearest => Sampling::Bilinear,
+            Sampling::Bilinear => Sampling::Bicubic,
+            Sampling::Bicubic => Sampling::Nearest,
+        }
+    }
+}
+
+// Catmull-Rom weights of the four grid points around a position `t` of the way
+// between the middle two.
+fn catmull_rom(t: f64) -> [f64; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        (-t3 + 2.0 * t2 - t) / 2.0,
+        (3.0 * t3 - 5.0 * t2 + 2.0) / 2.0,
+        (-3.0 * t3 + 4.0 * t2 + t) / 2.0,
+        (t3 - t2) / 2.0,
+    ]
+}
+
+/// Index of the cell containing `pos` in a `cols` by `rows` grid of `scl` pixel
+/// cells, clamped to the grid.
+pub fn cell_index(pos: Vector, scl: f64, cols: u32, rows: u32) -> usize {
+    let x = ((pos.x / scl).floor() as i64).max(0).min(cols as i64 - 1);
+    let y = ((pos.y / scl).floor() as i64).max(0).min(rows as i64 - 1);
+    (x + y * cols as i64) as usize
+}
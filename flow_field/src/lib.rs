@@ -7,6 +7,14 @@ use std::time::{Duration, Instant};
 pub const WINDOW_WIDTH: u32 = 1920;
 pub const WINDOW_HEIGHT: u32 = 1080;
 
+pub mod canvas;
+pub mod deposit;
+pub mod emitter;
+pub mod expr;
+pub mod field;
+pub mod particles;
+pub mod plot;
+pub mod trail;
 pub mod vector;
 
 use vector::Vector;
@@ -53,6 +61,8 @@ impl FPSCounter {
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Particle {
     pub pos: Vector,
+    /// Where the particle was before its last update, for drawing trails.
+    pub prev: Vector,
     pub vel: Vector,
     pub acc: Vector,
     pub max_speed : f64,
@@ -60,11 +70,13 @@ pub struct Particle {
 }
 impl Particle {
     pub fn new<T: 'static + Into<f64> + Copy>(x: T, y: T, max_speed : T) -> Particle {
+        let pos = Vector {
+            x: x.into(),
+            y: y.into(),
+        };
         let mut p = Particle {
-            pos: Vector {
-                x: x.into(),
-                y: y.into(),
-            },
+            pos,
+            prev: pos,
             vel: Vector::default(),
             acc: Vector::default(),
             max_speed: max_speed.into(),
@@ -75,14 +87,26 @@ impl Particle {
     }
 
     pub fn random() -> Particle {
-        let mut rng = rand::thread_rng();
+        Particle::random_with(&mut rand::thread_rng())
+    }
+
+    /// A random particle drawn from `rng`, so seeded runs are repeatable.
+    pub fn random_with<R: Rng>(rng: &mut R) -> Particle {
+        let pos = Vector {
+            x: rng.gen_range(0.0..WINDOW_WIDTH as f64),
+            y: rng.gen_range(0.0..WINDOW_HEIGHT as f64),
+        };
         let mut p = Particle {
-            pos: Vector {
-                x: rng.gen_range(0.0..WINDOW_WIDTH as f64),
-                y: rng.gen_range(0.0..WINDOW_HEIGHT as f64),
+            pos,
+            prev: pos,
+            vel: Vector {
+                x: rng.gen_range(-1.0..1.0),
+                y: rng.gen_range(-1.0..1.0),
+            },
+            acc: Vector {
+                x: rng.gen_range(-1.0..1.0),
+                y: rng.gen_range(-1.0..1.0),
             },
-            vel: Vector::random2D(),
-            acc: Vector::random2D(),
             max_speed: rng.gen_range(0.0..4.0 as f64),
             color: [0.0, 0.2, 0.0, 1.0],
         };
@@ -93,6 +117,7 @@ impl Particle {
     pub fn update(&mut self) {
         self.vel += self.acc;
         self.vel.limit_mag(self.max_speed);
+        self.prev = self.pos;
         self.pos += self.vel;
         self.acc *= 0.0;
 
@@ -102,14 +127,7 @@ impl Particle {
     }
 
     pub fn update_color(&mut self) {
-        let speed_percent = self.vel.mag() / self.max_speed;
-        if speed_percent <= 0.5 {
-            self.color[0] = 1.0 - map_range((0.0, 0.5), (0.0, 1.0), speed_percent) as f32;
-            self.color[2] = map_range((0.0, 0.5), (0.0, 1.0), speed_percent) as f32;
-        } else {
-            self.color[0] = map_range((0.5, 1.0), (0.0, 1.0), speed_percent) as f32;
-            self.color[2] = 1.0 - map_range((0.5, 1.0), (0.0, 1.0), speed_percent) as f32;
-        }
+        speed_color(self.vel.mag() / self.max_speed, &mut self.color);
     }
 
     pub fn apply_force(&mut self, force: Vector) {
@@ -117,21 +135,8 @@ impl Particle {
     }
 
     pub fn follow(&mut self, vectors : &Vec<Vector>, scl : &f64, cols : &u32, rows : &u32) {
-        loop {
-            let x = (self.pos.x / scl).floor() as usize;
-            let y = (self.pos.y / scl).floor() as usize;
-            let index = x + y * *cols as usize;
-    
-            if index >= (rows * cols) as usize {
-                //println!("Would have panicked! Index is {}. This particle's position is {:?}, and its (x, y) is {:?}. Scl is {}. This particle is {}", index, self.pos, (x, y), scl, if self.on_screen() { "on screen" } else { "off screen" });
-                self.edges();
-            } else {
-                let force = vectors[index];
-                self.apply_force(force);
-                break;
-            }
-        }
-
+        let force = vectors[field::cell_index(self.pos, *scl, *cols, *rows)];
+        self.apply_force(force);
     }
 
     pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs, particle_size : f64) {
@@ -150,26 +155,54 @@ impl Particle {
     }
 
     pub fn edges(&mut self) {
-        if !self.on_screen() {
-            if self.pos.x >= WINDOW_WIDTH as f64 {
-                self.pos.x = 0.0;
-            } else if self.pos.x <= 0.0 {
-                self.pos.x = WINDOW_WIDTH as f64 - 1.0;
-            }
-            if self.pos.y >= WINDOW_HEIGHT as f64 {
-                self.pos.y = 0.0;
-            } else if self.pos.y <= 0.0 {
-                self.pos.y = WINDOW_HEIGHT as f64 - 1.0;
-            }
+        if wrap(&mut self.pos) {
+            // Don't draw a trail across the whole screen.
+            self.prev = self.pos;
         }
     }
 
     pub fn on_screen(&self) -> bool {
-        self.pos.x > 0.0
-            && self.pos.x < WINDOW_WIDTH as f64
-            && self.pos.y > 0.0
-            && self.pos.y < WINDOW_HEIGHT as f64
+        on_screen(self.pos)
+    }
+}
+
+/// Sets the red and blue of `color` from how fast a particle is going: red when
+/// slow, blue at half speed and red again at full speed.
+pub fn speed_color(speed_percent: f64, color: &mut [f32; 4]) {
+    if speed_percent <= 0.5 {
+        color[0] = 1.0 - map_range((0.0, 0.5), (0.0, 1.0), speed_percent) as f32;
+        color[2] = map_range((0.0, 0.5), (0.0, 1.0), speed_percent) as f32;
+    } else {
+        color[0] = map_range((0.5, 1.0), (0.0, 1.0), speed_percent) as f32;
+        color[2] = 1.0 - map_range((0.5, 1.0), (0.0, 1.0), speed_percent) as f32;
+    }
+}
+
+/// Moves a position that has left the window to the opposite edge, keeping how far
+/// past the edge it went. Returns whether it was moved.
+pub fn wrap(pos: &mut Vector) -> bool {
+    if on_screen(*pos) {
+        return false;
     }
+    pos.x = wrap_coordinate(pos.x, WINDOW_WIDTH as f64);
+    pos.y = wrap_coordinate(pos.y, WINDOW_HEIGHT as f64);
+    true
+}
+
+fn wrap_coordinate(v: f64, size: f64) -> f64 {
+    let v = v.rem_euclid(size);
+    // Tiny negative values round up to `size` itself.
+    if v >= size {
+        0.0
+    } else {
+        v
+    }
+}
+
+/// Whether a position is inside the window. The left and top edges are inside and
+/// the right and bottom edges are not, so every pixel belongs to exactly one side.
+pub fn on_screen(pos: Vector) -> bool {
+    pos.x >= 0.0 && pos.x < WINDOW_WIDTH as f64 && pos.y >= 0.0 && pos.y < WINDOW_HEIGHT as f64
 }
 
 pub fn from_rgba<T: 'static + Into<f64> + Copy>(pack: [T; 4]) -> [f32; 4] {
@@ -4,10 +4,18 @@ extern crate opengl_graphics;
 extern crate piston;
 
 use ::image::Rgba;
-use flow_field::{vector::Vector, FPSCounter, Particle, WINDOW_HEIGHT, WINDOW_WIDTH};
+use flow_field::{
+    field::{self, Analytic, Attractor, Curl, FieldSource, FieldSum, FlowField, Vortex},
+    deposit::Deposit,
+    emitter::Emitter,
+    particles::{Boundary, Particles},
+    plot::{self, PlotConfig, Recorder},
+    trail::{self, ColorMode, RenderConfig, Trails, PALETTES},
+    vector::Vector,
+    FPSCounter, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
 use gl::types::GLuint;
 use glutin_window::GlutinWindow as Window;
-use noise::{NoiseFn, Perlin, Seedable};
 use opengl_graphics::{GlGraphics, OpenGL, Texture, TextureSettings};
 use piston::event_loop::{EventSettings, Events};
 use piston::input::RenderEvent;
@@ -38,11 +46,153 @@ const PI_OVER_6: f64 = PI / 6.0;
 const PI_OVER_8: f64 = PI / 8.0;
 const PI_OVER_16: f64 = PI / 16.0;
 
+const DEFAULT_EXPRESSION: &str = "(sin(y), cos(x))";
+const MASK_PATH: &str = "mask.png";
+
+// What a left click places in the field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tool {
+    Attractor,
+    Repeller,
+    Vortex,
+}
+
+// The noise or analytic source the field is built on, chosen with the number keys.
+fn base_source(key: u32, seed: u32, expression: &Analytic) -> Box<dyn FieldSource> {
+    match key {
+        2 => Box::new(field::simplex(seed)),
+        3 => Box::new(field::worley(seed)),
+        4 => Box::new(Curl::new(seed)),
+        5 => Box::new(expression.clone()),
+        _ => Box::new(field::perlin(seed)),
+    }
+}
+
+// The emitter shape chosen with O, placed at `pos`. Masks are loaded from MASK_PATH
+// and always cover the whole window.
+fn emitter_shape(shape: u32, pos: Vector) -> Emitter {
+    let half_line = Vector { x: 200.0, y: 0.0 };
+    match shape {
+        1 => Emitter::Line(pos - half_line, pos + half_line),
+        2 => Emitter::Circle {
+            center: pos,
+            radius: 150.0,
+        },
+        3 => match Emitter::mask(MASK_PATH) {
+            Ok(mask) => mask,
+            Err(err) => {
+                println!("Could not load {}: {}", MASK_PATH, err);
+                Emitter::Point(pos)
+            }
+        },
+        _ => Emitter::Point(pos),
+    }
+}
+
+fn parse_expression(text: Option<&String>) -> Analytic {
+    let text = text.map_or(DEFAULT_EXPRESSION, |t| t.as_str());
+    match Analytic::parse(text) {
+        Ok(analytic) => analytic,
+        Err(err) => {
+            println!("Could not parse {}: {}", text, err);
+            Analytic::parse(DEFAULT_EXPRESSION).unwrap()
+        }
+    }
+}
+
+// `flow_field render <out.png> [frames] [scale] [seed] [source 1-5] [expression]`
+fn render_headless(args: &[String]) {
+    let usage = "Usage: flow_field render <out.png> [frames] [scale] [seed] [source 1-5] [expression]";
+    let out = match args.get(2) {
+        Some(out) => out,
+        None => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let mut config = RenderConfig::default();
+    let numbers = (
+        args.get(3).map_or(Ok(config.frames), |f| f.parse()),
+        args.get(4).map_or(Ok(config.scale), |s| s.parse()),
+        args.get(5).map_or(Ok(config.seed), |s| s.parse()),
+        args.get(6).map_or(Ok(1), |k| k.parse()),
+    );
+    let key = match numbers {
+        (Ok(frames), Ok(scale), Ok(seed), Ok(key)) => {
+            config.frames = frames;
+            config.scale = scale;
+            config.seed = seed;
+            key
+        }
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let expression = parse_expression(args.get(7));
+    let source = base_source(key, config.seed as u32, &expression);
+
+    println!(
+        "Rendering {} frames of {} at {}x to {}",
+        config.frames,
+        source.name(),
+        config.scale,
+        out
+    );
+    let canvas = trail::render(&config, source.as_ref());
+    match canvas.save(out) {
+        Ok(()) => println!("Saved {}x{} image to {}", canvas.width, canvas.height, out),
+        Err(err) => println!("Could not save {}: {}", out, err),
+    }
+}
+
+// `flow_field svg <out.svg> [config.json]`, see `PlotConfig` for the config's fields.
+fn plot_headless(args: &[String]) {
+    let out = match args.get(2) {
+        Some(out) => out,
+        None => {
+            println!("Usage: flow_field svg <out.svg> [config.json]");
+            return;
+        }
+    };
+    let config = match args.get(3) {
+        Some(path) => match PlotConfig::load(path) {
+            Ok(config) => config,
+            Err(err) => {
+                println!("Could not load {}: {}", path, err);
+                return;
+            }
+        },
+        None => PlotConfig::default(),
+    };
+    let expression = parse_expression(Some(&config.expression));
+    let source = base_source(config.source, config.seed as u32, &expression);
+
+    println!(
+        "Recording {} particles for {} frames of {}",
+        config.particles,
+        config.frames,
+        source.name()
+    );
+    let (recorder, field) = plot::record(&config, source.as_ref());
+    match recorder.save_svg(out, &field, &config) {
+        Ok(()) => println!("Saved {} paths to {}", recorder.polylines().len(), out),
+        Err(err) => println!("Could not save {}: {}", out, err),
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        Some("render") => return render_headless(&args),
+        Some("svg") => return plot_headless(&args),
+        _ => (),
+    }
+
     let opengl = OpenGL::V3_2;
 
     let mut window: Window =
-        WindowSettings::new("Perlin Noise Flow Field", [WINDOW_WIDTH, WINDOW_HEIGHT])
+        WindowSettings::new("Flow Field", [WINDOW_WIDTH, WINDOW_HEIGHT])
             .graphics_api(opengl)
             .exit_on_esc(true)
             .build()
@@ -53,27 +203,59 @@ fn main() {
     let mut fps_counter: FPSCounter = FPSCounter::new();
 
     let mut rng = rand::thread_rng();
-    let mut perlin = Perlin::default().set_seed(rng.gen_range(0..u32::MAX));
+    let mut seed = rng.gen_range(0..u32::MAX);
+
+    // An expression for the analytic field can be passed on the command line.
+    let expression = parse_expression(args.get(1));
+
+    // The field is the base source plus whatever has been placed with the mouse.
+    // Keys 1-5 pick the base: Perlin, Simplex, Worley, curl noise or the expression.
+    let mut base_key = 1;
+    let mut field = FieldSum::new();
+    field.sources.push((1.0, base_source(base_key, seed, &expression)));
+    let mut markers: Vec<(Vector, [f32; 4])> = vec![];
+    let mut tool = Tool::Attractor;
+    let mut mouse = Vector::default();
 
-    const inc: f64 = 0.01;
     const scl: f64 = 20.0;
 
-    let cols = (WINDOW_WIDTH as f64 / scl).floor() as u32;
-    let rows = (WINDOW_HEIGHT as f64 / scl).floor() as u32;
+    let mut flow_field = FlowField::new(WINDOW_WIDTH, WINDOW_HEIGHT, scl);
+    let cols = flow_field.cols;
+    let rows = flow_field.rows;
 
     let flow_field_size = rows * cols;
-    const max_particles: u32 = 1500;
     const particle_size: f64 = 4.0;
 
     let mut now = Instant::now();
 
-    let mut flow_field: Vec<Vector> = vec![Vector::default(); flow_field_size as usize];
+    // Equals/Minus double or halve the number of particles. G cycles how the field
+    // is sampled between grid points and B what happens at the window's edges. E
+    // places an emitter at the mouse and sends every particle out from it, and O
+    // cycles its shape: point, line, circle or the bright parts of mask.png. Once
+    // there's an emitter, new particles start from it too.
+    let mut max_particles: usize = 1500;
+    let mut particles = Particles::random_with(max_particles, &mut rng);
+    let mut emitter_kind = 0;
+    let mut emitting = false;
 
-    let mut particles: Vec<Particle> = vec![];
+    // D lets particles lay their direction down into a second field that is added
+    // to the first, so they follow each other's trails like a slime mold.
+    let mut coupled = false;
+    let mut deposit = Deposit::new(WINDOW_WIDTH, WINDOW_HEIGHT, scl);
 
-    for _ in 0..max_particles {
-        particles.push(Particle::random());
-    }
+    // L switches to drawing trails into a slowly fading canvas. Up/Down change how
+    // quickly it fades, K the coloring, P the palette and X exports it to a PNG.
+    let mut show_trails = false;
+    let mut trails = Trails::new(1.0);
+    let mut canvas = trails.canvas.to_image();
+    let mut texture: Texture = Texture::from_image(&canvas, &TextureSettings::new());
+    let mut exports = 0;
+
+    // R starts and stops recording particle paths, V exports them as SVG for a pen
+    // plotter and A toggles the layer of field arrows in the export.
+    let mut recording = false;
+    let mut recorder = Recorder::new();
+    let mut plot_config = PlotConfig::default();
 
     let mut events = Events::new(EventSettings::new());
     events.set_max_fps(200);
@@ -81,13 +263,25 @@ fn main() {
         use graphics::*;
 
         if let Some(args) = e.render_args() {
+            if show_trails {
+                canvas = trails.canvas.to_image();
+                texture.update(&canvas);
+            }
             gl.draw(args.viewport(), |c, gl| {
                 clear(BLACK, gl);
 
+                if show_trails {
+                    image(&texture, c.transform, gl);
+                    for (pos, color) in &markers {
+                        ellipse(*color, [pos.x - 6.0, pos.y - 6.0, 12.0, 12.0], c.transform, gl);
+                    }
+                    return;
+                }
+
                 for y in 0..rows {
                     for x in 0..cols {
                         let index: usize = (x + y * cols) as usize;
-                        let v = flow_field[index];
+                        let v = flow_field.vectors[index];
 
                         let transform = c
                             .transform
@@ -100,38 +294,193 @@ fn main() {
                             transform,
                             gl,
                         );
+
+                        if coupled {
+                            let d = deposit.field.vectors[index];
+                            let strength = d.mag().min(1.0);
+                            if strength > 0.01 {
+                                let transform = c
+                                    .transform
+                                    .trans(x as f64 * scl, y as f64 * scl)
+                                    .rot_deg(d.heading());
+                                line(MAGENTA, scl * 0.05, [0., 0., scl * strength, 0.], transform, gl);
+                            }
+                        }
                     }
                 }
 
-                for particle in particles.clone() {
-                    if particle.on_screen() {
-                        ellipse(
-                            particle.color,
-                            [particle.pos.x, particle.pos.y, particle_size, particle_size],
-                            c.transform,
-                            gl,
-                        );
-                    }
+                for (pos, color) in &markers {
+                    ellipse(*color, [pos.x - 6.0, pos.y - 6.0, 12.0, 12.0], c.transform, gl);
                 }
+                if emitting || particles.boundary == Boundary::Emitter {
+                    particles.emitter.draw(YELLOW, c.transform, gl);
+                }
+
+                // Small squares look the same as circles at this size and are much
+                // cheaper to batch, which matters with hundreds of thousands of them.
+                particles.draw(particle_size, c.transform, gl);
             });
-            window.set_title(format!(
-                "Perlin Noise Flow Field | {:03} fps | Lines Drawn {:03} | Particles Drawn {:03}",
-                fps_counter.tick(),
-                flow_field_size,
-                max_particles
-            ));
+            if show_trails {
+                window.set_title(format!(
+                    "Flow Field Trails | {:03} fps | Particles {:03} | {} | Fade {:.4} | {} | L: dots, Up/Down: fade, K: colors, P: palette, X: export",
+                    fps_counter.tick(),
+                    max_particles,
+                    field.sources[0].1.name(),
+                    trails.fade,
+                    trails.color_mode.name()
+                ));
+            } else {
+                window.set_title(format!(
+                    "Flow Field | {:03} fps | Lines Drawn {:03} | Particles Drawn {:03} | {} + {} Placed | {:?} | {} ({}){} | Click: {:?}, T: tool, C: clear, 1-5: source, L: trails, =/-: particles, G: sampling, B: edges, E/O: emitter, D: coupling, R: record{}",
+                    fps_counter.tick(),
+                    flow_field_size,
+                    max_particles,
+                    field.sources[0].1.name(),
+                    markers.len(),
+                    particles.sampling,
+                    particles.boundary.name(),
+                    particles.emitter.name(),
+                    if coupled { " | Coupled" } else { "" },
+                    tool,
+                    if recording { " (recording)" } else { "" }
+                ));
+            }
+        } else if let Some([x, y]) = e.mouse_cursor_args() {
+            mouse = Vector { x, y };
         } else if let piston::Event::Input(i, _) = e {
             // Input stuff here
             match i {
                 piston::Input::Button(b) => match (b.state, b.button) {
+                    (piston::ButtonState::Release, piston::Button::Mouse(piston::MouseButton::Left)) => {
+                        let color = match tool {
+                            Tool::Attractor => {
+                                field.add(1.5, Attractor { pos: mouse, strength: 1.0, radius: 150.0 });
+                                GREEN
+                            }
+                            Tool::Repeller => {
+                                field.add(1.5, Attractor { pos: mouse, strength: -1.0, radius: 150.0 });
+                                RED
+                            }
+                            Tool::Vortex => {
+                                field.add(1.5, Vortex { pos: mouse, strength: 1.0, radius: 200.0 });
+                                CYAN
+                            }
+                        };
+                        markers.push((mouse, color));
+                    }
                     (piston::ButtonState::Release, piston::Button::Keyboard(k)) => match k {
+                        piston::Key::D1 | piston::Key::D2 | piston::Key::D3 | piston::Key::D4 | piston::Key::D5 => {
+                            base_key = match k {
+                                piston::Key::D2 => 2,
+                                piston::Key::D3 => 3,
+                                piston::Key::D4 => 4,
+                                piston::Key::D5 => 5,
+                                _ => 1,
+                            };
+                            field.sources[0] = (1.0, base_source(base_key, seed, &expression));
+                        }
+                        piston::Key::T => {
+                            tool = match tool {
+                                Tool::Attractor => Tool::Repeller,
+                                Tool::Repeller => Tool::Vortex,
+                                Tool::Vortex => Tool::Attractor,
+                            };
+                        }
+                        piston::Key::C => {
+                            field.sources.truncate(1);
+                            markers = vec![];
+                        }
+                        piston::Key::L => {
+                            show_trails = !show_trails;
+                            trails.canvas.clear();
+                        }
+                        piston::Key::Up => {
+                            trails.fade = (trails.fade * 1.5).clamp(0.0005, 1.0);
+                        }
+                        piston::Key::Down => {
+                            trails.fade = if trails.fade <= 0.0005 { 0.0 } else { trails.fade / 1.5 };
+                        }
+                        piston::Key::K => {
+                            trails.color_mode = trails.color_mode.next();
+                        }
+                        piston::Key::P => {
+                            trails.color_mode = match trails.color_mode {
+                                ColorMode::Palette(i) => ColorMode::Palette((i + 1) % PALETTES.len()),
+                                _ => ColorMode::Palette(0),
+                            };
+                        }
+                        piston::Key::X => {
+                            let path = format!("flow_field_{}.png", exports);
+                            match trails.canvas.save(&path) {
+                                Ok(()) => println!("Saved {}", path),
+                                Err(err) => println!("Could not save {}: {}", path, err),
+                            }
+                            exports += 1;
+                        }
+                        piston::Key::Equals => {
+                            let added = particles.len();
+                            max_particles = (max_particles * 2).min(1 << 20);
+                            particles.resize_with(max_particles, &mut rng);
+                            if emitting {
+                                particles.emit(added, &mut rng);
+                            }
+                        }
+                        piston::Key::Minus => {
+                            max_particles = (max_particles / 2).max(1);
+                            particles.resize_with(max_particles, &mut rng);
+                        }
+                        piston::Key::G => {
+                            particles.sampling = particles.sampling.next();
+                        }
+                        piston::Key::B => {
+                            particles.boundary = particles.boundary.next();
+                        }
+                        piston::Key::E => {
+                            particles.emitter = emitter_shape(emitter_kind, mouse);
+                            particles.emit(0, &mut rng);
+                            emitting = true;
+                        }
+                        piston::Key::O => {
+                            emitter_kind = (emitter_kind + 1) % 4;
+                            particles.emitter = emitter_shape(emitter_kind, mouse);
+                            particles.emit(0, &mut rng);
+                            emitting = true;
+                        }
+                        piston::Key::D => {
+                            coupled = !coupled;
+                            deposit.clear();
+                        }
+                        piston::Key::R => {
+                            recording = !recording;
+                            if recording {
+                                recorder = Recorder::new();
+                            }
+                        }
+                        piston::Key::A => {
+                            plot_config.arrows = !plot_config.arrows;
+                        }
+                        piston::Key::V => {
+                            let path = format!("flow_field_{}.svg", exports);
+                            match recorder.save_svg(&path, &flow_field, &plot_config) {
+                                Ok(()) => println!("Saved {}", path),
+                                Err(err) => println!("Could not save {}: {}", path, err),
+                            }
+                            exports += 1;
+                        }
                         piston::Key::Space => {
-                            perlin = Perlin::default().set_seed(rng.gen_range(0..u32::MAX));
+                            seed = rng.gen_range(0..u32::MAX);
+                            field.sources[0] = (1.0, base_source(base_key, seed, &expression));
                             now = Instant::now();
-                            particles = vec![];
-                            for _ in 0..max_particles {
-                                particles.push(Particle::random());
+                            trails.canvas.clear();
+                            deposit.clear();
+                            let mut fresh = Particles::random_with(max_particles, &mut rng);
+                            fresh.sampling = particles.sampling;
+                            fresh.boundary = particles.boundary;
+                            fresh.emitter = particles.emitter.clone();
+                            if emitting || fresh.boundary == Boundary::Emitter {
+                                fresh.emit(0, &mut rng);
                             }
+                            particles = fresh;
                         }
                         _ => (),
                     },
@@ -140,27 +489,18 @@ fn main() {
                 _ => (),
             }
         } else {
-            let mut yoff: f64 = 0.0;
-            for y in 0..rows {
-                let mut xoff: f64 = 0.0;
-                for x in 0..cols {
-                    let index: usize = (x + y * cols) as usize;
-                    let angle =
-                        perlin.get([xoff, yoff, now.elapsed().as_secs_f64() / 10.0]) * TWO_PI;
-                    //let angle = perlin.get([xoff, yoff]) * TWO_PI;
-                    let mut v = Vector::from_angle(angle);
-                    v.set_mag(0.1);
-                    flow_field[index] = v;
-
-                    xoff += inc;
-                }
-                yoff += inc;
+            flow_field.update(&field, now.elapsed().as_secs_f64(), 0.1);
+            if coupled {
+                deposit.couple(&particles, &mut flow_field);
             }
 
-            for particle in &mut particles {
-                particle.update();
-                particle.edges();
-                particle.follow(&flow_field, &scl, &cols, &rows);
+            particles.step(&flow_field);
+
+            if show_trails {
+                trails.draw(&particles);
+            }
+            if recording {
+                recorder.record(&particles);
             }
         }
     }
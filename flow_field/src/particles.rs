@@ -0,0 +1,310 @@
+//! A particle store laid out as one array per field, so large numbers of particles
+//! can be stepped in parallel and drawn in a single batch.
+
+use crate::{
+    emitter::Emitter,
+    field::{FlowField, Sampling},
+    on_screen, speed_color,
+    vector::Vector,
+    wrap, Particle, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use rand::Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Two triangles per particle, and as many particles per batch as fit in the
+// back end's vertex buffer.
+const VERTICES_PER_PARTICLE: usize = 6;
+const BATCH_VERTICES: usize =
+    graphics::BACK_END_MAX_VERTEX_COUNT / VERTICES_PER_PARTICLE * VERTICES_PER_PARTICLE;
+
+/// What happens to particles that leave the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Boundary {
+    /// Come back in at the opposite edge.
+    #[default]
+    Wrap,
+    /// Reflect off the edge.
+    Bounce,
+    /// Start again, at rest, somewhere random in the window.
+    Respawn,
+    /// Start again, at rest, at the particle system's emitter.
+    Emitter,
+}
+impl Boundary {
+    pub fn next(&self) -> Boundary {
+        match self {
+            Boundary::Wrap => Boundary::Bounce,
+            Boundary::Bounce => Boundary::Respawn,
+            Boundary::Respawn => Boundary::Emitter,
+            Boundary::Emitter => Boundary::Wrap,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Wrap => "Wrap",
+            Boundary::Bounce => "Bounce",
+            Boundary::Respawn => "Respawn",
+            Boundary::Emitter => "Emitter",
+        }
+    }
+}
+
+/// The same state as a `Vec<Particle>`, with particle `i` spread across index `i`
+/// of every array, plus settings shared by the whole system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Particles {
+    pub pos: Vec<Vector>,
+    pub prev: Vec<Vector>,
+    pub vel: Vec<Vector>,
+    pub acc: Vec<Vector>,
+    pub max_speed: Vec<f64>,
+    pub color: Vec<[f32; 4]>,
+    pub sampling: Sampling,
+    pub boundary: Boundary,
+    /// Where particles are spawned once one is picked, and where
+    /// `Boundary::Emitter` respawns them.
+    pub emitter: Emitter,
+    /// Seeds where `Boundary::Respawn` puts particles, so runs are repeatable.
+    pub seed: u64,
+    steps: u64,
+}
+impl Default for Particles {
+    fn default() -> Particles {
+        Particles::new()
+    }
+}
+impl Particles {
+    pub fn new() -> Particles {
+        Particles {
+            pos: vec![],
+            prev: vec![],
+            vel: vec![],
+            acc: vec![],
+            max_speed: vec![],
+            color: vec![],
+            sampling: Sampling::default(),
+            boundary: Boundary::default(),
+            emitter: Emitter::Point(Vector {
+                x: WINDOW_WIDTH as f64 / 2.0,
+                y: WINDOW_HEIGHT as f64 / 2.0,
+            }),
+            seed: 0,
+            steps: 0,
+        }
+    }
+
+    pub fn random_with<R: Rng>(count: usize, rng: &mut R) -> Particles {
+        let mut particles = Particles::new();
+        particles.seed = rng.gen();
+        for _ in 0..count {
+            particles.push(Particle::random_with(rng));
+        }
+        particles
+    }
+
+    pub fn len(&self) -> usize {
+        self.pos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos.is_empty()
+    }
+
+    pub fn push(&mut self, particle: Particle) {
+        self.pos.push(particle.pos);
+        self.prev.push(particle.prev);
+        self.vel.push(particle.vel);
+        self.acc.push(particle.acc);
+        self.max_speed.push(particle.max_speed);
+        self.color.push(particle.color);
+    }
+
+    /// Grows or shrinks the store to `count` particles, adding random ones.
+    pub fn resize_with<R: Rng>(&mut self, count: usize, rng: &mut R) {
+        while self.len() < count {
+            self.push(Particle::random_with(rng));
+        }
+        self.pos.truncate(count);
+        self.prev.truncate(count);
+        self.vel.truncate(count);
+        self.acc.truncate(count);
+        self.max_speed.truncate(count);
+        self.color.truncate(count);
+    }
+
+    /// Moves every particle from index `from` on to a random spot on the emitter,
+    /// at rest.
+    pub fn emit<R: Rng>(&mut self, from: usize, rng: &mut R) {
+        for i in from..self.len() {
+            let mut pos = self.emitter.sample(rng.gen(), rng.gen());
+            wrap(&mut pos);
+            self.pos[i] = pos;
+            self.prev[i] = pos;
+            self.vel[i] = Vector::default();
+            self.acc[i] = Vector::default();
+        }
+    }
+
+    /// A copy of particle `i`.
+    pub fn get(&self, i: usize) -> Particle {
+        Particle {
+            pos: self.pos[i],
+            prev: self.prev[i],
+            vel: self.vel[i],
+            acc: self.acc[i],
+            max_speed: self.max_speed[i],
+            color: self.color[i],
+        }
+    }
+
+    /// Does what `Particle::update`, `edges` and `follow` do, for every particle
+    /// at once, spread over all cores, using this system's sampling and boundary.
+    pub fn step(&mut self, field: &FlowField) {
+        let (sampling, boundary, emitter) = (self.sampling, self.boundary, &self.emitter);
+        let seed = self.seed ^ self.steps.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        self.steps += 1;
+
+        self.pos
+            .par_iter_mut()
+            .zip(self.prev.par_iter_mut())
+            .zip(self.vel.par_iter_mut())
+            .zip(self.acc.par_iter_mut())
+            .zip(self.max_speed.par_iter())
+            .zip(self.color.par_iter_mut())
+            .enumerate()
+            .for_each(|(i, (((((pos, prev), vel), acc), max_speed), color))| {
+                *vel += *acc;
+                vel.limit_mag(*max_speed);
+                *prev = *pos;
+                *pos += *vel;
+                *acc = *vel * -0.07;
+                speed_color(vel.mag() / max_speed, color);
+
+                if !on_screen(*pos) {
+                    match boundary {
+                        Boundary::Wrap => {
+                            wrap(pos);
+                            // Don't draw a trail across the whole screen.
+                            *prev = *pos;
+                        }
+                        Boundary::Bounce => bounce(pos, vel),
+                        Boundary::Respawn | Boundary::Emitter => {
+                            let (u, v) = (
+                                unit_hash(seed, 2 * i as u64),
+                                unit_hash(seed, 2 * i as u64 + 1),
+                            );
+                            *pos = if boundary == Boundary::Respawn {
+                                Vector {
+                                    x: u * WINDOW_WIDTH as f64,
+                                    y: v * WINDOW_HEIGHT as f64,
+                                }
+                            } else {
+                                emitter.sample(u, v)
+                            };
+                            wrap(pos);
+                            *prev = *pos;
+                            *vel = Vector::default();
+                            *acc = Vector::default();
+                        }
+                    }
+                }
+
+                *acc += field.sample(*pos, sampling);
+            });
+    }
+
+    /// Draws every particle on screen as a `size` pixel square, with all of them
+    /// sent to the GPU in one triangle list.
+    pub fn draw(&self, size: f64, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::{
+            triangulation::{tx, ty},
+            DrawState, Graphics,
+        };
+
+        gl.tri_list_c(&DrawState::new_alpha(), |f| {
+            let mut vertices: Vec<[f32; 2]> = Vec::with_capacity(BATCH_VERTICES);
+            let mut colors: Vec<[f32; 4]> = Vec::with_capacity(BATCH_VERTICES);
+            for (pos, color) in self.pos.iter().zip(&self.color) {
+                if !on_screen(*pos) {
+                    continue;
+                }
+                let (x0, y0, x1, y1) = (pos.x, pos.y, pos.x + size, pos.y + size);
+                for &(x, y) in [(x0, y0), (x1, y0), (x1, y1), (x0, y0), (x1, y1), (x0, y1)].iter() {
+                    vertices.push([tx(transform, x, y), ty(transform, x, y)]);
+                    colors.push(*color);
+                }
+                if vertices.len() == BATCH_VERTICES {
+                    f(&vertices, &colors);
+                    vertices.clear();
+                    colors.clear();
+                }
+            }
+            if !vertices.is_empty() {
+                f(&vertices, &colors);
+            }
+        });
+    }
+}
+
+// Reflects a position that has left the window back in, and turns its velocity
+// around along that axis.
+fn bounce(pos: &mut Vector, vel: &mut Vector) {
+    let (width, height) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+    if pos.x < 0.0 {
+        pos.x = -pos.x;
+        vel.x = vel.x.abs();
+    } else if pos.x >= width {
+        pos.x = 2.0 * width - pos.x;
+        vel.x = -vel.x.abs();
+    }
+    if pos.y < 0.0 {
+        pos.y = -pos.y;
+        vel.y = vel.y.abs();
+    } else if pos.y >= height {
+        pos.y = 2.0 * height - pos.y;
+        vel.y = -vel.y.abs();
+    }
+    // A particle faster than the window is wide, or one sitting exactly on the
+    // far edge, is kept just inside.
+    pos.x = pos.x.max(0.0).min(width - 1e-9);
+    pos.y = pos.y.max(0.0).min(height - 1e-9);
+}
+
+// A number between 0 and 1 picked by hashing `seed` and `n` (SplitMix64), so each
+// particle gets its own random numbers without sharing a generator across threads.
+fn unit_hash(seed: u64, n: u64) -> f64 {
+    let mut z = seed.wrapping_add(n.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn emit_moves_particles_onto_the_emitter() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut particles = Particles::random_with(6, &mut rng);
+        let before = particles.clone();
+        let center = Vector { x: 200.0, y: 300.0 };
+        particles.emitter = Emitter::Point(center);
+
+        particles.emit(2, &mut rng);
+        for i in 0..2 {
+            assert_eq!(particles.get(i), before.get(i));
+        }
+        for i in 2..6 {
+            assert!((particles.pos[i] - center).mag() <= 5.0 + 1e-9, "{:?}", particles.pos[i]);
+            assert_eq!(particles.prev[i], particles.pos[i]);
+            assert_eq!(particles.vel[i], Vector::default());
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Recording particle paths and exporting them as layered SVG for pen plotters.
+
+use crate::{
+    field::{FieldSource, FlowField, Sampling},
+    particles::{Boundary, Particles},
+    trail::PALETTES,
+    vector::Vector,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Settings for a plot, stored as JSON. Missing fields take their defaults, so
+/// `{ "seed": 7, "layers": 3, "arrows": true }` is a complete config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlotConfig {
+    pub seed: u64,
+    pub frames: u32,
+    pub particles: usize,
+    /// The base field, numbered like the demo's keys: 1 Perlin, 2 Simplex,
+    /// 3 Worley, 4 curl noise and 5 `expression`.
+    pub source: u32,
+    pub expression: String,
+    pub sampling: Sampling,
+    pub boundary: Boundary,
+    /// Largest distance, in pixels, a simplified path may stray from the recorded one.
+    pub tolerance: f64,
+    /// Paths shorter than this many pixels are left out.
+    pub min_length: f64,
+    /// Number of pens. Particles are shared out between the layers in turn.
+    pub layers: usize,
+    /// Index into `PALETTES` used to color the layers on screen.
+    pub palette: usize,
+    /// Whether to add a layer of arrows showing the field.
+    pub arrows: bool,
+    /// Grid cells between arrows.
+    pub arrow_spacing: u32,
+    /// Width of the drawing on paper. The height follows from the window's shape.
+    pub page_width_mm: f64,
+}
+impl Default for PlotConfig {
+    fn default() -> PlotConfig {
+        PlotConfig {
+            seed: 0,
+            frames: 600,
+            particles: 1000,
+            source: 1,
+            expression: "(sin(y), cos(x))".to_string(),
+            sampling: Sampling::default(),
+            boundary: Boundary::default(),
+            tolerance: 0.5,
+            min_length: 20.0,
+            layers: 1,
+            palette: 0,
+            arrows: false,
+            arrow_spacing: 2,
+            page_width_mm: 297.0,
+        }
+    }
+}
+impl PlotConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PlotConfig> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// Collects the path of every particle, step by step. A particle that jumps,
+/// by wrapping around or respawning, starts a new path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recorder {
+    // The path each particle is drawing right now.
+    open: Vec<Vec<Vector>>,
+    /// Finished paths, each with the index of the particle that drew it.
+    pub paths: Vec<(usize, Vec<Vector>)>,
+}
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    /// Adds the latest step of every particle.
+    pub fn record(&mut self, particles: &Particles) {
+        while self.open.len() > particles.len() {
+            let i = self.open.len() - 1;
+            self.finish(i);
+            self.open.pop();
+        }
+        self.open.resize(particles.len(), vec![]);
+
+        for i in 0..particles.len() {
+            let (prev, pos) = (particles.prev[i], particles.pos[i]);
+            if self.open[i].last() != Some(&prev) {
+                self.finish(i);
+                self.open[i].push(prev);
+            }
+            if pos != prev {
+                self.open[i].push(pos);
+            }
+        }
+    }
+
+    fn finish(&mut self, i: usize) {
+        let path = std::mem::take(&mut self.open[i]);
+        if path.len() >= 2 {
+            self.paths.push((i, path));
+        }
+    }
+
+    /// Every path so far, finished or not.
+    pub fn polylines(&self) -> Vec<(usize, &[Vector])> {
+        let finished = self.paths.iter().map(|(i, path)| (*i, path.as_slice()));
+        let open = self
+            .open
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| path.len() >= 2)
+            .map(|(i, path)| (i, path.as_slice()));
+        finished.chain(open).collect()
+    }
+
+    /// The recorded paths as an SVG document, simplified, filtered and split into
+    /// layers as `config` says, with arrows from `field` if it asks for them.
+    pub fn to_svg(&self, field: &FlowField, config: &PlotConfig) -> String {
+        let layers = config.layers.max(1);
+        let mut groups = vec![String::new(); layers];
+        for (i, path) in self.polylines() {
+            if length(path) < config.min_length {
+                continue;
+            }
+            let points: Vec<String> = simplify(path, config.tolerance)
+                .iter()
+                .map(|p| format!("{:.2},{:.2}", p.x, p.y))
+                .collect();
+            writeln!(groups[i % layers], "    <polyline points=\"{}\"/>", points.join(" ")).unwrap();
+        }
+
+        let palette = &PALETTES[config.palette % PALETTES.len()];
+        let (width, height) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+        let mut svg = String::new();
+        writeln!(svg, "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>").unwrap();
+        writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:inkscape=\"http://www.inkscape.org/namespaces/inkscape\" width=\"{:.2}mm\" height=\"{:.2}mm\" viewBox=\"0 0 {} {}\">",
+            config.page_width_mm,
+            config.page_width_mm * height / width,
+            width,
+            height
+        )
+        .unwrap();
+        // Plotting software treats each Inkscape layer as one pen, and reads the
+        // number its label starts with as the layer number.
+        for (n, group) in groups.iter().enumerate() {
+            let color = palette.1[n % palette.1.len()];
+            let label = format!("{} Pen", n + 1);
+            write_layer(&mut svg, n + 1, &label, color, group);
+        }
+        if config.arrows {
+            let label = format!("{} Field", layers + 1);
+            write_layer(&mut svg, layers + 1, &label, [0.5, 0.5, 0.5], &arrows(field, config.arrow_spacing));
+        }
+        writeln!(svg, "</svg>").unwrap();
+        svg
+    }
+
+    pub fn save_svg<P: AsRef<Path>>(&self, path: P, field: &FlowField, config: &PlotConfig) -> io::Result<()> {
+        fs::write(path, self.to_svg(field, config))
+    }
+}
+
+fn write_layer(svg: &mut String, id: usize, label: &str, color: [f32; 3], body: &str) {
+    let to_byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    writeln!(
+        svg,
+        "  <g inkscape:groupmode=\"layer\" id=\"layer{}\" inkscape:label=\"{}\" style=\"fill:none;stroke:#{:02x}{:02x}{:02x};stroke-width:1;stroke-linecap:round;stroke-linejoin:round\">",
+        id,
+        label,
+        to_byte(color[0]),
+        to_byte(color[1]),
+        to_byte(color[2])
+    )
+    .unwrap();
+    svg.push_str(body);
+    writeln!(svg, "  </g>").unwrap();
+}
+
+// An arrow centered on every `spacing`th grid point, scaled so the strongest force
+// in the field nearly spans the gap between arrows.
+fn arrows(field: &FlowField, spacing: u32) -> String {
+    let spacing = spacing.max(1);
+    let step = spacing as f64 * field.scl;
+    let strongest = field.vectors.iter().map(|v| v.mag()).fold(0.0, f64::max);
+    let mut out = String::new();
+    if strongest == 0.0 {
+        return out;
+    }
+
+    for y in (0..field.rows).step_by(spacing as usize) {
+        for x in (0..field.cols).step_by(spacing as usize) {
+            let v = field.vectors[(x + y * field.cols) as usize];
+            let len = v.mag() / strongest * step * 0.8;
+            if len < 1.0 {
+                continue;
+            }
+            let center = Vector {
+                x: x as f64 * field.scl,
+                y: y as f64 * field.scl,
+            };
+            let angle = v.y.atan2(v.x);
+            let tail = center - Vector::from_angle(angle) * (len / 2.0);
+            let tip = center + Vector::from_angle(angle) * (len / 2.0);
+            let barb = len * 0.25;
+            let left = tip + Vector::from_angle(angle + 2.6) * barb;
+            let right = tip + Vector::from_angle(angle - 2.6) * barb;
+            writeln!(
+                out,
+                "    <path d=\"M {:.2},{:.2} L {:.2},{:.2} M {:.2},{:.2} L {:.2},{:.2} L {:.2},{:.2}\"/>",
+                tail.x, tail.y, tip.x, tip.y, left.x, left.y, tip.x, tip.y, right.x, right.y
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// Total length of a polyline.
+pub fn length(points: &[Vector]) -> f64 {
+    points.windows(2).map(|w| (w[1] - w[0]).mag()).sum()
+}
+
+/// Drops points that are within `tolerance` of the line through their
+/// neighbours (Ramer-Douglas-Peucker), keeping both ends.
+pub fn simplify(points: &[Vector], tolerance: f64) -> Vec<Vector> {
+    if points.len() <= 2 || tolerance <= 0.0 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let (a, b) = (points[first], points[last]);
+        let mut farthest = (0.0, first);
+        for (i, p) in points.iter().enumerate().take(last).skip(first + 1) {
+            let d = distance_to_segment(*p, a, b);
+            if d > farthest.0 {
+                farthest = (d, i);
+            }
+        }
+        if farthest.0 > tolerance {
+            keep[farthest.1] = true;
+            stack.push((first, farthest.1));
+            stack.push((farthest.1, last));
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(p, _)| *p)
+        .collect()
+}
+
+fn distance_to_segment(p: Vector, a: Vector, b: Vector) -> f64 {
+    let ab = b - a;
+    let len_sq = ab.x * ab.x + ab.y * ab.y;
+    let t = if len_sq == 0.0 {
+        0.0
+    } else {
+        (((p.x - a.x) * ab.x + (p.y - a.y) * ab.y) / len_sq).clamp(0.0, 1.0)
+    };
+    (p - (a + ab * t)).mag()
+}
+
+/// Runs the simulation `config` describes without opening a window, recording
+/// every particle's path. Returns the paths and the field as it was at the end.
+pub fn record(config: &PlotConfig, source: &dyn FieldSource) -> (Recorder, FlowField) {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut field = FlowField::new(WINDOW_WIDTH, WINDOW_HEIGHT, 20.0);
+    let mut particles = Particles::random_with(config.particles, &mut rng);
+    particles.sampling = config.sampling;
+    particles.boundary = config.boundary;
+
+    let mut recorder = Recorder::new();
+    for frame in 0..config.frames {
+        field.update(source, frame as f64 / 60.0, 0.1);
+        particles.step(&field);
+        recorder.record(&particles);
+    }
+    (recorder, field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Particle;
+
+    fn v(x: f64, y: f64) -> Vector {
+        Vector { x, y }
+    }
+
+    #[test]
+    fn simplify_collapses_a_straight_line() {
+        let line: Vec<Vector> = [0.0, 0.5, 3.0, 4.0, 9.0, 10.0].iter().map(|&t| v(t, 2.0 * t)).collect();
+        assert_eq!(simplify(&line, 0.5), vec![v(0.0, 0.0), v(10.0, 20.0)]);
+    }
+
+    #[test]
+    fn simplify_keeps_points_beyond_the_tolerance() {
+        let path = [v(0.0, 0.0), v(2.5, 1.1), v(5.0, 2.0), v(7.5, 0.9), v(10.0, 0.0)];
+        assert_eq!(simplify(&path, 0.5), vec![v(0.0, 0.0), v(5.0, 2.0), v(10.0, 0.0)]);
+        assert_eq!(simplify(&path, 2.5), vec![v(0.0, 0.0), v(10.0, 0.0)]);
+        assert_eq!(simplify(&path, 0.0), path.to_vec());
+    }
+
+    // Steps a particle heading right off the edge of the window and on after it
+    // has come back in, recording it all the way.
+    fn record_off_the_edge(boundary: Boundary) -> Recorder {
+        let field = FlowField::new(WINDOW_WIDTH, WINDOW_HEIGHT, 20.0);
+        let mut particles = Particles::new();
+        particles.boundary = boundary;
+        particles.push(Particle::new(WINDOW_WIDTH as f64 - 10.0, 300.0, 4.0));
+
+        let mut recorder = Recorder::new();
+        for _ in 0..10 {
+            // Respawning stops particles, so keep it going.
+            particles.vel[0] = v(3.0, 0.0);
+            particles.step(&field);
+            recorder.record(&particles);
+        }
+        recorder
+    }
+
+    #[test]
+    fn a_wrapped_particle_starts_a_new_path() {
+        let recorder = record_off_the_edge(Boundary::Wrap);
+        let paths = recorder.polylines();
+        assert_eq!(paths.len(), 2);
+        let (before, after) = (paths[0].1, paths[1].1);
+        assert!(before.last().unwrap().x > WINDOW_WIDTH as f64 - 5.0, "{:?}", before);
+        assert!(after[0].x < 5.0, "{:?}", after);
+        assert!(paths.iter().all(|(i, _)| *i == 0));
+    }
+
+    #[test]
+    fn a_respawned_particle_starts_a_new_path() {
+        let recorder = record_off_the_edge(Boundary::Respawn);
+        let paths = recorder.polylines();
+        assert_eq!(paths.len(), 2);
+        let (before, after) = (paths[0].1, paths[1].1);
+        // Neither path crosses the window to where it respawned.
+        assert!(length(before) < 30.0 && length(after) < 30.0);
+        assert_ne!(before.last(), after.first());
+    }
+}
@@ -0,0 +1,186 @@
+//! Particle trails accumulated over many frames, and rendering them without a window.
+
+use crate::{
+    canvas::Canvas,
+    field::{FieldSource, FlowField, Sampling},
+    particles::{Boundary, Particles},
+    Particle, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Named five-color palettes for `ColorMode::Palette`.
+pub const PALETTES: [(&str, [[f32; 3]; 5]); 4] = [
+    (
+        "Ember",
+        [
+            [0.98, 0.84, 0.47],
+            [0.96, 0.55, 0.24],
+            [0.85, 0.27, 0.19],
+            [0.55, 0.12, 0.20],
+            [0.25, 0.06, 0.16],
+        ],
+    ),
+    (
+        "Ocean",
+        [
+            [0.80, 0.95, 0.96],
+            [0.45, 0.80, 0.87],
+            [0.18, 0.56, 0.72],
+            [0.08, 0.33, 0.55],
+            [0.04, 0.15, 0.33],
+        ],
+    ),
+    (
+        "Forest",
+        [
+            [0.85, 0.90, 0.62],
+            [0.55, 0.75, 0.40],
+            [0.30, 0.55, 0.30],
+            [0.16, 0.38, 0.26],
+            [0.60, 0.45, 0.30],
+        ],
+    ),
+    (
+        "Neon",
+        [
+            [1.00, 0.20, 0.60],
+            [0.20, 0.90, 1.00],
+            [0.70, 1.00, 0.20],
+            [1.00, 0.85, 0.10],
+            [0.60, 0.30, 1.00],
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    /// Red when slow, blue at half speed and red again at full speed, like the dots.
+    Speed,
+    /// Hue from the direction of travel.
+    Heading,
+    /// Each particle keeps one color from a palette in `PALETTES`.
+    Palette(usize),
+}
+impl ColorMode {
+    pub fn next(&self) -> ColorMode {
+        match self {
+            ColorMode::Speed => ColorMode::Heading,
+            ColorMode::Heading => ColorMode::Palette(0),
+            ColorMode::Palette(_) => ColorMode::Speed,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ColorMode::Speed => "Speed".to_string(),
+            ColorMode::Heading => "Heading".to_string(),
+            ColorMode::Palette(i) => format!("{} Palette", PALETTES[*i % PALETTES.len()].0),
+        }
+    }
+
+    /// The color of the `index`th particle, at full opacity.
+    pub fn color(&self, particle: &Particle, index: usize) -> [f32; 3] {
+        match self {
+            ColorMode::Speed => [particle.color[0], particle.color[1], particle.color[2]],
+            ColorMode::Heading => {
+                let angle = particle.vel.y.atan2(particle.vel.x);
+                hue(angle / (2.0 * std::f64::consts::PI) + 0.5)
+            }
+            ColorMode::Palette(i) => {
+                let palette = &PALETTES[*i % PALETTES.len()].1;
+                palette[index % palette.len()]
+            }
+        }
+    }
+}
+
+// A fully saturated color for a hue between 0 and 1.
+fn hue(h: f64) -> [f32; 3] {
+    let h = (h.rem_euclid(1.0) * 6.0) as f32;
+    let channel = |offset: f32| (((h + offset) % 6.0 - 3.0).abs() - 1.0).clamp(0.0, 1.0);
+    [channel(0.0), channel(4.0), channel(2.0)]
+}
+
+/// Draws each particle's movement as a faint line into a canvas that fades slowly,
+/// so the paths particles take build up into the flow field's picture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trails {
+    pub canvas: Canvas,
+    /// Fraction of the way back to the background the canvas fades each frame.
+    pub fade: f32,
+    /// Opacity of each line segment.
+    pub alpha: f32,
+    pub color_mode: ColorMode,
+}
+impl Trails {
+    pub fn new(scale: f64) -> Trails {
+        Trails {
+            canvas: Canvas::new(WINDOW_WIDTH, WINDOW_HEIGHT, scale),
+            fade: 0.005,
+            alpha: 0.05,
+            color_mode: ColorMode::Heading,
+        }
+    }
+
+    pub fn draw(&mut self, particles: &Particles) {
+        self.canvas.fade(self.fade);
+        for i in 0..particles.len() {
+            let particle = particles.get(i);
+            let [r, g, b] = self.color_mode.color(&particle, i);
+            self.canvas.line(particle.prev, particle.pos, [r, g, b, self.alpha]);
+        }
+    }
+}
+
+/// Everything that decides a headless render, so the same settings always give
+/// the same image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderConfig {
+    pub seed: u64,
+    pub frames: u32,
+    /// Output pixels per window pixel.
+    pub scale: f64,
+    pub particles: usize,
+    pub fade: f32,
+    pub alpha: f32,
+    pub color_mode: ColorMode,
+    pub sampling: Sampling,
+    pub boundary: Boundary,
+}
+impl Default for RenderConfig {
+    fn default() -> RenderConfig {
+        RenderConfig {
+            seed: 0,
+            frames: 600,
+            scale: 2.0,
+            particles: 5000,
+            fade: 0.0,
+            alpha: 0.05,
+            color_mode: ColorMode::Heading,
+            sampling: Sampling::default(),
+            boundary: Boundary::default(),
+        }
+    }
+}
+
+/// Runs the simulation at 60 steps per simulated second without opening a window
+/// and returns the accumulated trails.
+pub fn render(config: &RenderConfig, source: &dyn FieldSource) -> Canvas {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut field = FlowField::new(WINDOW_WIDTH, WINDOW_HEIGHT, 20.0);
+    let mut particles = Particles::random_with(config.particles, &mut rng);
+    particles.sampling = config.sampling;
+    particles.boundary = config.boundary;
+
+    let mut trails = Trails::new(config.scale);
+    trails.fade = config.fade;
+    trails.alpha = config.alpha;
+    trails.color_mode = config.color_mode;
+
+    for frame in 0..config.frames {
+        field.update(source, frame as f64 / 60.0, 0.1);
+        particles.step(&field);
+        trails.draw(&particles);
+    }
+    trails.canvas
+}
@@ -14,6 +14,7 @@ pub struct Fluid {
     pub v_y: Vec<f64>,
     pub v_x0: Vec<f64>,
     pub v_y0: Vec<f64>,
+    pub obstacles: Vec<bool>,
 }
 impl Fluid {
     pub fn new(diff: i32, visc: f64, dt: f64) -> Fluid {
@@ -28,6 +29,7 @@ impl Fluid {
             v_y: vec![0.0; (N * N) as usize],
             v_x0: vec![0.0; (N * N) as usize],
             v_y0: vec![0.0; (N * N) as usize],
+            obstacles: vec![false; (N * N) as usize],
         }
     }
 
@@ -100,6 +102,47 @@ impl Fluid {
         self.s = self_s;
         self.v_x = self_v_x.unwrap();
         self.v_y = self_v_y.unwrap();
+
+        self.apply_obstacles();
+    }
+
+    // Clears velocity and dye from every cell marked as an obstacle.
+    pub fn apply_obstacles(&mut self) {
+        for i in 0..self.obstacles.len() {
+            if self.obstacles[i] {
+                self.v_x[i] = 0.0;
+                self.v_y[i] = 0.0;
+                self.density[i] = 0.0;
+            }
+        }
+    }
+
+    pub fn add_obstacle(&mut self, x: u32, y: u32) {
+        self.obstacles[IX(x, y)] = true;
+    }
+
+    // Bilinearly interpolates the velocity field at a position given in grid cells.
+    pub fn velocity_at(&self, x: f64, y: f64) -> Vector {
+        let max = (N - 1) as f64 - 0.001;
+        let x = x.max(0.0).min(max);
+        let y = y.max(0.0).min(max);
+
+        let i0 = x.floor() as u32;
+        let j0 = y.floor() as u32;
+        let s1 = x - i0 as f64;
+        let s0 = 1.0 - s1;
+        let t1 = y - j0 as f64;
+        let t0 = 1.0 - t1;
+
+        let sample = |field: &Vec<f64>| {
+            s0 * (t0 * field[IX(i0, j0)] + t1 * field[IX(i0, j0 + 1)])
+                + s1 * (t0 * field[IX(i0 + 1, j0)] + t1 * field[IX(i0 + 1, j0 + 1)])
+        };
+
+        Vector {
+            x: sample(&self.v_x),
+            y: sample(&self.v_y),
+        }
     }
 
     pub fn add_density(&mut self, x: u32, y: u32, amount: f64) {
@@ -150,9 +193,14 @@ impl Fluid {
             for i in 0..NX {
                 let x = i * scl;
                 let y = j * scl;
+                let color = if self.obstacles[IX(i as u32, j as u32)] {
+                    [0.5, 0.5, 0.5, 1.0]
+                } else {
+                    [1.0, 1.0, 1.0, self.density[IX(i as u32, j as u32)] as f32]
+                };
                 gl.draw(args.viewport(), |c, gl| {
                     rectangle(
-                        [1.0, 1.0, 1.0, self.density[IX(i as u32, j as u32)] as f32],
+                        color,
                         [x as f64, y as f64, scl as f64, scl as f64],
                         c.transform,
                         gl,
@@ -1,4 +1,7 @@
 pub mod fluid;
+pub mod script;
+pub mod snapshot;
+pub mod tracer;
 pub mod vector;
 
 use std::collections::VecDeque;
@@ -13,7 +13,12 @@ use piston::window::WindowSettings;
 use piston::{AdvancedWindow, EventLoop, MouseCursorEvent, RenderArgs};
 use rand::Rng;
 use Fluid_Simulation::{
-    fluid::Fluid, scl, vector::Vector, FPSCounter, WINDOW_HEIGHT, WINDOW_WIDTH,
+    fluid::Fluid,
+    scl,
+    script::{Command, InputScript},
+    tracer::TracerSystem,
+    vector::Vector,
+    FPSCounter, WINDOW_HEIGHT, WINDOW_WIDTH,
 };
 
 const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
@@ -30,7 +35,18 @@ const LIGHT_BLUE: [f32; 4] = [0.5, 0.5, 1.0, 1.0];
 const LIGHT_GREEN: [f32; 4] = [0.0, 1.0, 0.5, 1.0];
 const LIGHT_RED: [f32; 4] = [1.0, 0.0, 0.5, 1.0];
 
+const SNAPSHOT_PATH: &str = "fluid.snap";
+const SCRIPT_PATH: &str = "fluid_script.txt";
+const NPY_DIR: &str = "fluid_npy";
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        Some("replay") => return replay(&args[2..]),
+        Some("compare") => return compare(&args[2..]),
+        _ => (),
+    }
+
     let opengl = OpenGL::V3_2;
 
     let mut window: Window = WindowSettings::new("Fluid Simulation", [WINDOW_WIDTH, WINDOW_HEIGHT])
@@ -50,6 +66,17 @@ fn main() {
 
     let mut fluid = Fluid::new(0, 0.000001, 0.01);
 
+    // Every input applied to the fluid is tagged with the frame it lands in,
+    // so a recording can be replayed headlessly with identical results.
+    let mut frame: u64 = 0;
+    let mut script = InputScript::new();
+    let mut recording = false;
+    let mut obstacle_brush = false;
+
+    let mut tracers = TracerSystem::new();
+    let mut show_tracers = true;
+    let mut cursor = [0.0, 0.0];
+
     while let Some(e) = events.next(&mut window) {
         use graphics::*;
 
@@ -58,28 +85,198 @@ fn main() {
                 clear(BLACK, gl);
 
                 fluid.step();
+                tracers.update(&fluid);
                 fluid.renderD(gl, &args);
+                if show_tracers {
+                    tracers.show(gl, &args);
+                }
                 fluid.fadeD();
             });
-            window.set_title(format!("Fluid Simulation | {:03} fps", fps_counter.tick()));
+            frame += 1;
+            window.set_title(format!(
+                "Fluid Simulation | {:03} fps | Frame {:06} | Brush {} | Tracers {:04} ({:?}){}",
+                fps_counter.tick(),
+                frame,
+                if obstacle_brush { "Obstacle" } else { "Dye" },
+                tracers.tracers.len(),
+                tracers.integrator,
+                if recording { " | Recording" } else { "" }
+            ));
         } else if let Some([x, y]) = e.mouse_cursor_args() {
-            for i in 0..5 {
-                let mut v = Vector { x: x, y: y };
-                v *= 2.0;
-                let x = x / scl as f64 + rng.gen_range(-2..3) as f64;
-                let y = y / scl as f64 + rng.gen_range(-2..3) as f64;
-                fluid.add_velocity(x.floor() as u32, y.floor() as u32, v.x, v.y);
+            cursor = [x, y];
+            let mut commands = vec![];
+
+            if obstacle_brush {
+                for x in x.floor() as u32 - 2..x.floor() as u32 + 2 {
+                    for y in y.floor() as u32 - 2..y.floor() as u32 + 2 {
+                        commands.push(Command::Obstacle {
+                            x: x / scl as u32,
+                            y: y / scl as u32,
+                        });
+                    }
+                }
+            } else {
+                for i in 0..5 {
+                    let mut v = Vector { x: x, y: y };
+                    v *= 2.0;
+                    let x = x / scl as f64 + rng.gen_range(-2..3) as f64;
+                    let y = y / scl as f64 + rng.gen_range(-2..3) as f64;
+                    commands.push(Command::Velocity {
+                        x: x.floor() as u32,
+                        y: y.floor() as u32,
+                        amount_x: v.x,
+                        amount_y: v.y,
+                    });
+                }
+
+                for x in x.floor() as u32 - 2..x.floor() as u32 + 2 {
+                    for y in y.floor() as u32 - 2..y.floor() as u32 + 2 {
+                        commands.push(Command::Density {
+                            x: x / scl as u32,
+                            y: y / scl as u32,
+                            amount: rng.gen_range(10..25) as f64,
+                        });
+                    }
+                }
             }
 
-            for x in x.floor() as u32 - 2..x.floor() as u32 + 2 {
-                for y in y.floor() as u32 - 2..y.floor() as u32 + 2 {
-                    fluid.add_density(x / scl as u32, y / scl as u32, rng.gen_range(10..25) as f64);
+            for command in commands {
+                command.apply(&mut fluid);
+                if recording {
+                    script.record(frame, command);
+                }
+            }
+        } else if let piston::Event::Input(piston::Input::Button(b), _) = e {
+            match (b.state, b.button) {
+                (piston::ButtonState::Press, piston::Button::Mouse(piston::MouseButton::Left)) => {
+                    tracers.add_emitter(cursor[0] / scl as f64, cursor[1] / scl as f64);
                 }
+                (piston::ButtonState::Release, piston::Button::Keyboard(k)) => match k {
+                    piston::Key::F5 => match fluid.save_snapshot(SNAPSHOT_PATH) {
+                        Ok(()) => println!("Saved snapshot to {}", SNAPSHOT_PATH),
+                        Err(err) => println!("Could not save snapshot: {}", err),
+                    },
+                    piston::Key::F9 => match Fluid::load_snapshot(SNAPSHOT_PATH) {
+                        Ok(loaded) => {
+                            fluid = loaded;
+                            println!("Loaded snapshot from {}", SNAPSHOT_PATH);
+                        }
+                        Err(err) => println!("Could not load snapshot: {}", err),
+                    },
+                    piston::Key::E => match fluid.export_npy(NPY_DIR) {
+                        Ok(()) => println!("Exported .npy files to {}/", NPY_DIR),
+                        Err(err) => println!("Could not export .npy files: {}", err),
+                    },
+                    piston::Key::R => {
+                        if recording {
+                            match script.save(SCRIPT_PATH) {
+                                Ok(()) => println!(
+                                    "Saved {} commands to {}, replay with `replay {} {}`",
+                                    script.commands.len(),
+                                    SCRIPT_PATH,
+                                    SCRIPT_PATH,
+                                    frame
+                                ),
+                                Err(err) => println!("Could not save script: {}", err),
+                            }
+                        } else {
+                            // Recordings always start from an empty fluid so they can be
+                            // replayed from scratch.
+                            fluid = Fluid::new(0, 0.000001, 0.01);
+                            script = InputScript::new();
+                            frame = 0;
+                        }
+                        recording = !recording;
+                    }
+                    piston::Key::O => {
+                        obstacle_brush = !obstacle_brush;
+                    }
+                    piston::Key::T => {
+                        show_tracers = !show_tracers;
+                    }
+                    piston::Key::I => {
+                        tracers.toggle_integrator();
+                    }
+                    piston::Key::C => {
+                        tracers.clear();
+                    }
+                    _ => (),
+                },
+                _ => (),
             }
         }
     }
 }
 
+/// `replay <script> <frames> [snapshot] [npy dir]`
+///
+/// Runs a recorded input script without opening a window and writes the final state.
+fn replay(args: &[String]) {
+    let usage = "Usage: replay <script> <frames> [snapshot] [npy dir]";
+    let (path, frames) = match (args.first(), args.get(1).map(|f| f.parse::<u64>())) {
+        (Some(path), Some(Ok(frames))) => (path, frames),
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let script = match InputScript::load(path) {
+        Ok(script) => script,
+        Err(err) => {
+            println!("Could not load input script {}: {}", path, err);
+            return;
+        }
+    };
+    let snapshot = args.get(2).map(|s| s.as_str()).unwrap_or(SNAPSHOT_PATH);
+
+    let mut fluid = Fluid::new(0, 0.000001, 0.01);
+    script.replay(&mut fluid, frames);
+
+    match fluid.save_snapshot(snapshot) {
+        Ok(()) => println!("Replayed {} frames, saved snapshot to {}", frames, snapshot),
+        Err(err) => {
+            println!("Could not save snapshot to {}: {}", snapshot, err);
+            return;
+        }
+    }
+
+    if let Some(dir) = args.get(3) {
+        match fluid.export_npy(dir) {
+            Ok(()) => println!("Exported .npy files to {}/", dir),
+            Err(err) => println!("Could not export .npy files to {}: {}", dir, err),
+        }
+    }
+}
+
+/// `compare <a> <b> [tolerance]`
+///
+/// Compares two snapshots and exits with status 1 if they differ by more than the tolerance,
+/// or with status 2 if they can't be compared.
+fn compare(args: &[String]) {
+    let usage = "Usage: compare <a> <b> [tolerance]";
+    let tolerance: f64 = match args.get(2).map_or(Ok(1e-9), |t| t.parse()) {
+        Ok(tolerance) if args.len() >= 2 => tolerance,
+        _ => {
+            println!("{}", usage);
+            std::process::exit(2);
+        }
+    };
+    let load = |path: &String| match Fluid::load_snapshot(path) {
+        Ok(fluid) => fluid,
+        Err(err) => {
+            println!("Could not load snapshot {}: {}", path, err);
+            std::process::exit(2);
+        }
+    };
+    let (a, b) = (load(&args[0]), load(&args[1]));
+
+    let difference = a.max_difference(&b);
+    println!("Max difference: {:e} (tolerance {:e})", difference, tolerance);
+    if difference > tolerance {
+        std::process::exit(1);
+    }
+}
+
 fn from_rgba(pack: [f32; 4]) -> [f32; 4] {
     let [r, g, b, a] = pack;
     let [r_f, g_f, b_f] = [r / 255.0, g / 255.0, b / 255.0];
@@ -0,0 +1,145 @@
+//! Recording and headless replaying of the inputs applied to a `Fluid`.
+
+use crate::fluid::Fluid;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Density { x: u32, y: u32, amount: f64 },
+    Velocity { x: u32, y: u32, amount_x: f64, amount_y: f64 },
+    Obstacle { x: u32, y: u32 },
+}
+impl Command {
+    pub fn apply(&self, fluid: &mut Fluid) {
+        match *self {
+            Command::Density { x, y, amount } => fluid.add_density(x, y, amount),
+            Command::Velocity {
+                x,
+                y,
+                amount_x,
+                amount_y,
+            } => fluid.add_velocity(x, y, amount_x, amount_y),
+            Command::Obstacle { x, y } => fluid.add_obstacle(x, y),
+        }
+    }
+}
+
+/// A list of commands, each tagged with the frame before whose step it is applied.
+///
+/// Scripts are stored as text, one command per line:
+///
+/// ```text
+/// 12 density 40 52 18
+/// 12 velocity 41 52 310.5 204
+/// 30 obstacle 64 64
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputScript {
+    pub commands: Vec<(u64, Command)>,
+}
+impl InputScript {
+    pub fn new() -> InputScript {
+        InputScript { commands: vec![] }
+    }
+
+    pub fn record(&mut self, frame: u64, command: Command) {
+        self.commands.push((frame, command));
+    }
+
+    /// Applies every command recorded for `frame`, in recording order.
+    pub fn apply_frame(&self, frame: u64, fluid: &mut Fluid) {
+        for (f, command) in &self.commands {
+            if *f == frame {
+                command.apply(fluid);
+            }
+        }
+    }
+
+    /// The frame of the last recorded command, if any.
+    pub fn last_frame(&self) -> Option<u64> {
+        self.commands.iter().map(|(f, _)| *f).max()
+    }
+
+    /// Steps `fluid` for `frames` frames exactly like the interactive demo does,
+    /// without opening a window.
+    pub fn replay(&self, fluid: &mut Fluid, frames: u64) {
+        for frame in 0..frames {
+            self.apply_frame(frame, fluid);
+            fluid.step();
+            fluid.fadeD();
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        for (frame, command) in &self.commands {
+            match command {
+                Command::Density { x, y, amount } => {
+                    writeln!(w, "{} density {} {} {}", frame, x, y, amount)?
+                }
+                Command::Velocity {
+                    x,
+                    y,
+                    amount_x,
+                    amount_y,
+                } => writeln!(
+                    w,
+                    "{} velocity {} {} {} {}",
+                    frame, x, y, amount_x, amount_y
+                )?,
+                Command::Obstacle { x, y } => writeln!(w, "{} obstacle {} {}", frame, x, y)?,
+            }
+        }
+        w.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<InputScript> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut script = InputScript::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some((frame, command)) => script.record(frame, command),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid command on line {}: {}", number + 1, line),
+                    ))
+                }
+            }
+        }
+        Ok(script)
+    }
+}
+
+fn parse_line(line: &str) -> Option<(u64, Command)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let frame = parts.first()?.parse().ok()?;
+    let x = parts.get(2)?.parse().ok()?;
+    let y = parts.get(3)?.parse().ok()?;
+
+    let command = match (*parts.get(1)?, parts.len()) {
+        ("density", 5) => Command::Density {
+            x,
+            y,
+            amount: parts[4].parse().ok()?,
+        },
+        ("velocity", 6) => Command::Velocity {
+            x,
+            y,
+            amount_x: parts[4].parse().ok()?,
+            amount_y: parts[5].parse().ok()?,
+        },
+        ("obstacle", 4) => Command::Obstacle { x, y },
+        _ => return None,
+    };
+    Some((frame, command))
+}
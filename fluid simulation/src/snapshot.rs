@@ -0,0 +1,330 @@
+//! Saving, loading and exporting of `Fluid` state.
+
+use crate::{fluid::Fluid, N};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"FLSN";
+const VERSION: u32 = 1;
+
+impl Fluid {
+    /// Writes the full grid (parameters, velocity, density and obstacles) to `path`.
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_snapshot(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Reads a snapshot written by `save_snapshot`.
+    pub fn load_snapshot<P: AsRef<Path>>(path: P) -> io::Result<Fluid> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Fluid::read_snapshot(&mut reader)
+    }
+
+    pub fn write_snapshot<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&(self.size as u32).to_le_bytes())?;
+        w.write_all(&self.dt.to_le_bytes())?;
+        w.write_all(&self.diff.to_le_bytes())?;
+        w.write_all(&self.visc.to_le_bytes())?;
+
+        for field in [
+            &self.s,
+            &self.density,
+            &self.v_x,
+            &self.v_y,
+            &self.v_x0,
+            &self.v_y0,
+        ]
+        .iter()
+        {
+            for value in field.iter() {
+                w.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        // Obstacles are packed one bit per cell.
+        let mut packed = vec![0u8; self.obstacles.len().div_ceil(8)];
+        for (i, obstacle) in self.obstacles.iter().enumerate() {
+            if *obstacle {
+                packed[i / 8] |= 1 << (i % 8);
+            }
+        }
+        w.write_all(&packed)
+    }
+
+    pub fn read_snapshot<R: Read>(r: &mut R) -> io::Result<Fluid> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a fluid snapshot"));
+        }
+
+        let version = read_u32(r)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported snapshot version {}",
+                version
+            )));
+        }
+
+        let size = read_u32(r)? as i32;
+        if size != N as i32 {
+            return Err(invalid_data(&format!(
+                "snapshot grid is {}x{}, but this build uses {}x{}",
+                size, size, N, N
+            )));
+        }
+
+        let dt = read_f64(r)?;
+        let diff = read_f64(r)?;
+        let visc = read_f64(r)?;
+
+        let mut fluid = Fluid::new(0, visc, dt);
+        fluid.diff = diff;
+
+        let cells = (N * N) as usize;
+        fluid.s = read_field(r, cells)?;
+        fluid.density = read_field(r, cells)?;
+        fluid.v_x = read_field(r, cells)?;
+        fluid.v_y = read_field(r, cells)?;
+        fluid.v_x0 = read_field(r, cells)?;
+        fluid.v_y0 = read_field(r, cells)?;
+
+        let mut packed = vec![0u8; cells.div_ceil(8)];
+        r.read_exact(&mut packed)?;
+        for i in 0..cells {
+            fluid.obstacles[i] = packed[i / 8] & (1 << (i % 8)) != 0;
+        }
+
+        Ok(fluid)
+    }
+
+    /// Exports `density`, `v_x`, `v_y` and `obstacles` as `N`x`N` NumPy arrays into `dir`.
+    pub fn export_npy<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let obstacles: Vec<f64> = self
+            .obstacles
+            .iter()
+            .map(|o| if *o { 1.0 } else { 0.0 })
+            .collect();
+
+        for (name, field) in [
+            ("density", &self.density),
+            ("v_x", &self.v_x),
+            ("v_y", &self.v_y),
+            ("obstacles", &obstacles),
+        ]
+        .iter()
+        {
+            let mut writer = BufWriter::new(File::create(dir.join(format!("{}.npy", name)))?);
+            write_npy(&mut writer, field, (N as usize, N as usize))?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The largest absolute difference between any velocity or density cell of two fluids.
+    pub fn max_difference(&self, other: &Fluid) -> f64 {
+        let mut max = 0.0;
+        for (a, b) in [
+            (&self.density, &other.density),
+            (&self.v_x, &other.v_x),
+            (&self.v_y, &other.v_y),
+        ]
+        .iter()
+        {
+            for (x, y) in a.iter().zip(b.iter()) {
+                let d = (x - y).abs();
+                if d > max {
+                    max = d;
+                }
+            }
+        }
+        max
+    }
+}
+
+/// Writes `data` as a little-endian `f64` NumPy (version 1.0) array in C order.
+pub fn write_npy<W: Write>(w: &mut W, data: &[f64], shape: (usize, usize)) -> io::Result<()> {
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+        shape.0, shape.1
+    );
+    // The magic string, version and header length take 10 bytes, and the
+    // header (terminated by a newline) has to pad the total to a multiple of 64.
+    let unpadded = 10 + header.len() + 1;
+    let padding = (64 - unpadded % 64) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    w.write_all(b"\x93NUMPY")?;
+    w.write_all(&[1, 0])?;
+    w.write_all(&(header.len() as u16).to_le_bytes())?;
+    w.write_all(header.as_bytes())?;
+    for value in data {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(f64::from_le_bytes(buf))
+}
+
+fn read_field<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<f64>> {
+    let mut field = Vec::with_capacity(len);
+    for _ in 0..len {
+        field.push(read_f64(r)?);
+    }
+    Ok(field)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn stirred() -> Fluid {
+        let mut fluid = Fluid::new(0, 0.0000001, 0.2);
+        fluid.diff = 0.0001;
+        for (i, cell) in fluid.density.iter_mut().enumerate() {
+            *cell = (i as f64 * 0.37).sin() * 100.0;
+        }
+        for i in 0..20 {
+            fluid.add_velocity(100 + i, 120, 2.0, -1.0 / 3.0);
+        }
+        fluid.s[5] = f64::MIN_POSITIVE;
+        fluid.v_x0[7] = -0.0;
+        fluid.v_y0[9] = 1e300;
+        fluid.add_obstacle(30, 40);
+        fluid.add_obstacle(31, 40);
+        fluid
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let fluid = stirred();
+        let mut bytes = vec![];
+        fluid.write_snapshot(&mut bytes).unwrap();
+
+        let cells = (N * N) as usize;
+        assert_eq!(bytes.len(), 4 + 4 + 4 + 3 * 8 + 6 * cells * 8 + cells.div_ceil(8));
+        assert_eq!(&bytes[..4], MAGIC);
+
+        let read = Fluid::read_snapshot(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(read.size, fluid.size);
+        assert_eq!(read.dt, fluid.dt);
+        assert_eq!(read.diff, fluid.diff);
+        assert_eq!(read.visc, fluid.visc);
+        assert_eq!(read.s, fluid.s);
+        assert_eq!(read.density, fluid.density);
+        assert_eq!(read.v_x, fluid.v_x);
+        assert_eq!(read.v_y, fluid.v_y);
+        assert_eq!(read.v_x0, fluid.v_x0);
+        assert_eq!(read.v_y0, fluid.v_y0);
+        assert_eq!(read.obstacles, fluid.obstacles);
+        assert_eq!(read.max_difference(&fluid), 0.0);
+    }
+
+    #[test]
+    fn snapshot_file_round_trip() {
+        let fluid = stirred();
+        let path = std::env::temp_dir().join(format!("fluid_snapshot_{}.flsn", std::process::id()));
+        fluid.save_snapshot(&path).unwrap();
+        let read = Fluid::load_snapshot(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap().max_difference(&fluid), 0.0);
+    }
+
+    fn error_kind(bytes: &[u8]) -> io::ErrorKind {
+        match Fluid::read_snapshot(&mut Cursor::new(bytes)) {
+            Ok(_) => panic!("read an invalid snapshot"),
+            Err(error) => error.kind(),
+        }
+    }
+
+    #[test]
+    fn snapshot_rejects_bad_input() {
+        let mut bytes = vec![];
+        stirred().write_snapshot(&mut bytes).unwrap();
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert_eq!(error_kind(&wrong_magic), io::ErrorKind::InvalidData);
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(error_kind(&wrong_version), io::ErrorKind::InvalidData);
+
+        let mut wrong_size = bytes.clone();
+        wrong_size[8..12].copy_from_slice(&(N + 1).to_le_bytes());
+        assert_eq!(error_kind(&wrong_size), io::ErrorKind::InvalidData);
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert_eq!(error_kind(truncated), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn npy_header_is_padded_to_64_bytes() {
+        for &shape in [(1, 1), (3, 2), (256, 256), (12345, 678)].iter() {
+            let data: Vec<f64> = (0..shape.0 * shape.1).map(|i| i as f64 * 0.5).collect();
+            let mut bytes = vec![];
+            write_npy(&mut bytes, &data, shape).unwrap();
+
+            assert_eq!(&bytes[..6], b"\x93NUMPY");
+            assert_eq!(&bytes[6..8], &[1, 0]);
+            let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+            let data_start = 10 + header_len;
+            assert_eq!(data_start % 64, 0);
+            assert_eq!(bytes[data_start - 1], b'\n');
+
+            let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+            assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False,"));
+            assert!(header.contains(&format!("'shape': ({}, {}),", shape.0, shape.1)));
+
+            assert_eq!(bytes.len(), data_start + data.len() * 8);
+            for (i, chunk) in bytes[data_start..].chunks(8).enumerate() {
+                let mut value = [0u8; 8];
+                value.copy_from_slice(chunk);
+                assert_eq!(f64::from_le_bytes(value), data[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn export_npy_writes_every_field() {
+        let fluid = stirred();
+        let dir = std::env::temp_dir().join(format!("fluid_npy_{}", std::process::id()));
+        fluid.export_npy(&dir).unwrap();
+
+        let header_len = 128;
+        for name in ["density", "v_x", "v_y", "obstacles"].iter() {
+            let bytes = fs::read(dir.join(format!("{}.npy", name))).unwrap();
+            assert_eq!(bytes.len(), header_len + (N * N) as usize * 8, "{}", name);
+        }
+        let obstacles = fs::read(dir.join("obstacles.npy")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let cell = header_len + crate::IX(30, 40) * 8;
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&obstacles[cell..cell + 8]);
+        assert_eq!(f64::from_le_bytes(value), 1.0);
+    }
+}
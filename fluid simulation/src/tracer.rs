@@ -0,0 +1,177 @@
+use crate::{fluid::Fluid, scl, vector::Vector, N};
+use opengl_graphics::GlGraphics;
+use piston::RenderArgs;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    RK2,
+    RK4,
+}
+
+/// A passive particle carried along by the velocity field. Positions are in grid cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracer {
+    pub pos: Vector,
+    pub trail: VecDeque<Vector>,
+    pub color: [f32; 4],
+    pub age: u32,
+}
+impl Tracer {
+    pub fn new(x: f64, y: f64, color: [f32; 4]) -> Tracer {
+        Tracer {
+            pos: Vector { x, y },
+            trail: VecDeque::new(),
+            color,
+            age: 0,
+        }
+    }
+
+    pub fn update(&mut self, fluid: &Fluid, integrator: Integrator, trail_length: usize) {
+        self.trail.push_back(self.pos);
+        while self.trail.len() > trail_length {
+            self.trail.pop_front();
+        }
+
+        // The solver advects by `dt * (N - 2)` cells per unit of velocity, so
+        // tracers use the same scale to move with the dye.
+        let h = fluid.dt * (N - 2) as f64;
+        let v = |p: Vector| fluid.velocity_at(p.x, p.y) * h;
+
+        let p = self.pos;
+        self.pos = match integrator {
+            Integrator::RK2 => {
+                let k1 = v(p);
+                let k2 = v(p + k1 * 0.5);
+                p + k2
+            }
+            Integrator::RK4 => {
+                let k1 = v(p);
+                let k2 = v(p + k1 * 0.5);
+                let k3 = v(p + k2 * 0.5);
+                let k4 = v(p + k3);
+                p + (k1 + k2 * 2.0 + k3 * 2.0 + k4) * (1.0 / 6.0)
+            }
+        };
+        self.age += 1;
+    }
+
+    pub fn in_bounds(&self) -> bool {
+        self.pos.x >= 1.0
+            && self.pos.x < (N - 1) as f64
+            && self.pos.y >= 1.0
+            && self.pos.y < (N - 1) as f64
+    }
+
+    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+        use graphics::*;
+
+        let s = scl as f64;
+        gl.draw(args.viewport(), |c, gl| {
+            let mut points = self.trail.iter().chain(std::iter::once(&self.pos));
+            if let Some(mut prev) = points.next() {
+                let count = self.trail.len().max(1) as f32;
+                for (i, p) in points.enumerate() {
+                    let mut color = self.color;
+                    color[3] *= (i + 1) as f32 / count;
+                    line(color, 0.5, [prev.x * s, prev.y * s, p.x * s, p.y * s], c.transform, gl);
+                    prev = p;
+                }
+            }
+            ellipse(
+                self.color,
+                [self.pos.x * s - 1.0, self.pos.y * s - 1.0, 2.0, 2.0],
+                c.transform,
+                gl,
+            );
+        });
+    }
+}
+
+/// Emitters and the tracers they have spawned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracerSystem {
+    pub tracers: Vec<Tracer>,
+    pub emitters: Vec<Vector>,
+    pub integrator: Integrator,
+    pub trail_length: usize,
+    pub lifetime: u32,
+    pub max_tracers: usize,
+}
+impl Default for TracerSystem {
+    fn default() -> Self {
+        TracerSystem::new()
+    }
+}
+impl TracerSystem {
+    pub fn new() -> TracerSystem {
+        TracerSystem {
+            tracers: vec![],
+            emitters: vec![],
+            integrator: Integrator::RK2,
+            trail_length: 20,
+            lifetime: 600,
+            max_tracers: 2000,
+        }
+    }
+
+    /// Adds an emitter at a position in grid cells.
+    pub fn add_emitter(&mut self, x: f64, y: f64) {
+        self.emitters.push(Vector { x, y });
+    }
+
+    pub fn clear(&mut self) {
+        self.tracers = vec![];
+        self.emitters = vec![];
+    }
+
+    pub fn toggle_integrator(&mut self) {
+        self.integrator = match self.integrator {
+            Integrator::RK2 => Integrator::RK4,
+            Integrator::RK4 => Integrator::RK2,
+        };
+    }
+
+    pub fn update(&mut self, fluid: &Fluid) {
+        let mut rng = rand::thread_rng();
+
+        for (i, emitter) in self.emitters.iter().enumerate() {
+            let hue = i as f32 * 0.618;
+            let color = [
+                0.5 + 0.5 * (hue * TAU).cos(),
+                0.5 + 0.5 * ((hue + 0.33) * TAU).cos(),
+                0.5 + 0.5 * ((hue + 0.67) * TAU).cos(),
+                0.8,
+            ];
+            self.tracers.push(Tracer::new(
+                emitter.x + rng.gen_range(-0.5..0.5),
+                emitter.y + rng.gen_range(-0.5..0.5),
+                color,
+            ));
+        }
+
+        for tracer in &mut self.tracers {
+            tracer.update(fluid, self.integrator, self.trail_length);
+        }
+
+        let lifetime = self.lifetime;
+        self.tracers.retain(|t| {
+            t.age < lifetime
+                && t.in_bounds()
+                && !fluid.obstacles[crate::IX(t.pos.x as u32, t.pos.y as u32)]
+        });
+
+        if self.tracers.len() > self.max_tracers {
+            let excess = self.tracers.len() - self.max_tracers;
+            self.tracers.drain(0..excess);
+        }
+    }
+
+    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+        for tracer in &self.tracers {
+            tracer.show(gl, args);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Compares brute-force ray casting against the wall grid.
+//!
+//! Run with `cargo bench`. Each case casts a full 360 ray sweep from a number of
+//! random points and checks that both methods agree on every hit.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+use Raycasting::{boundary::Boundary, grid::WallGrid, ray::Ray, WINDOW_HEIGHT, WINDOW_WIDTH};
+
+const ORIGINS: usize = 50;
+
+fn random_walls(rng: &mut StdRng, count: usize, max_length: f64) -> Vec<Boundary> {
+    let (w, h) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+    let mut walls = vec![
+        Boundary::new(0.0, 0.0, w, 0.0),
+        Boundary::new(w, 0.0, w, h),
+        Boundary::new(w, h, 0.0, h),
+        Boundary::new(0.0, h, 0.0, 0.0),
+    ];
+    for _ in 0..count {
+        let (x, y) = (rng.gen_range(0.0..w), rng.gen_range(0.0..h));
+        walls.push(Boundary::new(
+            x,
+            y,
+            (x + rng.gen_range(-max_length..max_length)).max(0.0).min(w),
+            (y + rng.gen_range(-max_length..max_length)).max(0.0).min(h),
+        ));
+    }
+    walls
+}
+
+fn sweeps(rng: &mut StdRng) -> Vec<Ray> {
+    let mut rays = vec![];
+    for _ in 0..ORIGINS {
+        let pos = [
+            rng.gen_range(0.0..WINDOW_WIDTH as f64),
+            rng.gen_range(0.0..WINDOW_HEIGHT as f64),
+        ];
+        for i in 0..360 {
+            rays.push(Ray::new(pos, (i as f64).to_radians()));
+        }
+    }
+    rays
+}
+
+fn time<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    // One warm-up run, then the best of five.
+    let hits = f();
+    let mut best = Duration::from_secs(u64::MAX);
+    for _ in 0..5 {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+    (best, hits)
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(30);
+
+    println!(
+        "{:>6} {:>8} {:>14} {:>14} {:>14} {:>8}",
+        "walls", "length", "build", "brute force", "grid", "speedup"
+    );
+    for &(count, max_length) in &[
+        (10, 1000.0),
+        (100, 200.0),
+        (1000, 50.0),
+        (1000, 500.0),
+        (10000, 20.0),
+    ] {
+        let walls = random_walls(&mut rng, count, max_length);
+        let rays = sweeps(&mut rng);
+
+        let start = Instant::now();
+        let grid = WallGrid::new(&walls);
+        let build = start.elapsed();
+
+        for ray in &rays {
+            let brute = ray.nearest_hit(&walls).map(|h| h.dist);
+            let fast = grid.nearest_hit(ray).map(|h| h.dist);
+            assert_eq!(brute, fast, "grid and brute force disagree for {:?}", ray);
+        }
+
+        let (brute, hits) = time(|| rays.iter().filter_map(|r| r.nearest_hit(&walls)).count());
+        let (fast, _) = time(|| rays.iter().filter_map(|r| grid.nearest_hit(r)).count());
+
+        println!(
+            "{:>6} {:>8} {:>14?} {:>14?} {:>14?} {:>7.1}x",
+            walls.len(),
+            max_length,
+            build,
+            brute,
+            fast,
+            brute.as_secs_f64() / fast.as_secs_f64()
+        );
+        assert_eq!(hits, rays.len());
+    }
+}
@@ -0,0 +1,103 @@
+use crate::vector::Vector;
+use graphics::color::WHITE;
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+use piston::RenderArgs;
+use serde::{Deserialize, Serialize};
+
+/// What happens to a ray that hits a wall.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Material {
+    /// Stops the ray.
+    #[default]
+    Absorbing,
+    /// Reflects the ray perfectly.
+    Mirror,
+    /// Splits the ray into a reflected and a refracted part. Closed shapes made of
+    /// glass walls act as prisms, lenses or fibers with this index of refraction.
+    Glass { ior: f64 },
+}
+impl Material {
+    /// The next material in the editor's cycle.
+    pub fn next(&self) -> Material {
+        match self {
+            Material::Absorbing => Material::Mirror,
+            Material::Mirror => Material::Glass { ior: 1.5 },
+            Material::Glass { .. } => Material::Absorbing,
+        }
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        match self {
+            Material::Absorbing => WHITE,
+            Material::Mirror => [0.6, 0.8, 1.0, 1.0],
+            Material::Glass { .. } => [0.3, 1.0, 0.9, 0.6],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Boundary {
+    pub a: Vector,
+    pub b: Vector,
+    #[serde(default)]
+    pub material: Material,
+}
+impl Boundary {
+    pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Boundary {
+        Boundary {
+            a: Vector { x: x1, y: y1 },
+            b: Vector { x: x2, y: y2 },
+            material: Material::Absorbing,
+        }
+    }
+
+    pub fn with_material(mut self, material: Material) -> Boundary {
+        self.material = material;
+        self
+    }
+
+    pub fn length(&self) -> f64 {
+        self.a.distance(&self.b)
+    }
+
+    /// A unit vector perpendicular to the wall, pointing to the left of `a` → `b`.
+    pub fn normal(&self) -> Vector {
+        let d = self.b - self.a;
+        let mut n = Vector::new(-d.y, d.x);
+        n.normalize();
+        n
+    }
+
+    // The shortest distance from a point to any point on this wall.
+    pub fn distance_to(&self, p: Vector) -> f64 {
+        let ab = self.b - self.a;
+        let len_sq = ab.dot(&ab);
+        if len_sq == 0.0 {
+            return p.distance(&self.a);
+        }
+        let t = ((p - self.a).dot(&ab) / len_sq).clamp(0.0, 1.0);
+        p.distance(&(self.a + ab * t))
+    }
+
+    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+        gl.draw(args.viewport(), |context, gl| {
+            self.draw(context.transform, gl);
+        });
+    }
+
+    pub fn draw(&self, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+        let width = match self.material {
+            Material::Absorbing => 1.0,
+            _ => 2.0,
+        };
+        line(
+            self.material.color(),
+            width,
+            [self.a.x, self.a.y, self.b.x, self.b.y],
+            transform,
+            gl,
+        )
+    }
+}
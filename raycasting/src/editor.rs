@@ -0,0 +1,199 @@
+use crate::{
+    boundary::Boundary,
+    vector::Vector,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+
+// How close, in pixels, the cursor has to be to pick a wall or an endpoint.
+const PICK_DISTANCE: f64 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Drag {
+    /// Drawing a new wall starting at this point.
+    New(Vector),
+    /// Moving a whole wall, remembering where it was grabbed and its original position.
+    Move(usize, Vector, Boundary),
+    /// Moving one endpoint of a wall (`true` for `a`, `false` for `b`).
+    Endpoint(usize, bool),
+}
+
+/// Interactive editing of a wall set: click-drag on empty space to add a wall,
+/// drag a wall to move it, drag an endpoint to reshape it, right click to delete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Editor {
+    /// Grid size that points snap to, if snapping is enabled.
+    pub snap: Option<f64>,
+    pub grid_size: f64,
+    pub selected: Option<usize>,
+    pub cursor: Vector,
+    drag: Option<Drag>,
+}
+impl Default for Editor {
+    fn default() -> Self {
+        Editor::new()
+    }
+}
+impl Editor {
+    pub fn new() -> Editor {
+        Editor {
+            snap: Some(20.0),
+            grid_size: 20.0,
+            selected: None,
+            cursor: Vector::default(),
+            drag: None,
+        }
+    }
+
+    /// Forgets the selected wall and any drag in progress, for when the walls are
+    /// replaced with another set.
+    pub fn deselect(&mut self) {
+        self.selected = None;
+        self.drag = None;
+    }
+
+    pub fn toggle_snap(&mut self) {
+        self.snap = match self.snap {
+            Some(_) => None,
+            None => Some(self.grid_size),
+        };
+    }
+
+    fn snapped(&self, p: Vector) -> Vector {
+        match self.snap {
+            Some(size) => Vector::new((p.x / size).round() * size, (p.y / size).round() * size),
+            None => p,
+        }
+    }
+
+    /// The wall closest to `p`, if any is within picking distance.
+    pub fn pick(&self, walls: &[Boundary], p: Vector) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for (i, wall) in walls.iter().enumerate() {
+            let d = wall.distance_to(p);
+            if d <= PICK_DISTANCE && best.is_none_or(|(_, bd)| d < bd) {
+                best = Some((i, d));
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+
+    fn pick_endpoint(&self, walls: &[Boundary], p: Vector) -> Option<(usize, bool)> {
+        for (i, wall) in walls.iter().enumerate() {
+            if wall.a.distance(&p) <= PICK_DISTANCE {
+                return Some((i, true));
+            }
+            if wall.b.distance(&p) <= PICK_DISTANCE {
+                return Some((i, false));
+            }
+        }
+        None
+    }
+
+    /// Moves the cursor, dragging whatever is being dragged. Returns whether any
+    /// wall changed.
+    pub fn mouse_move(&mut self, walls: &mut [Boundary], pos: [f64; 2]) -> bool {
+        self.cursor = Vector::new(pos[0], pos[1]);
+        let p = self.snapped(self.cursor);
+
+        match self.drag {
+            Some(Drag::Move(i, grab, original)) => {
+                let offset = p - self.snapped(grab);
+                walls[i] = Boundary {
+                    a: original.a + offset,
+                    b: original.b + offset,
+                    ..original
+                };
+            }
+            Some(Drag::Endpoint(i, true)) => walls[i].a = p,
+            Some(Drag::Endpoint(i, false)) => walls[i].b = p,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn mouse_press(&mut self, walls: &[Boundary]) {
+        if let Some((i, which)) = self.pick_endpoint(walls, self.cursor) {
+            self.selected = Some(i);
+            self.drag = Some(Drag::Endpoint(i, which));
+        } else if let Some(i) = self.pick(walls, self.cursor) {
+            self.selected = Some(i);
+            self.drag = Some(Drag::Move(i, self.cursor, walls[i]));
+        } else {
+            self.selected = None;
+            self.drag = Some(Drag::New(self.snapped(self.cursor)));
+        }
+    }
+
+    /// Finishes a drag, adding the wall being drawn if there is one. Returns
+    /// whether any wall changed.
+    pub fn mouse_release(&mut self, walls: &mut Vec<Boundary>) -> bool {
+        let mut changed = false;
+        if let Some(Drag::New(start)) = self.drag {
+            let end = self.snapped(self.cursor);
+            if start.distance(&end) > 1.0 {
+                walls.push(Boundary::new(start.x, start.y, end.x, end.y));
+                self.selected = Some(walls.len() - 1);
+                changed = true;
+            }
+        }
+        self.drag = None;
+        changed
+    }
+
+    /// Deletes the wall under the cursor, or the selected wall if `under_cursor` is false.
+    /// Returns whether a wall was deleted.
+    pub fn delete(&mut self, walls: &mut Vec<Boundary>, under_cursor: bool) -> bool {
+        let target = if under_cursor {
+            self.pick(walls, self.cursor)
+        } else {
+            self.selected
+        };
+        if let Some(i) = target {
+            walls.remove(i);
+            self.selected = None;
+            self.drag = None;
+            return true;
+        }
+        false
+    }
+
+    pub fn draw(&self, walls: &[Boundary], transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        if self.snap.is_some() {
+            let grid = [1.0, 1.0, 1.0, 0.05];
+            let mut x = 0.0;
+            while x <= WINDOW_WIDTH as f64 {
+                line(grid, 0.5, [x, 0.0, x, WINDOW_HEIGHT as f64], transform, gl);
+                x += self.grid_size;
+            }
+            let mut y = 0.0;
+            while y <= WINDOW_HEIGHT as f64 {
+                line(grid, 0.5, [0.0, y, WINDOW_WIDTH as f64, y], transform, gl);
+                y += self.grid_size;
+            }
+        }
+
+        for (i, wall) in walls.iter().enumerate() {
+            let color = if Some(i) == self.selected {
+                [1.0, 0.5, 0.0, 1.0]
+            } else {
+                [1.0, 1.0, 1.0, 1.0]
+            };
+            line(color, 1.5, [wall.a.x, wall.a.y, wall.b.x, wall.b.y], transform, gl);
+            for p in [wall.a, wall.b].iter() {
+                rectangle(color, [p.x - 3.0, p.y - 3.0, 6.0, 6.0], transform, gl);
+            }
+        }
+
+        if let Some(Drag::New(start)) = self.drag {
+            let end = self.snapped(self.cursor);
+            line([0.0, 1.0, 0.0, 1.0], 1.5, [start.x, start.y, end.x, end.y], transform, gl);
+        }
+
+        let c = self.snapped(self.cursor);
+        ellipse([0.0, 1.0, 0.0, 0.8], [c.x - 4.0, c.y - 4.0, 8.0, 8.0], transform, gl);
+    }
+}
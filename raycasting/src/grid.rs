@@ -0,0 +1,223 @@
+use crate::{
+    boundary::Boundary,
+    ray::{Hit, Ray},
+    vector::Vector,
+};
+
+/// A uniform grid over a set of walls. Rays walk the grid cell by cell (a DDA
+/// traversal) and only test the walls in the cells they pass through, stopping
+/// as soon as the closest hit so far lies inside the current cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallGrid {
+    walls: Vec<Boundary>,
+    min: Vector,
+    cell_size: f64,
+    cols: usize,
+    rows: usize,
+    /// Indices into `walls` for every cell, row by row.
+    cells: Vec<Vec<usize>>,
+}
+impl WallGrid {
+    /// Builds a grid with a cell size chosen from the number of walls and their extent.
+    pub fn new(walls: &[Boundary]) -> WallGrid {
+        let (min, max) = bounds(walls);
+        let size = max - min;
+        // Aim for roughly one wall per cell, but never more than 256 cells per side.
+        let area = (size.x * size.y).max(1.0);
+        let cell_size = (area / walls.len().max(1) as f64)
+            .sqrt()
+            .max(size.x.max(size.y) / 256.0)
+            .max(1.0);
+        WallGrid::with_cell_size(walls, cell_size)
+    }
+
+    pub fn with_cell_size(walls: &[Boundary], cell_size: f64) -> WallGrid {
+        let (min, max) = bounds(walls);
+        let cols = (((max.x - min.x) / cell_size).floor() as usize + 1).max(1);
+        let rows = (((max.y - min.y) / cell_size).floor() as usize + 1).max(1);
+
+        let mut grid = WallGrid {
+            walls: walls.to_vec(),
+            min,
+            cell_size,
+            cols,
+            rows,
+            cells: vec![vec![]; cols * rows],
+        };
+
+        for (i, wall) in walls.iter().enumerate() {
+            let (c0, r0) = grid.cell_of(Vector::new(wall.a.x.min(wall.b.x), wall.a.y.min(wall.b.y)));
+            let (c1, r1) = grid.cell_of(Vector::new(wall.a.x.max(wall.b.x), wall.a.y.max(wall.b.y)));
+            for r in r0..=r1 {
+                for c in c0..=c1 {
+                    // Cells are padded slightly so rounding can't drop a wall
+                    // that only grazes a corner.
+                    let pad = cell_size * 1e-6;
+                    let cell_min = Vector::new(
+                        min.x + c as f64 * cell_size - pad,
+                        min.y + r as f64 * cell_size - pad,
+                    );
+                    if segment_touches_box(wall, cell_min, cell_size + 2.0 * pad) {
+                        grid.cells[c + r * cols].push(i);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn walls(&self) -> &[Boundary] {
+        &self.walls
+    }
+
+    fn cell_of(&self, p: Vector) -> (usize, usize) {
+        let c = ((p.x - self.min.x) / self.cell_size).floor().max(0.0) as usize;
+        let r = ((p.y - self.min.y) / self.cell_size).floor().max(0.0) as usize;
+        (c.min(self.cols - 1), r.min(self.rows - 1))
+    }
+
+    /// The closest wall hit by `ray`, exactly as `Ray::nearest_hit` would find it.
+    pub fn nearest_hit(&self, ray: &Ray) -> Option<Hit> {
+        if self.walls.is_empty() {
+            return None;
+        }
+
+        let len = ray.dir.mag();
+        if len == 0.0 {
+            return None;
+        }
+        let dir = ray.dir * (1.0 / len);
+
+        // Clip the ray against the grid's bounding box.
+        let max = Vector::new(
+            self.min.x + self.cols as f64 * self.cell_size,
+            self.min.y + self.rows as f64 * self.cell_size,
+        );
+        let (mut t_enter, mut t_leave) = (0.0f64, f64::MAX);
+        for (o, d, lo, hi) in [
+            (ray.pos.x, dir.x, self.min.x, max.x),
+            (ray.pos.y, dir.y, self.min.y, max.y),
+        ]
+        .iter()
+        {
+            if *d == 0.0 {
+                if o < lo || o > hi {
+                    return None;
+                }
+            } else {
+                let t0 = (lo - o) / d;
+                let t1 = (hi - o) / d;
+                t_enter = t_enter.max(t0.min(t1));
+                t_leave = t_leave.min(t0.max(t1));
+            }
+        }
+        if t_enter > t_leave {
+            return None;
+        }
+
+        let start = ray.pos + dir * t_enter;
+        let (mut c, mut r) = self.cell_of(start);
+
+        let step_c: isize = if dir.x > 0.0 { 1 } else { -1 };
+        let step_r: isize = if dir.y > 0.0 { 1 } else { -1 };
+        let boundary_t = |cell: usize, step: isize, origin: f64, d: f64, min: f64| {
+            if d == 0.0 {
+                return f64::MAX;
+            }
+            let next = if step > 0 { cell + 1 } else { cell };
+            (min + next as f64 * self.cell_size - origin) / d
+        };
+        let mut t_max_c = boundary_t(c, step_c, ray.pos.x, dir.x, self.min.x);
+        let mut t_max_r = boundary_t(r, step_r, ray.pos.y, dir.y, self.min.y);
+        let t_delta_c = if dir.x == 0.0 { f64::MAX } else { self.cell_size / dir.x.abs() };
+        let t_delta_r = if dir.y == 0.0 { f64::MAX } else { self.cell_size / dir.y.abs() };
+
+        let mut best: Option<Hit> = None;
+        loop {
+            for &i in &self.cells[c + r * self.cols] {
+                if let Some(pt) = ray.cast(&self.walls[i]) {
+                    let dist = ray.pos.distance(&pt);
+                    if best.is_none_or(|b| dist < b.dist) {
+                        best = Some(Hit {
+                            dist,
+                            point: pt,
+                            wall: i,
+                        });
+                    }
+                }
+            }
+
+            // Any hit closer than where the ray leaves this cell can't be beaten
+            // by walls in later cells.
+            let t_exit = t_max_c.min(t_max_r);
+            if let Some(hit) = best {
+                if hit.dist <= t_exit {
+                    return best;
+                }
+            }
+
+            if t_max_c < t_max_r {
+                if (step_c < 0 && c == 0) || (step_c > 0 && c + 1 >= self.cols) {
+                    return best;
+                }
+                c = (c as isize + step_c) as usize;
+                t_max_c += t_delta_c;
+            } else {
+                if (step_r < 0 && r == 0) || (step_r > 0 && r + 1 >= self.rows) {
+                    return best;
+                }
+                r = (r as isize + step_r) as usize;
+                t_max_r += t_delta_r;
+            }
+        }
+    }
+}
+
+fn bounds(walls: &[Boundary]) -> (Vector, Vector) {
+    let mut min = Vector::new(f64::MAX, f64::MAX);
+    let mut max = Vector::new(f64::MIN, f64::MIN);
+    for wall in walls {
+        for p in [wall.a, wall.b].iter() {
+            min.x = min.x.min(p.x);
+            min.y = min.y.min(p.y);
+            max.x = max.x.max(p.x);
+            max.y = max.y.max(p.y);
+        }
+    }
+    if walls.is_empty() {
+        (Vector::default(), Vector::default())
+    } else {
+        (min, max)
+    }
+}
+
+// Whether a wall passes through (or touches) the square cell at `min` (Liang–Barsky clipping).
+fn segment_touches_box(wall: &Boundary, min: Vector, size: f64) -> bool {
+    let d = wall.b - wall.a;
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [
+        (-d.x, wall.a.x - min.x),
+        (d.x, min.x + size - wall.a.x),
+        (-d.y, wall.a.y - min.y),
+        (d.y, min.y + size - wall.a.y),
+    ]
+    .iter()
+    {
+        if *p == 0.0 {
+            if *q < 0.0 {
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if *p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+            if t0 > t1 {
+                return false;
+            }
+        }
+    }
+    true
+}
@@ -0,0 +1,370 @@
+//! Saving and loading of wall sets, and importing them from SVG floor plans.
+
+use crate::{
+    boundary::Boundary,
+    vector::Vector,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A set of walls, stored as JSON:
+///
+/// ```json
+/// { "walls": [ { "a": { "x": 0.0, "y": 0.0 }, "b": { "x": 100.0, "y": 0.0 } } ] }
+/// ```
+///
+/// Walls may also give a `"material"`: `"Absorbing"` (the default), `"Mirror"`
+/// or `{ "Glass": { "ior": 1.5 } }`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub walls: Vec<Boundary>,
+}
+impl Level {
+    pub fn new(walls: Vec<Boundary>) -> Level {
+        Level { walls }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Level> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Imports every `path`, `line`, `polyline`, `polygon` and `rect` in an SVG file
+    /// as walls, scaled to fit the window.
+    pub fn from_svg<P: AsRef<Path>>(path: P) -> io::Result<Level> {
+        let walls = parse_svg(&fs::read_to_string(path)?);
+        if walls.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no drawable shapes found in SVG",
+            ));
+        }
+        Ok(Level {
+            walls: fit_to_window(walls, 20.0),
+        })
+    }
+
+    /// Loads a level from JSON, or imports it if the file ends in `.svg`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Level> {
+        let is_svg = path
+            .as_ref()
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("svg"));
+        if is_svg {
+            Level::from_svg(path)
+        } else {
+            Level::load(path)
+        }
+    }
+}
+
+/// Scales and centers walls so they fit inside the window with `margin` pixels to spare.
+pub fn fit_to_window(walls: Vec<Boundary>, margin: f64) -> Vec<Boundary> {
+    let mut min = Vector::new(f64::MAX, f64::MAX);
+    let mut max = Vector::new(f64::MIN, f64::MIN);
+    for wall in &walls {
+        for p in [wall.a, wall.b].iter() {
+            min.x = min.x.min(p.x);
+            min.y = min.y.min(p.y);
+            max.x = max.x.max(p.x);
+            max.y = max.y.max(p.y);
+        }
+    }
+
+    let size = max - min;
+    let available = Vector::new(
+        WINDOW_WIDTH as f64 - 2.0 * margin,
+        WINDOW_HEIGHT as f64 - 2.0 * margin,
+    );
+    let scale = (available.x / size.x.max(1e-9)).min(available.y / size.y.max(1e-9));
+    let offset = Vector::new(
+        margin + (available.x - size.x * scale) / 2.0,
+        margin + (available.y - size.y * scale) / 2.0,
+    );
+
+    let transform = |p: Vector| (p - min) * scale + offset;
+    walls
+        .into_iter()
+        .map(|w| Boundary {
+            a: transform(w.a),
+            b: transform(w.b),
+            ..w
+        })
+        .collect()
+}
+
+/// Extracts line segments from the shapes in an SVG document. Cubic and quadratic
+/// curves are flattened, while arcs and `T` segments are replaced by a straight line
+/// to their end point. Transforms are ignored.
+pub fn parse_svg(svg: &str) -> Vec<Boundary> {
+    let mut walls = vec![];
+
+    for (name, tag) in tags(svg) {
+        match name {
+            "path" => {
+                if let Some(d) = attribute(tag, "d") {
+                    walls.append(&mut parse_path(d));
+                }
+            }
+            "line" => {
+                let num = |n| attribute(tag, n).and_then(|v| v.parse::<f64>().ok());
+                if let (Some(x1), Some(y1), Some(x2), Some(y2)) =
+                    (num("x1"), num("y1"), num("x2"), num("y2"))
+                {
+                    walls.push(Boundary::new(x1, y1, x2, y2));
+                }
+            }
+            "polyline" | "polygon" => {
+                if let Some(points) = attribute(tag, "points") {
+                    let numbers = numbers(points);
+                    let points: Vec<Vector> = numbers
+                        .chunks(2)
+                        .filter(|c| c.len() == 2)
+                        .map(|c| Vector::new(c[0], c[1]))
+                        .collect();
+                    connect(&points, name == "polygon", &mut walls);
+                }
+            }
+            "rect" => {
+                let num = |n| {
+                    attribute(tag, n)
+                        .and_then(|v| v.parse::<f64>().ok())
+                        .unwrap_or(0.0)
+                };
+                let (x, y, w, h) = (num("x"), num("y"), num("width"), num("height"));
+                if w > 0.0 && h > 0.0 {
+                    let points = [
+                        Vector::new(x, y),
+                        Vector::new(x + w, y),
+                        Vector::new(x + w, y + h),
+                        Vector::new(x, y + h),
+                    ];
+                    connect(&points, true, &mut walls);
+                }
+            }
+            _ => (),
+        }
+    }
+    walls
+}
+
+fn connect(points: &[Vector], closed: bool, walls: &mut Vec<Boundary>) {
+    for pair in points.windows(2) {
+        walls.push(Boundary::new(pair[0].x, pair[0].y, pair[1].x, pair[1].y));
+    }
+    if closed && points.len() > 2 {
+        let (last, first) = (points[points.len() - 1], points[0]);
+        walls.push(Boundary::new(last.x, last.y, first.x, first.y));
+    }
+}
+
+// Yields the name and attribute text of every start or empty-element tag.
+fn tags(svg: &str) -> Vec<(&str, &str)> {
+    let mut tags = vec![];
+    let mut rest = svg;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('/') || tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        tags.push((&tag[..name_end], &tag[name_end..]));
+    }
+    tags
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().last();
+        let after = rest[pos + name.len()..].trim_start();
+        rest = &rest[pos + name.len()..];
+
+        if before.is_none_or(|c| c.is_whitespace()) && after.starts_with('=') {
+            let value = after[1..].trim_start();
+            let quote = value.chars().next()?;
+            if quote == '"' || quote == '\'' {
+                let end = value[1..].find(quote)?;
+                return Some(&value[1..end + 1]);
+            }
+        }
+    }
+    None
+}
+
+fn numbers(text: &str) -> Vec<f64> {
+    let mut numbers = vec![];
+    let mut current = String::new();
+    let mut last = ' ';
+    for c in text.chars() {
+        // "1-2" and "0.5.5" are two numbers each.
+        let starts_new = c == '-' && !(last == 'e' || last == 'E') || c == '.' && current.contains('.');
+        if starts_new || !(c.is_ascii_digit() || c == '.' || c == '-' || c == 'e' || c == 'E') {
+            if let Ok(n) = current.parse() {
+                numbers.push(n);
+            }
+            current.clear();
+        }
+        if c.is_ascii_digit() || c == '.' || c == '-' || c == 'e' || c == 'E' {
+            current.push(c);
+        }
+        last = c;
+    }
+    if let Ok(n) = current.parse() {
+        numbers.push(n);
+    }
+    numbers
+}
+
+// Number of straight segments each Bézier curve is flattened into.
+const CURVE_SEGMENTS: usize = 12;
+
+fn line_to(from: Vector, to: Vector, walls: &mut Vec<Boundary>) {
+    if from != to {
+        walls.push(Boundary::new(from.x, from.y, to.x, to.y));
+    }
+}
+
+fn parse_path(d: &str) -> Vec<Boundary> {
+    let mut walls = vec![];
+
+    // Split the path data into (command, arguments) pairs.
+    let mut commands: Vec<(char, Vec<f64>)> = vec![];
+    let mut start = None;
+    for (i, c) in d.char_indices() {
+        if c.is_ascii_alphabetic() && c != 'e' && c != 'E' {
+            if let Some((s, command)) = start {
+                commands.push((command, numbers(&d[s..i])));
+            }
+            start = Some((i + 1, c));
+        }
+    }
+    if let Some((s, command)) = start {
+        commands.push((command, numbers(&d[s..])));
+    }
+
+    let mut pos = Vector::default();
+    let mut subpath_start = pos;
+    let mut last_control: Option<Vector> = None;
+
+    for (command, args) in commands {
+        let relative = command.is_ascii_lowercase();
+        let base = |pos: Vector| if relative { pos } else { Vector::default() };
+
+        match command.to_ascii_uppercase() {
+            'M' => {
+                for (i, c) in args.chunks(2).filter(|c| c.len() == 2).enumerate() {
+                    let p = base(pos) + Vector::new(c[0], c[1]);
+                    // Extra coordinate pairs after a move are implicit line-tos.
+                    if i == 0 {
+                        subpath_start = p;
+                    } else {
+                        line_to(pos, p, &mut walls);
+                    }
+                    pos = p;
+                }
+                last_control = None;
+            }
+            'L' | 'T' => {
+                for c in args.chunks(2).filter(|c| c.len() == 2) {
+                    let p = base(pos) + Vector::new(c[0], c[1]);
+                    line_to(pos, p, &mut walls);
+                    pos = p;
+                }
+                last_control = None;
+            }
+            'H' => {
+                for x in &args {
+                    let p = Vector::new(if relative { pos.x + x } else { *x }, pos.y);
+                    line_to(pos, p, &mut walls);
+                    pos = p;
+                }
+                last_control = None;
+            }
+            'V' => {
+                for y in &args {
+                    let p = Vector::new(pos.x, if relative { pos.y + y } else { *y });
+                    line_to(pos, p, &mut walls);
+                    pos = p;
+                }
+                last_control = None;
+            }
+            'C' | 'S' => {
+                let smooth = command.eq_ignore_ascii_case(&'S');
+                let stride = if smooth { 4 } else { 6 };
+                for c in args.chunks(stride).filter(|c| c.len() == stride) {
+                    let b = base(pos);
+                    let (c1, c2, end) = if smooth {
+                        let c1 = last_control.map_or(pos, |lc| pos * 2.0 - lc);
+                        (c1, b + Vector::new(c[0], c[1]), b + Vector::new(c[2], c[3]))
+                    } else {
+                        (
+                            b + Vector::new(c[0], c[1]),
+                            b + Vector::new(c[2], c[3]),
+                            b + Vector::new(c[4], c[5]),
+                        )
+                    };
+                    let mut prev = pos;
+                    for i in 1..=CURVE_SEGMENTS {
+                        let t = i as f64 / CURVE_SEGMENTS as f64;
+                        let u = 1.0 - t;
+                        let p = pos * (u * u * u)
+                            + c1 * (3.0 * u * u * t)
+                            + c2 * (3.0 * u * t * t)
+                            + end * (t * t * t);
+                        line_to(prev, p, &mut walls);
+                        prev = p;
+                    }
+                    last_control = Some(c2);
+                    pos = end;
+                }
+            }
+            'Q' => {
+                for c in args.chunks(4).filter(|c| c.len() == 4) {
+                    let b = base(pos);
+                    let (c1, end) = (b + Vector::new(c[0], c[1]), b + Vector::new(c[2], c[3]));
+                    let mut prev = pos;
+                    for i in 1..=CURVE_SEGMENTS {
+                        let t = i as f64 / CURVE_SEGMENTS as f64;
+                        let u = 1.0 - t;
+                        let p = pos * (u * u) + c1 * (2.0 * u * t) + end * (t * t);
+                        line_to(prev, p, &mut walls);
+                        prev = p;
+                    }
+                    last_control = None;
+                    pos = end;
+                }
+            }
+            'A' => {
+                for c in args.chunks(7).filter(|c| c.len() == 7) {
+                    let p = base(pos) + Vector::new(c[5], c[6]);
+                    line_to(pos, p, &mut walls);
+                    pos = p;
+                }
+                last_control = None;
+            }
+            'Z' => {
+                line_to(pos, subpath_start, &mut walls);
+                pos = subpath_start;
+                last_control = None;
+            }
+            _ => (),
+        }
+    }
+    walls
+}
@@ -3,6 +3,23 @@
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+pub mod boundary;
+pub mod editor;
+pub mod grid;
+pub mod level;
+pub mod light;
+pub mod optics;
+pub mod particle;
+pub mod player;
+pub mod ray;
+pub mod shape;
+pub mod vector;
+pub mod viewer;
+
+pub const WINDOW_WIDTH: u32 = 1920;
+pub const WINDOW_HEIGHT: u32 = 1080;
+pub const PI : f64 = 3.1415926535897932384626433832795028841971693993751058209749445923078164062862089986280348253421170679;
+
 /// Measures Frames Per Second (FPS).
 #[derive(Debug)]
 pub struct FPSCounter {
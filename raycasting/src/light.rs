@@ -0,0 +1,85 @@
+//! Exact 2D visibility polygons, used to light a scene with colored point lights.
+
+use crate::{grid::WallGrid, ray::Ray, vector::Vector};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+
+// Angle, in radians, that rays are nudged to either side of each wall endpoint
+// so they slip past corners and reach the walls behind them.
+const EPSILON: f64 = 1e-5;
+
+// How far rays that escape every wall are drawn.
+const FAR: f64 = 1e5;
+
+/// The region visible from `pos`, as points sorted by angle around it.
+///
+/// A ray is cast at every wall endpoint and just either side of it, so every
+/// corner of the lit region lies exactly on a wall corner or a shadow edge.
+pub fn visibility_polygon(pos: Vector, walls: &WallGrid) -> Vec<Vector> {
+    // Each ray remembers the endpoint it aims at, if any. Casting exactly at a
+    // corner can slip between the two walls meeting there, so the endpoint
+    // itself is used whenever nothing closer is in the way.
+    let mut rays = vec![];
+    for wall in walls.walls() {
+        for p in [wall.a, wall.b].iter() {
+            let angle = (*p - pos).heading();
+            rays.push((angle - EPSILON, None));
+            rays.push((angle, Some(*p)));
+            rays.push((angle + EPSILON, None));
+        }
+    }
+    rays.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    rays.into_iter()
+        .map(|(angle, corner)| {
+            let ray = Ray::new([pos.x, pos.y], angle);
+            let hit = walls.nearest_hit(&ray);
+            match (hit, corner) {
+                (Some(hit), Some(corner)) if hit.dist < pos.distance(&corner) => hit.point,
+                (_, Some(corner)) => corner,
+                (Some(hit), None) => hit.point,
+                (None, None) => pos + ray.dir * FAR,
+            }
+        })
+        .collect()
+}
+
+/// A colored point light. Overlapping lights add up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub pos: Vector,
+    pub color: [f32; 4],
+}
+impl Light {
+    pub fn new(x: f64, y: f64, color: [f32; 4]) -> Light {
+        Light {
+            pos: Vector::new(x, y),
+            color,
+        }
+    }
+
+    /// Fills the region this light reaches and returns the number of rays cast.
+    pub fn draw(&self, walls: &WallGrid, transform: Matrix2d, gl: &mut GlGraphics) -> usize {
+        use graphics::*;
+
+        let visible = visibility_polygon(self.pos, walls);
+        if visible.len() < 2 {
+            return visible.len();
+        }
+
+        // The polygon is star-shaped around the light, so a fan from its
+        // position (which is how polygons are triangulated) covers it exactly.
+        let mut points = vec![[self.pos.x, self.pos.y]];
+        points.extend(visible.iter().map(|p| [p.x, p.y]));
+        points.push([visible[0].x, visible[0].y]);
+
+        let additive = DrawState::new_alpha().blend(draw_state::Blend::Add);
+        Polygon::new(self.color).draw(&points, &additive, transform, gl);
+
+        let mut center = self.color;
+        center[3] = 1.0;
+        ellipse(center, [self.pos.x - 5.0, self.pos.y - 5.0, 10.0, 10.0], transform, gl);
+
+        visible.len()
+    }
+}
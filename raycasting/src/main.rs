@@ -1,218 +1,76 @@
 use glutin_window::GlutinWindow as Window;
-use graphics::color::WHITE;
 use opengl_graphics::{GlGraphics, OpenGL};
 use piston::event_loop::{EventSettings, Events};
-use piston::input::RenderEvent;
+use piston::input::{RenderEvent, UpdateEvent};
 use piston::window::WindowSettings;
-use piston::{AdvancedWindow, EventLoop, MouseCursorEvent, RenderArgs};
+use piston::{AdvancedWindow, EventLoop, MouseCursorEvent, MouseRelativeEvent};
 use rand::Rng;
-use Raycasting::FPSCounter;
+use std::collections::HashSet;
+use Raycasting::{
+    boundary::Boundary,
+    editor::Editor,
+    grid::WallGrid,
+    level::Level,
+    light::Light,
+    optics,
+    particle::Particle,
+    player::Player,
+    shape,
+    viewer::Viewer,
+    FPSCounter, PI, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
 
 const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
-const WINDOW_WIDTH: u32 = 1920;
-const WINDOW_HEIGHT: u32 = 1080;
-const PI : f64 = 3.1415926535897932384626433832795028841971693993751058209749445923078164062862089986280348253421170679;
-
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-struct Vector {
-    pub x: f64,
-    pub y: f64,
-}
-impl Vector {
-    // A function that normalizes this vector.
-    fn normalize(&mut self) {
-        let len = ((self.x * self.x) + (self.y * self.y)).sqrt();
-        self.x /= len;
-        self.y /= len;
-    }
 
-    // A function that finds the distance between this vector and another.
-    fn distance(&mut self, other: &Vector) -> f64 {
-        let x_diff = self.x - other.x;
-        let y_diff = self.y - other.y;
-        let diff_sq = (x_diff * x_diff) + (y_diff * y_diff);
-        let sqrt_diff = diff_sq.sqrt();
-        return sqrt_diff;
-    }
+// Number of columns (and rays) in the first-person view.
+const VIEW_COLUMNS: usize = 320;
+
+// Colors given to lights, in the order they are placed.
+const LIGHT_COLORS: [[f32; 4]; 6] = [
+    [1.0, 0.9, 0.7, 0.5],
+    [1.0, 0.2, 0.2, 0.5],
+    [0.2, 1.0, 0.3, 0.5],
+    [0.2, 0.4, 1.0, 0.5],
+    [1.0, 0.2, 1.0, 0.5],
+    [0.2, 1.0, 1.0, 0.5],
+];
+
+// Colors given to viewers, in the order they are placed.
+const VIEWER_COLORS: [[f32; 4]; 4] = [
+    [1.0, 0.8, 0.2, 1.0],
+    [0.3, 0.7, 1.0, 1.0],
+    [1.0, 0.4, 0.8, 1.0],
+    [0.4, 1.0, 0.5, 1.0],
+];
+
+const LEVEL_PATH: &str = "level.json";
+const SVG_PATH: &str = "level.svg";
+
+// What the window shows and what the mouse does, picked with Tab, E, L, O, K and V.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Rays,
+    FirstPerson,
+    Editing,
+    Lighting,
+    Optics,
+    Shapes,
+    Viewers,
 }
-
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-struct Boundary {
-    pub a: Vector,
-    pub b: Vector,
-}
-impl Boundary {
-    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
-        use graphics::*;
-        gl.draw(args.viewport(), |context, gl| {
-            line(
-                WHITE,
-                1.0,
-                [self.a.x, self.a.y, self.b.x, self.b.y],
-                context.transform,
-                gl,
-            )
-        });
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-struct Ray {
-    pub pos: Vector,
-    pub dir: Vector,
-}
-impl Ray {
-    pub fn new(pos: [f64; 2], angle: f64) -> Ray {
-        Ray {
-            pos: Vector {
-                x: pos[0],
-                y: pos[1],
-            },
-            dir: Vector {
-                x: angle.cos(),
-                y: angle.sin(),
-            },
-        }
-    }
-
-    pub fn lookAt(&mut self, x: f64, y: f64) {
-        self.dir.x = x - self.pos.x;
-        self.dir.y = y - self.pos.y;
-        self.dir.normalize();
-    }
-
-    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
-        use graphics::*;
-        gl.draw(args.viewport(), |context, gl| {
-            line(
-                WHITE,
-                1.0,
-                [
-                    self.pos.x,
-                    self.pos.y,
-                    self.pos.x + (self.dir.x * 10.0),
-                    self.pos.y + (self.dir.y * 10.0),
-                ],
-                context.transform,
-                gl,
-            );
-        });
-    }
-
-    pub fn cast(&self, wall: &Boundary) -> Option<Vector> {
-        let x1: f64 = wall.a.x;
-        let y1: f64 = wall.a.y;
-        let x2: f64 = wall.b.x;
-        let y2: f64 = wall.b.y;
-
-        let x3: f64 = self.pos.x;
-        let y3: f64 = self.pos.y;
-        let x4: f64 = self.pos.x + self.dir.x;
-        let y4: f64 = self.pos.y + self.dir.y;
-
-        let den = (x1 - x2) * (y3 - y4) - (y1 - y2) * (x3 - x4);
-
-        if den == 0.0 {
-            return None;
-        }
-
-        let num1 = (x1 - x3) * (y3 - y4) - (y1 - y3) * (x3 - x4);
-        let num2 = -1.0 * ((x1 - x2) * (y1 - y3) - (y1 - y2) * (x1 - x3));
-
-        let t = num1 / den;
-        let u = num2 / den;
-
-        if t > 0.0 && t < 1.0 && u > 0.0 {
-            Some(Vector {
-                x: x1 + t * (x2 - x1),
-                y: y1 + t * (y2 - y1),
-            })
+impl Mode {
+    // Switches to `other`, or back to casting rays if it's already there.
+    fn toggle(self, other: Mode) -> Mode {
+        if self == other {
+            Mode::Rays
         } else {
-            None
-        }
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-struct Particle {
-    pub pos: Vector,
-    pub rays: Vec<Ray>,
-}
-impl Particle {
-    pub fn new() -> Particle {
-        let pos: Vector = Vector {
-            x: (WINDOW_WIDTH as f64 / 2.0),
-            y: (WINDOW_HEIGHT as f64 / 2.0),
-        };
-
-        let mut rays = vec![];
-        for i_raw in 0..360 {
-            let i = i_raw as f64;
-            rays.push(Ray {
-                pos: pos,
-                dir: Vector {
-                    x: (i * (1.0 / 180.0) * PI).cos(),
-                    y: (i * (1.0 / 180.0) * PI).sin(),
-                },
-            });
-        }
-        Particle {
-            pos: pos,
-            rays: rays,
-        }
-    }
-
-    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
-        gl.draw(args.viewport(), |_context, gl| {
-            for ray in self.rays.clone() {
-                ray.show(gl, args);
-            }
-        });
-    }
-
-    pub fn look(&mut self, walls: Vec<Boundary>, step : usize, gl: &mut GlGraphics, args: &RenderArgs) -> usize {
-        use graphics::*;
-
-        let mut total_rays = 0;
-        for ray in self.rays.clone().iter().step_by(step) {
-            let mut record: (f64, Vector) = (f64::MAX, Vector { x: 0.0, y: 0.0 });
-
-            for wall in walls.clone() {
-                if let Some(pt) = ray.cast(&wall) {
-                    let dist = self.pos.distance(&pt);
-
-                    if dist < record.0 {
-                        record = (dist, pt);
-                    }
-                }
-            }
-
-            if record.0 != f64::MAX {
-                total_rays += 1;
-                gl.draw(args.viewport(), |context, gl| {
-                    line(
-                        [1.0, 1.0, 1.0, 0.7],
-                        0.5,
-                        [self.pos.x, self.pos.y, record.1.x, record.1.y],
-                        context.transform,
-                        gl,
-                    );
-                });
-            }
+            other
         }
-        total_rays
     }
 
-    pub fn update(&mut self, pos: [f64; 2]) {
-        self.pos.x = pos[0];
-        self.pos.y = pos[1];
-        let mut new_rays = vec![];
-        for ray in self.rays.clone() {
-            let mut new_ray = ray;
-            new_ray.pos = self.pos;
-            new_rays.push(new_ray);
-        }
-        self.rays = new_rays;
+    // Whether L, O, K and V switch away from this mode, which they do from every
+    // mode but the editor and the first-person view.
+    fn switchable(self) -> bool {
+        self != Mode::Editing && self != Mode::FirstPerson
     }
 }
 
@@ -236,127 +94,497 @@ fn main() {
 
     let mut rng = rand::thread_rng();
 
+    let (w, h) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
     let bounds = vec![
-        Boundary {
-            a: Vector { x: 0.0, y: 0.0 },
-            b: Vector {
-                x: WINDOW_WIDTH as f64,
-                y: 0.0,
-            },
-        },
-        Boundary {
-            a: Vector {
-                x: WINDOW_WIDTH as f64,
-                y: 0.0,
-            },
-            b: Vector {
-                x: WINDOW_WIDTH as f64,
-                y: WINDOW_HEIGHT as f64,
-            },
-        },
-        Boundary {
-            a: Vector {
-                x: WINDOW_WIDTH as f64,
-                y: WINDOW_HEIGHT as f64,
-            },
-            b: Vector {
-                x: 0.0,
-                y: WINDOW_HEIGHT as f64,
-            },
-        },
-        Boundary {
-            a: Vector {
-                x: 0.0,
-                y: WINDOW_HEIGHT as f64,
-            },
-            b: Vector { x: 0.0, y: 0.0 },
-        },
+        Boundary::new(0.0, 0.0, w, 0.0),
+        Boundary::new(w, 0.0, w, h),
+        Boundary::new(w, h, 0.0, h),
+        Boundary::new(0.0, h, 0.0, 0.0),
     ];
 
     let mut walls = vec![];
 
     for _ in 0..10 {
-        walls.push(Boundary {
-            a: Vector {
-                x: rng.gen_range(0..WINDOW_WIDTH) as f64,
-                y: rng.gen_range(0..WINDOW_HEIGHT) as f64,
-            },
-            b: Vector {
-                x: rng.gen_range(0..WINDOW_WIDTH) as f64,
-                y: rng.gen_range(0..WINDOW_HEIGHT) as f64,
-            },
-        });
+        walls.push(Boundary::new(
+            rng.gen_range(0..WINDOW_WIDTH) as f64,
+            rng.gen_range(0..WINDOW_HEIGHT) as f64,
+            rng.gen_range(0..WINDOW_WIDTH) as f64,
+            rng.gen_range(0..WINDOW_HEIGHT) as f64,
+        ));
     }
 
     for wall in bounds.clone() {
         walls.push(wall);
     }
 
+    // A level (JSON or SVG) can be passed on the command line instead of random walls.
+    if let Some(path) = std::env::args().nth(1) {
+        match Level::open(&path) {
+            Ok(level) => walls = level.walls,
+            Err(err) => println!("Could not open {}: {}", path, err),
+        }
+    }
+
     let mut step : usize = 5;
 
     let mut particle = Particle::new();
 
+    // Tab switches between casting the particle's rays and the split 2D map /
+    // first-person view, and the other modes' keys likewise switch to them and
+    // back.
+    let mut mode = Mode::Rays;
+    let mut player = Player::new(WINDOW_WIDTH as f64 / 2.0, WINDOW_HEIGHT as f64 / 2.0);
+    let mut keys_down: HashSet<piston::Key> = HashSet::new();
+    let mouse_sensitivity = 0.003;
+
+    // E toggles the wall editor, which goes back to whichever mode it was opened
+    // from.
+    let mut mode_before_editing = Mode::Rays;
+    let mut editor = Editor::new();
+
+    // Rays are cast through a uniform grid over the walls; B switches to testing
+    // every wall for comparison, M adds a thousand small walls to stress it.
+    // Whatever changes the walls marks the grid dirty, and it's rebuilt before
+    // it's next used.
+    let mut grid = WallGrid::new(&walls);
+    let mut grid_dirty = false;
+    let mut brute_force = false;
+
+    // L switches to lighting the walls with visibility polygons. The first light
+    // follows the mouse, clicking places more and C removes them again.
+    let mut lights = vec![Light::new(particle.pos.x, particle.pos.y, LIGHT_COLORS[0])];
+
+    // O traces the particle's rays through mirrors and glass, up to `max_bounces` deep.
+    let mut max_bounces: usize = 8;
+
+    // K casts the particle's rays against curved and polygonal shapes as well as walls.
+    let shapes = shape::scene();
+
+    // V shows viewers with limited fields of view. The selected viewer follows
+    // the mouse and turns with the arrow keys, clicking places another one.
+    let mut viewers = vec![Viewer::new(particle.pos.x, particle.pos.y, 0.0, VIEWER_COLORS[0])];
+    let mut selected_viewer = 0;
+
     while let Some(e) = events.next(&mut window) {
         use graphics::*;
 
+        if grid_dirty {
+            grid = WallGrid::new(&walls);
+            grid_dirty = false;
+        }
+
         if let Some(args) = e.render_args() {
             let mut total_rays = 0;
-            gl.draw(args.viewport(), |_context, gl| {
+            let mut viewer_summary = String::new();
+            gl.draw(args.viewport(), |context, gl| {
                 clear(BLACK, gl);
 
-                for wall in walls.clone() {
-                    wall.show(gl, &args);
+                match mode {
+                    Mode::Editing => editor.draw(&walls, context.transform, gl),
+                    Mode::FirstPerson => {
+                        let half_width = WINDOW_WIDTH as f64 / 2.0;
+                        let map = context
+                            .transform
+                            .trans(0.0, WINDOW_HEIGHT as f64 / 4.0)
+                            .scale(0.5, 0.5);
+                        for wall in &walls {
+                            wall.draw(map, gl);
+                        }
+                        player.draw(&grid, VIEW_COLUMNS / 4, map, gl);
+
+                        total_rays = player.render_view(
+                            &grid,
+                            VIEW_COLUMNS,
+                            [half_width, 0.0, half_width, WINDOW_HEIGHT as f64],
+                            context.transform,
+                            gl,
+                        );
+                    }
+                    Mode::Viewers => {
+                        for wall in &walls {
+                            wall.draw(context.transform, gl);
+                        }
+                        let visibilities: Vec<_> = viewers.iter().map(|v| v.look(&grid)).collect();
+                        for (i, viewer) in viewers.iter().enumerate() {
+                            total_rays += visibilities[i].hits.len();
+                            viewer.draw(&visibilities[i], &grid, context.transform, gl);
+
+                            // Ring viewers spotted by any other viewer.
+                            let spotted = viewers
+                                .iter()
+                                .enumerate()
+                                .any(|(j, other)| j != i && other.sees(viewer.pos, &grid));
+                            if spotted {
+                                let p = viewer.pos;
+                                Ellipse::new_border([1.0, 0.0, 0.0, 1.0], 1.5).draw(
+                                    [p.x - 12.0, p.y - 12.0, 24.0, 24.0],
+                                    &context.draw_state,
+                                    context.transform,
+                                    gl,
+                                );
+                            }
+                        }
+
+                        let viewer = &viewers[selected_viewer];
+                        let visibility = &visibilities[selected_viewer];
+                        let total_length: f64 = walls.iter().map(|w| w.length()).sum();
+                        let seen_length: f64 = visibility
+                            .walls
+                            .iter()
+                            .map(|seen| seen.fraction * walls[seen.wall].length())
+                            .sum();
+                        viewer_summary = format!(
+                            "Viewer {}/{}: FOV {:.0}, {:.0} Rays/rad, Sees {} Walls ({:.1}% of Wall Length)",
+                            selected_viewer + 1,
+                            viewers.len(),
+                            viewer.fov.to_degrees(),
+                            viewer.density,
+                            visibility.walls.len(),
+                            100.0 * seen_length / total_length.max(1e-9)
+                        );
+                    }
+                    Mode::Shapes => {
+                        for shape in &shapes {
+                            shape.draw([0.4, 0.8, 1.0, 1.0], context.transform, gl);
+                        }
+                        for wall in &walls {
+                            wall.draw(context.transform, gl);
+                        }
+                        for ray in particle.rays.iter().step_by(step) {
+                            let wall_hit = grid.nearest_hit(ray);
+                            let shape_hit = shape::nearest_hit(ray, &shapes);
+                            let (point, normal) = match (wall_hit, shape_hit) {
+                                (Some(w), Some((_, s))) if s.dist < w.dist => (s.point, Some(s.normal)),
+                                (Some(w), _) => (w.point, None),
+                                (None, Some((_, s))) => (s.point, Some(s.normal)),
+                                (None, None) => continue,
+                            };
+                            total_rays += 1;
+                            line(
+                                [1.0, 1.0, 1.0, 0.7],
+                                0.5,
+                                [particle.pos.x, particle.pos.y, point.x, point.y],
+                                context.transform,
+                                gl,
+                            );
+                            // Shape hits also show their surface normal.
+                            if let Some(n) = normal {
+                                let end = point + n * 10.0;
+                                line(
+                                    [1.0, 0.3, 0.3, 0.8],
+                                    0.5,
+                                    [point.x, point.y, end.x, end.y],
+                                    context.transform,
+                                    gl,
+                                );
+                            }
+                        }
+                    }
+                    Mode::Optics => {
+                        for ray in particle.rays.iter().step_by(step) {
+                            let beams = optics::trace(ray, &grid, max_bounces);
+                            total_rays += beams.len();
+                            optics::draw(&beams, [1.0, 0.9, 0.5, 0.8], context.transform, gl);
+                        }
+                        for wall in &walls {
+                            wall.draw(context.transform, gl);
+                        }
+                    }
+                    Mode::Lighting => {
+                        for light in &lights {
+                            total_rays += light.draw(&grid, context.transform, gl);
+                        }
+                        for wall in &walls {
+                            wall.draw(context.transform, gl);
+                        }
+                    }
+                    Mode::Rays => {
+                        for wall in &walls {
+                            wall.show(gl, &args);
+                        }
+                        total_rays = particle.look(&grid, step, brute_force, gl, &args);
+                    }
                 }
-                total_rays = particle.look(walls.clone(), step, gl, &args);
             });
 
-            window.set_title(format!(
-                "Raycasting Test | {:03} fps | {:04} Rays Drawn | {:02} Walls Drawn | Showing Every {:03} Ray",
-                fps_counter.tick(),
-                total_rays,
-                walls.len(),
-                step
-            ));
+            match mode {
+                Mode::Editing => window.set_title(format!(
+                    "Raycasting Editor | {:03} fps | {:02} Walls | Snap {} | {} | G: snap, T: material, Del: delete, F5: save, F9: load, I: import {}",
+                    fps_counter.tick(),
+                    walls.len(),
+                    editor.snap.map_or("off".to_string(), |s| format!("{}px", s)),
+                    editor.selected.map_or("No Selection".to_string(), |i| format!("{:?}", walls[i].material)),
+                    SVG_PATH
+                )),
+                Mode::Viewers => window.set_title(format!(
+                    "Raycasting Viewers | {:03} fps | {:04} Rays Drawn | {:02} Walls Drawn | {} | Click: add, N: next, Arrows: turn/FOV, , .: density",
+                    fps_counter.tick(),
+                    total_rays,
+                    walls.len(),
+                    viewer_summary
+                )),
+                Mode::Shapes => window.set_title(format!(
+                    "Raycasting Shapes | {:03} fps | {:04} Rays Drawn | {:02} Walls Drawn | {} Shapes | Showing Every {:03} Ray",
+                    fps_counter.tick(),
+                    total_rays,
+                    walls.len(),
+                    shapes.len(),
+                    step
+                )),
+                Mode::Optics => window.set_title(format!(
+                    "Raycasting Optics | {:03} fps | {:04} Beams Drawn | {:02} Walls Drawn | Showing Every {:03} Ray | Max Bounces {} | P: optics scene, [ ]: bounces",
+                    fps_counter.tick(),
+                    total_rays,
+                    walls.len(),
+                    step,
+                    max_bounces
+                )),
+                Mode::Lighting => window.set_title(format!(
+                    "Raycasting Lighting | {:03} fps | {:04} Rays Drawn | {:02} Walls Drawn | {} Lights | Click: add light, C: clear",
+                    fps_counter.tick(),
+                    total_rays,
+                    walls.len(),
+                    lights.len()
+                )),
+                Mode::FirstPerson => window.set_title(format!(
+                    "Raycasting Test | {:03} fps | {:04} Rays Drawn | {:02} Walls Drawn | First Person (FOV {:.0})",
+                    fps_counter.tick(),
+                    total_rays,
+                    walls.len(),
+                    player.fov.to_degrees()
+                )),
+                Mode::Rays => window.set_title(format!(
+                    "Raycasting Test | {:03} fps | {:04} Rays Drawn | {:02} Walls Drawn | Showing Every {:03} Ray | {}",
+                    fps_counter.tick(),
+                    total_rays,
+                    walls.len(),
+                    step,
+                    if brute_force { "Brute Force" } else { "Grid" }
+                )),
+            }
+        } else if let Some(args) = e.update_args() {
+            match mode {
+                Mode::FirstPerson => {
+                    let mut forward = 0.0;
+                    let mut strafe = 0.0;
+                    let mut turn = 0.0;
+                    if keys_down.contains(&piston::Key::W) {
+                        forward += 1.0;
+                    }
+                    if keys_down.contains(&piston::Key::S) {
+                        forward -= 1.0;
+                    }
+                    if keys_down.contains(&piston::Key::D) {
+                        strafe += 1.0;
+                    }
+                    if keys_down.contains(&piston::Key::A) {
+                        strafe -= 1.0;
+                    }
+                    if keys_down.contains(&piston::Key::Right) {
+                        turn += 1.0;
+                    }
+                    if keys_down.contains(&piston::Key::Left) {
+                        turn -= 1.0;
+                    }
+                    player.turn(turn * player.turn_speed * args.dt);
+                    player.walk(forward, strafe, args.dt, &grid);
+                }
+                Mode::Viewers => {
+                    let viewer = &mut viewers[selected_viewer];
+                    if keys_down.contains(&piston::Key::Right) {
+                        viewer.heading += PI * args.dt;
+                    }
+                    if keys_down.contains(&piston::Key::Left) {
+                        viewer.heading -= PI * args.dt;
+                    }
+                    if keys_down.contains(&piston::Key::Up) {
+                        viewer.fov = (viewer.fov + args.dt).min(2.0 * PI);
+                    }
+                    if keys_down.contains(&piston::Key::Down) {
+                        viewer.fov = (viewer.fov - args.dt).max(0.05);
+                    }
+                }
+                _ => (),
+            }
+        } else if let Some([dx, _]) = e.mouse_relative_args() {
+            if mode == Mode::FirstPerson {
+                player.turn(dx * mouse_sensitivity);
+            }
         } else if let Some([x, y]) = e.mouse_cursor_args() {
-            particle.update([x, y]);
+            if mode == Mode::Editing {
+                grid_dirty |= editor.mouse_move(&mut walls, [x, y]);
+            } else {
+                particle.update([x, y]);
+                lights[0].pos = particle.pos;
+                if mode == Mode::Viewers {
+                    viewers[selected_viewer].pos = particle.pos;
+                }
+            }
         } else if let piston::Event::Input(i, _) = e {
             match i {
-                piston::Input::Button(b) => match (b.state, b.button) {
-                    (piston::ButtonState::Release, piston::Button::Keyboard(k)) => match k {
-                        piston::Key::Space => {
-                            walls = vec![];
-                            for _ in 0..rng.gen_range(5..20) {
-                                walls.push(Boundary {
-                                    a: Vector {
-                                        x: rng.gen_range(0..WINDOW_WIDTH) as f64,
-                                        y: rng.gen_range(0..WINDOW_HEIGHT) as f64,
-                                    },
-                                    b: Vector {
-                                        x: rng.gen_range(0..WINDOW_WIDTH) as f64,
-                                        y: rng.gen_range(0..WINDOW_HEIGHT) as f64,
-                                    },
-                                });
-                            }
+                piston::Input::Button(b) => {
+                    if let piston::Button::Keyboard(k) = b.button {
+                        match b.state {
+                            piston::ButtonState::Press => keys_down.insert(k),
+                            piston::ButtonState::Release => keys_down.remove(&k),
+                        };
+                    }
 
-                            for wall in bounds.clone() {
-                                walls.push(wall);
-                            }
+                    match (b.state, b.button) {
+                        (piston::ButtonState::Press, piston::Button::Mouse(piston::MouseButton::Left)) if mode == Mode::Editing => {
+                            editor.mouse_press(&walls);
                         },
-                        piston::Key::A => {
-                            step += 1;
-                            if step >= 360 {
-                                step = 1;
-                            }
+                        (piston::ButtonState::Release, piston::Button::Mouse(piston::MouseButton::Left)) if mode == Mode::Editing => {
+                            grid_dirty |= editor.mouse_release(&mut walls);
                         },
-                        piston::Key::S => {
-                            step -= 1;
-                            if step <= 0 {
-                                step = 359;
+                        (piston::ButtonState::Release, piston::Button::Mouse(piston::MouseButton::Right)) if mode == Mode::Editing => {
+                            grid_dirty |= editor.delete(&mut walls, true);
+                        },
+                        (piston::ButtonState::Release, piston::Button::Mouse(piston::MouseButton::Left)) if mode == Mode::Viewers => {
+                            let color = VIEWER_COLORS[viewers.len() % VIEWER_COLORS.len()];
+                            let heading = viewers[selected_viewer].heading;
+                            viewers.push(Viewer::new(particle.pos.x, particle.pos.y, heading, color));
+                            selected_viewer = viewers.len() - 1;
+                        },
+                        (piston::ButtonState::Release, piston::Button::Mouse(piston::MouseButton::Left)) if mode == Mode::Lighting => {
+                            let color = LIGHT_COLORS[lights.len() % LIGHT_COLORS.len()];
+                            lights.push(Light::new(particle.pos.x, particle.pos.y, color));
+                        },
+                        (piston::ButtonState::Release, piston::Button::Keyboard(k)) => match k {
+                            piston::Key::Tab if mode != Mode::Editing => {
+                                mode = mode.toggle(Mode::FirstPerson);
+                                window.set_capture_cursor(mode == Mode::FirstPerson);
+                            },
+                            piston::Key::E => {
+                                if mode == Mode::Editing {
+                                    mode = mode_before_editing;
+                                } else {
+                                    // The editor needs the mouse, so it leaves the
+                                    // first-person view for good.
+                                    mode_before_editing = match mode {
+                                        Mode::FirstPerson => Mode::Rays,
+                                        other => other,
+                                    };
+                                    mode = Mode::Editing;
+                                    window.set_capture_cursor(false);
+                                }
+                            },
+                            piston::Key::L if mode.switchable() => mode = mode.toggle(Mode::Lighting),
+                            piston::Key::O if mode.switchable() => mode = mode.toggle(Mode::Optics),
+                            piston::Key::K if mode.switchable() => mode = mode.toggle(Mode::Shapes),
+                            piston::Key::V if mode.switchable() => mode = mode.toggle(Mode::Viewers),
+                            piston::Key::N if mode == Mode::Viewers => {
+                                selected_viewer = (selected_viewer + 1) % viewers.len();
+                            },
+                            piston::Key::Comma if mode == Mode::Viewers => {
+                                let viewer = &mut viewers[selected_viewer];
+                                viewer.density = (viewer.density / 2.0).max(2.0);
+                            },
+                            piston::Key::Period if mode == Mode::Viewers => {
+                                let viewer = &mut viewers[selected_viewer];
+                                viewer.density = (viewer.density * 2.0).min(2000.0);
+                            },
+                            piston::Key::P if mode == Mode::Optics => {
+                                walls = optics::scene();
+                                editor.deselect();
+                                grid_dirty = true;
+                            },
+                            piston::Key::LeftBracket if mode == Mode::Optics => {
+                                max_bounces = max_bounces.saturating_sub(1);
+                            },
+                            piston::Key::RightBracket if mode == Mode::Optics => {
+                                max_bounces += 1;
+                            },
+                            piston::Key::T if mode == Mode::Editing => {
+                                if let Some(i) = editor.selected {
+                                    walls[i].material = walls[i].material.next();
+                                    grid_dirty = true;
+                                }
+                            },
+                            piston::Key::C if mode == Mode::Lighting => {
+                                lights.truncate(1);
+                            },
+                            piston::Key::G if mode == Mode::Editing => {
+                                editor.toggle_snap();
+                            },
+                            piston::Key::Delete | piston::Key::Backspace if mode == Mode::Editing => {
+                                grid_dirty |= editor.delete(&mut walls, false);
+                            },
+                            piston::Key::F5 => match Level::new(walls.clone()).save(LEVEL_PATH) {
+                                Ok(()) => println!("Saved {} walls to {}", walls.len(), LEVEL_PATH),
+                                Err(err) => println!("Could not save {}: {}", LEVEL_PATH, err),
+                            },
+                            piston::Key::F9 => match Level::load(LEVEL_PATH) {
+                                Ok(level) => {
+                                    walls = level.walls;
+                                    editor.deselect();
+                                    grid_dirty = true;
+                                },
+                                Err(err) => println!("Could not load {}: {}", LEVEL_PATH, err),
+                            },
+                            piston::Key::I if mode == Mode::Editing => match Level::from_svg(SVG_PATH) {
+                                Ok(level) => {
+                                    walls = level.walls;
+                                    editor.deselect();
+                                    grid_dirty = true;
+                                },
+                                Err(err) => println!("Could not import {}: {}", SVG_PATH, err),
+                            },
+                            piston::Key::Equals if mode == Mode::FirstPerson => {
+                                player.fov = (player.fov + 5f64.to_radians()).min(170f64.to_radians());
+                            },
+                            piston::Key::Minus if mode == Mode::FirstPerson => {
+                                player.fov = (player.fov - 5f64.to_radians()).max(10f64.to_radians());
+                            },
+                            piston::Key::Space => {
+                                editor.deselect();
+                                walls = vec![];
+                                for _ in 0..rng.gen_range(5..20) {
+                                    walls.push(Boundary::new(
+                                        rng.gen_range(0..WINDOW_WIDTH) as f64,
+                                        rng.gen_range(0..WINDOW_HEIGHT) as f64,
+                                        rng.gen_range(0..WINDOW_WIDTH) as f64,
+                                        rng.gen_range(0..WINDOW_HEIGHT) as f64,
+                                    ));
+                                }
+
+                                for wall in bounds.clone() {
+                                    walls.push(wall);
+                                }
+                                grid_dirty = true;
+                            },
+                            piston::Key::B if mode != Mode::Editing => {
+                                brute_force = !brute_force;
+                            },
+                            piston::Key::M if mode != Mode::Editing => {
+                                for _ in 0..1000 {
+                                    let x = rng.gen_range(0.0..WINDOW_WIDTH as f64);
+                                    let y = rng.gen_range(0.0..WINDOW_HEIGHT as f64);
+                                    walls.push(Boundary::new(
+                                        x,
+                                        y,
+                                        x + rng.gen_range(-20.0..20.0),
+                                        y + rng.gen_range(-20.0..20.0),
+                                    ));
+                                }
+                                grid_dirty = true;
+                            },
+                            piston::Key::A if mode != Mode::FirstPerson => {
+                                step += 1;
+                                if step >= 360 {
+                                    step = 1;
+                                }
+                            },
+                            piston::Key::S if mode != Mode::FirstPerson => {
+                                step -= 1;
+                                if step <= 0 {
+                                    step = 359;
+                                }
                             }
-                        }
+                            _ => (),
+                        },
                         _ => (),
-                    },
-                    _ => (),
+                    }
                 },
                 _ => (),
             }
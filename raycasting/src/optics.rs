@@ -0,0 +1,189 @@
+//! Rays that bounce off mirrors and split at glass walls.
+
+use crate::{
+    boundary::{Boundary, Material},
+    grid::WallGrid,
+    ray::Ray,
+    vector::Vector,
+    PI, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+
+// How far rays that escape every wall are drawn.
+const FAR: f64 = 1e4;
+
+// Distance a new ray starts away from the wall it leaves, so it can't hit it again.
+const NUDGE: f64 = 1e-3;
+
+// Rays dimmer than this aren't followed any further.
+const MIN_INTENSITY: f64 = 0.01;
+
+/// One straight piece of a traced ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Beam {
+    pub a: Vector,
+    pub b: Vector,
+    /// Fraction of the original ray's energy carried by this piece.
+    pub intensity: f64,
+}
+
+/// Follows `ray` through reflections and refractions, at most `max_bounces` deep,
+/// and returns every piece of it.
+///
+/// Glass walls split rays by the Fresnel equations. A ray outside any glass that
+/// hits a glass wall enters it, and a ray inside leaves it, so glass walls should
+/// form closed shapes.
+pub fn trace(ray: &Ray, walls: &WallGrid, max_bounces: usize) -> Vec<Beam> {
+    let mut beams = vec![];
+    let mut dir = ray.dir;
+    dir.normalize();
+    trace_into(ray.pos, dir, 1.0, 1.0, max_bounces, walls, &mut beams);
+    beams
+}
+
+fn trace_into(
+    pos: Vector,
+    dir: Vector,
+    intensity: f64,
+    medium: f64,
+    bounces: usize,
+    walls: &WallGrid,
+    beams: &mut Vec<Beam>,
+) {
+    let hit = match walls.nearest_hit(&Ray { pos, dir }) {
+        Some(hit) => hit,
+        None => {
+            beams.push(Beam {
+                a: pos,
+                b: pos + dir * FAR,
+                intensity,
+            });
+            return;
+        }
+    };
+    beams.push(Beam {
+        a: pos,
+        b: hit.point,
+        intensity,
+    });
+
+    let wall = &walls.walls()[hit.wall];
+    if bounces == 0 || wall.material == Material::Absorbing {
+        return;
+    }
+
+    // The normal facing back towards the incoming ray.
+    let mut normal = wall.normal();
+    let mut cos_i = -dir.dot(&normal);
+    if cos_i < 0.0 {
+        normal = -normal;
+        cos_i = -cos_i;
+    }
+    let reflected = dir + normal * (2.0 * cos_i);
+
+    let mut follow = |dir: Vector, intensity: f64, medium: f64| {
+        if intensity >= MIN_INTENSITY {
+            let start = hit.point + dir * NUDGE;
+            trace_into(start, dir, intensity, medium, bounces - 1, walls, beams);
+        }
+    };
+
+    match wall.material {
+        Material::Absorbing => (),
+        Material::Mirror => follow(reflected, intensity, medium),
+        Material::Glass { ior } => {
+            let (n1, n2) = if medium == 1.0 { (1.0, ior) } else { (medium, 1.0) };
+            let eta = n1 / n2;
+            let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+            if sin2_t > 1.0 {
+                // Total internal reflection.
+                follow(reflected, intensity, medium);
+                return;
+            }
+
+            let cos_t = (1.0 - sin2_t).sqrt();
+            let refracted = dir * eta + normal * (eta * cos_i - cos_t);
+
+            // Fresnel reflectance for unpolarized light.
+            let rs = ((n1 * cos_i - n2 * cos_t) / (n1 * cos_i + n2 * cos_t)).powi(2);
+            let rp = ((n1 * cos_t - n2 * cos_i) / (n1 * cos_t + n2 * cos_i)).powi(2);
+            let reflectance = (rs + rp) / 2.0;
+
+            follow(reflected, intensity * reflectance, medium);
+            follow(refracted, intensity * (1.0 - reflectance), n2);
+        }
+    }
+}
+
+/// Draws beams in `color`, fading each by its intensity.
+pub fn draw(beams: &[Beam], color: [f32; 4], transform: Matrix2d, gl: &mut GlGraphics) {
+    use graphics::*;
+
+    for beam in beams {
+        let mut c = color;
+        c[3] *= beam.intensity as f32;
+        line(c, 0.5, [beam.a.x, beam.a.y, beam.b.x, beam.b.y], transform, gl);
+    }
+}
+
+/// A scene showing off the materials: a pair of mirrors, a glass prism, a glass
+/// slab and a bent optical fiber, inside absorbing window bounds.
+pub fn scene() -> Vec<Boundary> {
+    let (w, h) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+    let glass = Material::Glass { ior: 1.5 };
+    let mut walls = vec![
+        Boundary::new(0.0, 0.0, w, 0.0),
+        Boundary::new(w, 0.0, w, h),
+        Boundary::new(w, h, 0.0, h),
+        Boundary::new(0.0, h, 0.0, 0.0),
+        Boundary::new(w * 0.1, h * 0.15, w * 0.25, h * 0.05).with_material(Material::Mirror),
+        Boundary::new(w * 0.05, h * 0.6, w * 0.15, h * 0.9).with_material(Material::Mirror),
+    ];
+
+    let mut closed = |points: &[Vector], material: Material| {
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            walls.push(Boundary::new(a.x, a.y, b.x, b.y).with_material(material));
+        }
+    };
+
+    // An equilateral prism.
+    let (cx, cy, r) = (w * 0.5, h * 0.3, h * 0.15);
+    let prism: Vec<Vector> = (0..3)
+        .map(|i| {
+            let angle = -PI / 2.0 + i as f64 * 2.0 * PI / 3.0;
+            Vector::new(cx + r * angle.cos(), cy + r * angle.sin())
+        })
+        .collect();
+    closed(&prism, glass);
+
+    // A thick slab.
+    closed(
+        &[
+            Vector::new(w * 0.3, h * 0.6),
+            Vector::new(w * 0.45, h * 0.6),
+            Vector::new(w * 0.45, h * 0.68),
+            Vector::new(w * 0.3, h * 0.68),
+        ],
+        glass,
+    );
+
+    // A fiber bending through a quarter circle: rays entering its end close to
+    // its axis stay trapped by total internal reflection.
+    let (fx, fy, radius, width) = (w * 0.65, h * 0.55, h * 0.3, 24.0);
+    let steps = 24;
+    let mut outer = vec![Vector::new(fx - 250.0, fy - radius - width / 2.0)];
+    let mut inner = vec![Vector::new(fx - 250.0, fy - radius + width / 2.0)];
+    for i in 0..=steps {
+        let angle = -PI / 2.0 + i as f64 * (PI / 2.0) / steps as f64;
+        let dir = Vector::new(angle.cos(), angle.sin());
+        outer.push(Vector::new(fx, fy) + dir * (radius + width / 2.0));
+        inner.push(Vector::new(fx, fy) + dir * (radius - width / 2.0));
+    }
+    let mut fiber = outer;
+    fiber.extend(inner.into_iter().rev());
+    closed(&fiber, Material::Glass { ior: 1.6 });
+
+    walls
+}
@@ -0,0 +1,82 @@
+use crate::{grid::WallGrid, ray::Ray, vector::Vector, PI, WINDOW_HEIGHT, WINDOW_WIDTH};
+use opengl_graphics::GlGraphics;
+use piston::RenderArgs;
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Particle {
+    pub pos: Vector,
+    pub rays: Vec<Ray>,
+}
+impl Particle {
+    pub fn new() -> Particle {
+        let pos: Vector = Vector {
+            x: (WINDOW_WIDTH as f64 / 2.0),
+            y: (WINDOW_HEIGHT as f64 / 2.0),
+        };
+
+        let mut rays = vec![];
+        for i_raw in 0..360 {
+            let i = i_raw as f64;
+            rays.push(Ray {
+                pos: pos,
+                dir: Vector {
+                    x: (i * (1.0 / 180.0) * PI).cos(),
+                    y: (i * (1.0 / 180.0) * PI).sin(),
+                },
+            });
+        }
+        Particle {
+            pos: pos,
+            rays: rays,
+        }
+    }
+
+    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+        gl.draw(args.viewport(), |_context, gl| {
+            for ray in self.rays.clone() {
+                ray.show(gl, args);
+            }
+        });
+    }
+
+    /// Draws every `step`th ray up to the wall it hits and returns how many hit one.
+    /// With `brute_force` set every wall is tested instead of walking `walls`' grid.
+    pub fn look(&self, walls: &WallGrid, step: usize, brute_force: bool, gl: &mut GlGraphics, args: &RenderArgs) -> usize {
+        use graphics::*;
+
+        let mut total_rays = 0;
+        gl.draw(args.viewport(), |context, gl| {
+            for ray in self.rays.iter().step_by(step) {
+                let hit = if brute_force {
+                    ray.nearest_hit(walls.walls())
+                } else {
+                    walls.nearest_hit(ray)
+                };
+
+                if let Some(hit) = hit {
+                    total_rays += 1;
+                    line(
+                        [1.0, 1.0, 1.0, 0.7],
+                        0.5,
+                        [self.pos.x, self.pos.y, hit.point.x, hit.point.y],
+                        context.transform,
+                        gl,
+                    );
+                }
+            }
+        });
+        total_rays
+    }
+
+    pub fn update(&mut self, pos: [f64; 2]) {
+        self.pos.x = pos[0];
+        self.pos.y = pos[1];
+        let mut new_rays = vec![];
+        for ray in self.rays.clone() {
+            let mut new_ray = ray;
+            new_ray.pos = self.pos;
+            new_rays.push(new_ray);
+        }
+        self.rays = new_rays;
+    }
+}
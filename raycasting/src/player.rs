@@ -0,0 +1,156 @@
+use crate::{grid::WallGrid, ray::Ray, vector::Vector, PI};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+
+/// A first-person viewer that walks around the map and renders it as pseudo-3D columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Player {
+    pub pos: Vector,
+    /// Facing direction in radians.
+    pub heading: f64,
+    /// Horizontal field of view in radians.
+    pub fov: f64,
+    /// Movement speed in pixels per second.
+    pub speed: f64,
+    /// Keyboard turn speed in radians per second.
+    pub turn_speed: f64,
+    /// Collision radius in pixels.
+    pub radius: f64,
+}
+impl Player {
+    pub fn new(x: f64, y: f64) -> Player {
+        Player {
+            pos: Vector { x, y },
+            heading: 0.0,
+            fov: PI / 3.0,
+            speed: 300.0,
+            turn_speed: PI,
+            radius: 8.0,
+        }
+    }
+
+    pub fn forward(&self) -> Vector {
+        Vector::from_angle(self.heading)
+    }
+
+    pub fn turn(&mut self, angle: f64) {
+        self.heading = (self.heading + angle) % (2.0 * PI);
+    }
+
+    /// Moves along the heading (`forward`) and sideways (`strafe`), both in -1..1,
+    /// stopping short of any wall in the way.
+    pub fn walk(&mut self, forward: f64, strafe: f64, dt: f64, walls: &WallGrid) {
+        let right = Vector::from_angle(self.heading + PI / 2.0);
+        let mut dir = self.forward() * forward + right * strafe;
+        if dir.mag() == 0.0 {
+            return;
+        }
+        dir.normalize();
+
+        let step = self.speed * dt;
+        let ray = Ray { pos: self.pos, dir };
+        if let Some(hit) = walls.nearest_hit(&ray) {
+            if hit.dist < step + self.radius {
+                return;
+            }
+        }
+        self.pos += dir * step;
+    }
+
+    /// One ray per screen column, spaced evenly across the projection plane.
+    pub fn rays(&self, columns: usize) -> Vec<Ray> {
+        let half = (self.fov / 2.0).tan();
+        (0..columns)
+            .map(|i| {
+                let offset = ((i as f64 + 0.5) / columns as f64) * 2.0 - 1.0;
+                Ray {
+                    pos: self.pos,
+                    dir: Vector::from_angle(self.heading + (offset * half).atan()),
+                }
+            })
+            .collect()
+    }
+
+    /// Draws the player and its view cone on the 2D map.
+    pub fn draw(&self, walls: &WallGrid, columns: usize, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        for ray in self.rays(columns) {
+            if let Some(hit) = walls.nearest_hit(&ray) {
+                line(
+                    [1.0, 1.0, 0.0, 0.3],
+                    0.5,
+                    [self.pos.x, self.pos.y, hit.point.x, hit.point.y],
+                    transform,
+                    gl,
+                );
+            }
+        }
+        ellipse(
+            [1.0, 1.0, 0.0, 1.0],
+            [
+                self.pos.x - self.radius,
+                self.pos.y - self.radius,
+                self.radius * 2.0,
+                self.radius * 2.0,
+            ],
+            transform,
+            gl,
+        );
+    }
+
+    /// Renders the first-person view into `rect` (`[x, y, width, height]`), one
+    /// column per ray, and returns the number of rays that hit a wall.
+    pub fn render_view(
+        &self,
+        walls: &WallGrid,
+        columns: usize,
+        rect: [f64; 4],
+        transform: Matrix2d,
+        gl: &mut GlGraphics,
+    ) -> usize {
+        use graphics::*;
+
+        let [x, y, width, height] = rect;
+        let column_width = width / columns as f64;
+        // Distance from the eye to the projection plane, in screen pixels.
+        let plane = (width / 2.0) / (self.fov / 2.0).tan();
+        let max_dist = 2000.0;
+        let wall_height = 64.0;
+
+        // Ceiling and floor.
+        rectangle([0.1, 0.1, 0.15, 1.0], [x, y, width, height / 2.0], transform, gl);
+        rectangle(
+            [0.2, 0.2, 0.2, 1.0],
+            [x, y + height / 2.0, width, height / 2.0],
+            transform,
+            gl,
+        );
+
+        let mut hits = 0;
+        for (i, ray) in self.rays(columns).iter().enumerate() {
+            if let Some(hit) = walls.nearest_hit(ray) {
+                hits += 1;
+
+                // Use the distance along the heading rather than to the eye to
+                // avoid the fisheye effect.
+                let corrected = hit.dist * (ray.dir.heading() - self.heading).cos();
+                let h = (wall_height * plane / corrected.max(0.0001)).min(height);
+                let shade = (1.0 - corrected / max_dist).clamp(0.05, 1.0) as f32;
+
+                rectangle(
+                    [shade, shade, shade, 1.0],
+                    [
+                        x + i as f64 * column_width,
+                        y + (height - h) / 2.0,
+                        column_width.ceil(),
+                        h,
+                    ],
+                    transform,
+                    gl,
+                );
+            }
+        }
+        hits
+    }
+}
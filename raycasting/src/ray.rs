@@ -0,0 +1,110 @@
+use crate::{boundary::Boundary, vector::Vector};
+use graphics::color::WHITE;
+use opengl_graphics::GlGraphics;
+use piston::RenderArgs;
+
+/// Where a ray hit a wall.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    /// Distance from the ray's origin.
+    pub dist: f64,
+    pub point: Vector,
+    /// Index of the wall that was hit.
+    pub wall: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Ray {
+    pub pos: Vector,
+    pub dir: Vector,
+}
+impl Ray {
+    pub fn new(pos: [f64; 2], angle: f64) -> Ray {
+        Ray {
+            pos: Vector {
+                x: pos[0],
+                y: pos[1],
+            },
+            dir: Vector {
+                x: angle.cos(),
+                y: angle.sin(),
+            },
+        }
+    }
+
+    pub fn lookAt(&mut self, x: f64, y: f64) {
+        self.dir.x = x - self.pos.x;
+        self.dir.y = y - self.pos.y;
+        self.dir.normalize();
+    }
+
+    pub fn show(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+        use graphics::*;
+        gl.draw(args.viewport(), |context, gl| {
+            line(
+                WHITE,
+                1.0,
+                [
+                    self.pos.x,
+                    self.pos.y,
+                    self.pos.x + (self.dir.x * 10.0),
+                    self.pos.y + (self.dir.y * 10.0),
+                ],
+                context.transform,
+                gl,
+            );
+        });
+    }
+
+    pub fn cast(&self, wall: &Boundary) -> Option<Vector> {
+        let x1: f64 = wall.a.x;
+        let y1: f64 = wall.a.y;
+        let x2: f64 = wall.b.x;
+        let y2: f64 = wall.b.y;
+
+        let x3: f64 = self.pos.x;
+        let y3: f64 = self.pos.y;
+        let x4: f64 = self.pos.x + self.dir.x;
+        let y4: f64 = self.pos.y + self.dir.y;
+
+        let den = (x1 - x2) * (y3 - y4) - (y1 - y2) * (x3 - x4);
+
+        if den == 0.0 {
+            return None;
+        }
+
+        let num1 = (x1 - x3) * (y3 - y4) - (y1 - y3) * (x3 - x4);
+        let num2 = -1.0 * ((x1 - x2) * (y1 - y3) - (y1 - y2) * (x1 - x3));
+
+        let t = num1 / den;
+        let u = num2 / den;
+
+        if t > 0.0 && t < 1.0 && u > 0.0 {
+            Some(Vector {
+                x: x1 + t * (x2 - x1),
+                y: y1 + t * (y2 - y1),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Casts against every wall and returns the closest hit. This is the brute force
+    /// version of `WallGrid::nearest_hit`.
+    pub fn nearest_hit(&self, walls: &[Boundary]) -> Option<Hit> {
+        let mut record: Option<Hit> = None;
+        for (i, wall) in walls.iter().enumerate() {
+            if let Some(pt) = self.cast(wall) {
+                let dist = self.pos.distance(&pt);
+                if record.is_none_or(|r| dist < r.dist) {
+                    record = Some(Hit {
+                        dist,
+                        point: pt,
+                        wall: i,
+                    });
+                }
+            }
+        }
+        record
+    }
+}
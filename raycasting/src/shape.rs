@@ -0,0 +1,737 @@
+//! Obstacles other than straight walls: circles, arcs, boxes, convex polygons and
+//! Bézier curves, all of which rays can be cast against.
+
+use crate::{boundary::Boundary, ray::Ray, vector::Vector, PI, WINDOW_HEIGHT, WINDOW_WIDTH};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+
+// Hits closer than this to the ray's origin are ignored, so a ray leaving a
+// surface doesn't hit it again straight away.
+const EPSILON: f64 = 1e-9;
+
+// Number of straight pieces curves are drawn with.
+const OUTLINE_SEGMENTS: usize = 64;
+
+/// Where a ray hit a shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeHit {
+    /// Distance from the ray's origin.
+    pub dist: f64,
+    pub point: Vector,
+    /// Unit normal of the surface at `point`. For closed shapes it points outwards.
+    pub normal: Vector,
+}
+
+pub trait Shape {
+    /// The first point where `ray` meets this shape, if any. Rays that only touch
+    /// the shape (tangent to a circle, or grazing a corner) count as hits.
+    fn intersect(&self, ray: &Ray) -> Option<ShapeHit>;
+
+    /// Points along the shape's outline, used to draw it.
+    fn outline(&self) -> Vec<Vector>;
+
+    fn draw(&self, color: [f32; 4], transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        for pair in self.outline().windows(2) {
+            line(color, 1.0, [pair[0].x, pair[0].y, pair[1].x, pair[1].y], transform, gl);
+        }
+    }
+}
+
+/// The closest hit among `shapes`, with the index of the shape that was hit.
+pub fn nearest_hit(ray: &Ray, shapes: &[Box<dyn Shape>]) -> Option<(usize, ShapeHit)> {
+    let mut record: Option<(usize, ShapeHit)> = None;
+    for (i, shape) in shapes.iter().enumerate() {
+        if let Some(hit) = shape.intersect(ray) {
+            if record.is_none_or(|(_, r)| hit.dist < r.dist) {
+                record = Some((i, hit));
+            }
+        }
+    }
+    record
+}
+
+// The ray's origin and unit direction, or `None` for a ray without a direction.
+fn unit(ray: &Ray) -> Option<(Vector, Vector)> {
+    let len = ray.dir.mag();
+    if len == 0.0 {
+        None
+    } else {
+        Some((ray.pos, ray.dir * (1.0 / len)))
+    }
+}
+
+fn unit_normal(v: Vector) -> Vector {
+    let mut n = Vector::new(-v.y, v.x);
+    n.normalize();
+    n
+}
+
+// Distance along a ray from `pos` in unit direction `dir` to where it crosses the
+// segment from `a` to `b`. The interval is closed, so a ray through either end
+// still hits, while rays parallel to the segment never do.
+fn segment_dist(pos: Vector, dir: Vector, a: Vector, b: Vector) -> Option<f64> {
+    let edge = b - a;
+    let denom = dir.cross(&edge);
+    if denom == 0.0 {
+        return None;
+    }
+    let ap = a - pos;
+    let dist = ap.cross(&edge) / denom;
+    let u = ap.cross(&dir) / denom;
+    if dist > EPSILON && (0.0..=1.0).contains(&u) {
+        Some(dist)
+    } else {
+        None
+    }
+}
+
+impl Shape for Boundary {
+    fn intersect(&self, ray: &Ray) -> Option<ShapeHit> {
+        let (pos, dir) = unit(ray)?;
+        let dist = segment_dist(pos, dir, self.a, self.b)?;
+        Some(ShapeHit {
+            dist,
+            point: pos + dir * dist,
+            normal: self.normal(),
+        })
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        vec![self.a, self.b]
+    }
+}
+
+// Distances along a ray from `pos` in unit direction `dir` at which it crosses a circle.
+fn circle_roots(center: Vector, radius: f64, pos: Vector, dir: Vector) -> Option<(f64, f64)> {
+    let oc = pos - center;
+    let b = oc.dot(&dir);
+    let c = oc.dot(&oc) - radius * radius;
+    let disc = b * b - c;
+    // Tolerate rounding so a tangent ray still touches the circle.
+    if disc < -EPSILON * radius * radius {
+        return None;
+    }
+    let s = disc.max(0.0).sqrt();
+    Some((-b - s, -b + s))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub center: Vector,
+    pub radius: f64,
+}
+impl Circle {
+    pub fn new(x: f64, y: f64, radius: f64) -> Circle {
+        Circle {
+            center: Vector::new(x, y),
+            radius,
+        }
+    }
+}
+impl Shape for Circle {
+    fn intersect(&self, ray: &Ray) -> Option<ShapeHit> {
+        let (pos, dir) = unit(ray)?;
+        let (t0, t1) = circle_roots(self.center, self.radius, pos, dir)?;
+        // From inside the circle the first root is behind the ray.
+        let dist = if t0 > EPSILON { t0 } else { t1 };
+        if dist <= EPSILON {
+            return None;
+        }
+        let point = pos + dir * dist;
+        Some(ShapeHit {
+            dist,
+            point,
+            normal: (point - self.center) * (1.0 / self.radius),
+        })
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        Arc::new(self.center.x, self.center.y, self.radius, 0.0, 2.0 * PI).outline()
+    }
+}
+
+/// Part of a circle, from the angle `start` through `sweep` radians. Positive
+/// sweeps run clockwise on screen, since y points down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arc {
+    pub center: Vector,
+    pub radius: f64,
+    pub start: f64,
+    pub sweep: f64,
+}
+impl Arc {
+    pub fn new(x: f64, y: f64, radius: f64, start: f64, sweep: f64) -> Arc {
+        Arc {
+            center: Vector::new(x, y),
+            radius,
+            start,
+            sweep,
+        }
+    }
+
+    fn contains_angle(&self, angle: f64) -> bool {
+        if self.sweep.abs() >= 2.0 * PI {
+            return true;
+        }
+        let (from, sweep) = if self.sweep >= 0.0 {
+            (self.start, self.sweep)
+        } else {
+            (self.start + self.sweep, -self.sweep)
+        };
+        let offset = (angle - from).rem_euclid(2.0 * PI);
+        // The small tolerance keeps the arc's own endpoints on it.
+        offset <= sweep + EPSILON || offset >= 2.0 * PI - EPSILON
+    }
+}
+impl Shape for Arc {
+    fn intersect(&self, ray: &Ray) -> Option<ShapeHit> {
+        let (pos, dir) = unit(ray)?;
+        let (t0, t1) = circle_roots(self.center, self.radius, pos, dir)?;
+        for &dist in [t0, t1].iter() {
+            if dist <= EPSILON {
+                continue;
+            }
+            let point = pos + dir * dist;
+            if self.contains_angle((point - self.center).heading()) {
+                return Some(ShapeHit {
+                    dist,
+                    point,
+                    normal: (point - self.center) * (1.0 / self.radius),
+                });
+            }
+        }
+        None
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        let segments = ((OUTLINE_SEGMENTS as f64 * self.sweep.abs() / (2.0 * PI)).ceil() as usize).max(1);
+        (0..=segments)
+            .map(|i| {
+                let angle = self.start + self.sweep * i as f64 / segments as f64;
+                self.center + Vector::from_angle(angle) * self.radius
+            })
+            .collect()
+    }
+}
+
+/// An axis-aligned box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
+}
+impl Aabb {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Aabb {
+        Aabb {
+            min: Vector::new(x.min(x + width), y.min(y + height)),
+            max: Vector::new(x.max(x + width), y.max(y + height)),
+        }
+    }
+}
+impl Shape for Aabb {
+    fn intersect(&self, ray: &Ray) -> Option<ShapeHit> {
+        let (pos, dir) = unit(ray)?;
+
+        // Slab test, remembering which axis each bound came from.
+        let mut enter = (f64::MIN, Vector::default());
+        let mut leave = (f64::MAX, Vector::default());
+        for &(o, d, lo, hi, axis) in [
+            (pos.x, dir.x, self.min.x, self.max.x, Vector::new(1.0, 0.0)),
+            (pos.y, dir.y, self.min.y, self.max.y, Vector::new(0.0, 1.0)),
+        ]
+        .iter()
+        {
+            if d == 0.0 {
+                if o < lo || o > hi {
+                    return None;
+                }
+                continue;
+            }
+            let (t_lo, t_hi) = ((lo - o) / d, (hi - o) / d);
+            let (near, far, near_normal) = if t_lo < t_hi {
+                (t_lo, t_hi, -axis)
+            } else {
+                (t_hi, t_lo, axis)
+            };
+            if near > enter.0 {
+                enter = (near, near_normal);
+            }
+            if far < leave.0 {
+                leave = (far, -near_normal);
+            }
+        }
+
+        // Equal bounds mean the ray grazes a corner, which still counts.
+        if enter.0 > leave.0 {
+            return None;
+        }
+        let (dist, normal) = if enter.0 > EPSILON { enter } else { leave };
+        if dist <= EPSILON || dist == f64::MAX {
+            return None;
+        }
+        Some(ShapeHit {
+            dist,
+            point: pos + dir * dist,
+            normal,
+        })
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        vec![
+            self.min,
+            Vector::new(self.max.x, self.min.y),
+            self.max,
+            Vector::new(self.min.x, self.max.y),
+            self.min,
+        ]
+    }
+}
+
+/// A convex polygon, given by its corners in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexPolygon {
+    pub points: Vec<Vector>,
+}
+impl ConvexPolygon {
+    pub fn new(points: Vec<Vector>) -> ConvexPolygon {
+        ConvexPolygon { points }
+    }
+
+    /// A regular polygon with `sides` corners on a circle.
+    pub fn regular(x: f64, y: f64, radius: f64, sides: usize, rotation: f64) -> ConvexPolygon {
+        ConvexPolygon {
+            points: (0..sides)
+                .map(|i| {
+                    let angle = rotation + i as f64 * 2.0 * PI / sides as f64;
+                    Vector::new(x, y) + Vector::from_angle(angle) * radius
+                })
+                .collect(),
+        }
+    }
+
+    fn centroid(&self) -> Vector {
+        let sum = self.points.iter().fold(Vector::default(), |sum, p| sum + *p);
+        sum * (1.0 / self.points.len() as f64)
+    }
+}
+impl Shape for ConvexPolygon {
+    fn intersect(&self, ray: &Ray) -> Option<ShapeHit> {
+        let (pos, dir) = unit(ray)?;
+        if self.points.len() < 2 {
+            return None;
+        }
+        let centroid = self.centroid();
+
+        let mut record: Option<ShapeHit> = None;
+        for i in 0..self.points.len() {
+            let (a, b) = (self.points[i], self.points[(i + 1) % self.points.len()]);
+            // A ray through a corner hits either edge, and one sliding along an
+            // edge hits the edges at its ends.
+            let dist = match segment_dist(pos, dir, a, b) {
+                Some(dist) => dist,
+                None => continue,
+            };
+            if record.is_none_or(|r| dist < r.dist) {
+                let mut normal = unit_normal(b - a);
+                if normal.dot(&(a - centroid)) < 0.0 {
+                    normal = -normal;
+                }
+                record = Some(ShapeHit {
+                    dist,
+                    point: pos + dir * dist,
+                    normal,
+                });
+            }
+        }
+        record
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        let mut points = self.points.clone();
+        if let Some(first) = self.points.first() {
+            points.push(*first);
+        }
+        points
+    }
+}
+
+/// A cubic Bézier curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bezier {
+    pub p0: Vector,
+    pub p1: Vector,
+    pub p2: Vector,
+    pub p3: Vector,
+}
+impl Bezier {
+    pub fn new(p0: Vector, p1: Vector, p2: Vector, p3: Vector) -> Bezier {
+        Bezier { p0, p1, p2, p3 }
+    }
+
+    pub fn point(&self, t: f64) -> Vector {
+        let u = 1.0 - t;
+        self.p0 * (u * u * u) + self.p1 * (3.0 * u * u * t) + self.p2 * (3.0 * u * t * t) + self.p3 * (t * t * t)
+    }
+
+    pub fn tangent(&self, t: f64) -> Vector {
+        let u = 1.0 - t;
+        (self.p1 - self.p0) * (3.0 * u * u) + (self.p2 - self.p1) * (6.0 * u * t) + (self.p3 - self.p2) * (3.0 * t * t)
+    }
+}
+impl Shape for Bezier {
+    fn intersect(&self, ray: &Ray) -> Option<ShapeHit> {
+        let (pos, dir) = unit(ray)?;
+
+        // Signed distance of each control point from the ray's line. The curve
+        // crosses the line where the same combination of these is zero.
+        let side = |p: Vector| dir.cross(&(p - pos));
+        let (d0, d1, d2, d3) = (side(self.p0), side(self.p1), side(self.p2), side(self.p3));
+        let a = -d0 + 3.0 * d1 - 3.0 * d2 + d3;
+        let b = 3.0 * d0 - 6.0 * d1 + 3.0 * d2;
+        let c = -3.0 * d0 + 3.0 * d1;
+        let d = d0;
+
+        let mut record: Option<ShapeHit> = None;
+        for t in solve_cubic(a, b, c, d) {
+            if !(-EPSILON..=1.0 + EPSILON).contains(&t) {
+                continue;
+            }
+            let t = t.clamp(0.0, 1.0);
+            let point = self.point(t);
+            let dist = (point - pos).dot(&dir);
+            if dist > EPSILON && record.is_none_or(|r| dist < r.dist) {
+                let tangent = self.tangent(t);
+                let normal = if tangent.mag() == 0.0 {
+                    -dir
+                } else {
+                    unit_normal(tangent)
+                };
+                record = Some(ShapeHit {
+                    dist,
+                    point,
+                    normal,
+                });
+            }
+        }
+        record
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        (0..=OUTLINE_SEGMENTS)
+            .map(|i| self.point(i as f64 / OUTLINE_SEGMENTS as f64))
+            .collect()
+    }
+}
+
+/// Real roots of `a t³ + b t² + c t + d = 0`, falling back to lower degrees when
+/// the leading coefficients vanish. Double roots (tangent rays) are returned once.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    let scale = a.abs().max(b.abs()).max(c.abs()).max(d.abs());
+    if scale == 0.0 {
+        return vec![];
+    }
+    let (a, b, c, d) = (a / scale, b / scale, c / scale, d / scale);
+
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            if c.abs() < 1e-12 {
+                return vec![];
+            }
+            return vec![-d / c];
+        }
+        let disc = c * c - 4.0 * b * d;
+        if disc < -1e-12 {
+            return vec![];
+        }
+        if disc <= 1e-12 {
+            return vec![-c / (2.0 * b)];
+        }
+        let s = disc.sqrt();
+        return vec![(-c - s) / (2.0 * b), (-c + s) / (2.0 * b)];
+    }
+
+    // Depressed cubic x³ + p x + q = 0 with t = x - b / 3a.
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let disc = q * q / 4.0 + p * p * p / 27.0;
+
+    let roots = if disc.abs() < 1e-12 {
+        if p.abs() < 1e-12 {
+            vec![0.0]
+        } else {
+            // A single and a double root.
+            vec![3.0 * q / p, -3.0 * q / (2.0 * p)]
+        }
+    } else if disc > 0.0 {
+        let s = disc.sqrt();
+        vec![(-q / 2.0 + s).cbrt() + (-q / 2.0 - s).cbrt()]
+    } else {
+        // Three real roots, found with the trigonometric method.
+        let r = (-p / 3.0).sqrt();
+        let phi = (3.0 * q / (2.0 * p * r)).clamp(-1.0, 1.0).acos();
+        (0..3)
+            .map(|k| 2.0 * r * ((phi - 2.0 * PI * k as f64) / 3.0).cos())
+            .collect()
+    };
+    roots.into_iter().map(|x| x - shift).collect()
+}
+
+/// One of each kind of shape, spread over the window.
+pub fn scene() -> Vec<Box<dyn Shape>> {
+    let (w, h) = (WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+    vec![
+        Box::new(Circle::new(w * 0.2, h * 0.3, 90.0)),
+        Box::new(Arc::new(w * 0.8, h * 0.3, 120.0, PI * 0.25, PI * 1.25)),
+        Box::new(Aabb::new(w * 0.15, h * 0.65, 220.0, 140.0)),
+        Box::new(ConvexPolygon::regular(w * 0.5, h * 0.75, 110.0, 6, 0.0)),
+        Box::new(Bezier::new(
+            Vector::new(w * 0.35, h * 0.15),
+            Vector::new(w * 0.45, h * 0.55),
+            Vector::new(w * 0.6, h * -0.1),
+            Vector::new(w * 0.65, h * 0.35),
+        )),
+        Box::new(Circle::new(w * 0.8, h * 0.75, 60.0)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(x: f64, y: f64, dx: f64, dy: f64) -> Ray {
+        Ray {
+            pos: Vector::new(x, y),
+            dir: Vector::new(dx, dy),
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    fn assert_hit(hit: Option<ShapeHit>, dist: f64, point: Vector, normal: Vector) {
+        let hit = hit.expect("ray missed");
+        assert_close(hit.dist, dist);
+        assert_close(hit.point.x, point.x);
+        assert_close(hit.point.y, point.y);
+        assert_close(hit.normal.x, normal.x);
+        assert_close(hit.normal.y, normal.y);
+    }
+
+    fn sorted(mut roots: Vec<f64>) -> Vec<f64> {
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        roots
+    }
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        let roots = sorted(roots);
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (r, e) in roots.iter().zip(expected) {
+            assert!((r - e).abs() < 1e-6, "{:?} != {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn boundary_hits_its_endpoints() {
+        let wall = Boundary::new(0.0, 0.0, 10.0, 0.0);
+        let normal = wall.normal();
+        assert_hit(wall.intersect(&ray(10.0, -5.0, 0.0, 1.0)), 5.0, Vector::new(10.0, 0.0), normal);
+        assert_hit(wall.intersect(&ray(0.0, 5.0, 0.0, -2.0)), 5.0, Vector::new(0.0, 0.0), normal);
+        assert_hit(wall.intersect(&ray(-5.0, -5.0, 1.0, 1.0)), 50f64.sqrt(), Vector::new(0.0, 0.0), normal);
+        assert!(wall.intersect(&ray(10.5, -5.0, 0.0, 1.0)).is_none());
+        assert!(wall.intersect(&ray(-5.0, 0.0, 1.0, 0.0)).is_none());
+        assert!(wall.intersect(&ray(5.0, 0.0, 0.0, 1.0)).is_none());
+        assert!(wall.intersect(&ray(5.0, -5.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn circle_tangent_ray() {
+        let circle = Circle::new(0.0, 0.0, 10.0);
+        assert_hit(
+            circle.intersect(&ray(-20.0, 10.0, 1.0, 0.0)),
+            20.0,
+            Vector::new(0.0, 10.0),
+            Vector::new(0.0, 1.0),
+        );
+        assert!(circle.intersect(&ray(-20.0, 10.0 + 1e-6, 1.0, 0.0)).is_none());
+        assert!(circle.intersect(&ray(20.0, 10.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn circle_ray_from_inside() {
+        let circle = Circle::new(5.0, 5.0, 10.0);
+        assert_hit(
+            circle.intersect(&ray(5.0, 5.0, 3.0, 0.0)),
+            10.0,
+            Vector::new(15.0, 5.0),
+            Vector::new(1.0, 0.0),
+        );
+        assert_hit(
+            circle.intersect(&ray(5.0, 10.0, 0.0, -1.0)),
+            15.0,
+            Vector::new(5.0, -5.0),
+            Vector::new(0.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn arc_hits_its_endpoints() {
+        let arc = Arc::new(0.0, 0.0, 10.0, 0.0, PI / 2.0);
+        assert_hit(
+            arc.intersect(&ray(20.0, 0.0, -1.0, 0.0)),
+            10.0,
+            Vector::new(10.0, 0.0),
+            Vector::new(1.0, 0.0),
+        );
+        assert_hit(
+            arc.intersect(&ray(0.0, 20.0, 0.0, -1.0)),
+            10.0,
+            Vector::new(0.0, 10.0),
+            Vector::new(0.0, 1.0),
+        );
+
+        // The same endpoints with the arc swept the other way.
+        let arc = Arc::new(0.0, 0.0, 10.0, PI / 2.0, -PI / 2.0);
+        assert!(arc.intersect(&ray(20.0, 0.0, -1.0, 0.0)).is_some());
+        assert!(arc.intersect(&ray(0.0, 20.0, 0.0, -1.0)).is_some());
+    }
+
+    #[test]
+    fn arc_ignores_hits_outside_its_sweep() {
+        let arc = Arc::new(0.0, 0.0, 10.0, 0.0, PI / 2.0);
+        // Both crossings of the circle are above the arc.
+        assert!(arc.intersect(&ray(-20.0, -5.0, 1.0, 0.0)).is_none());
+        // The first crossing is off the arc, so the ray hits the far side instead.
+        assert_hit(
+            arc.intersect(&ray(-20.0, 0.0, 1.0, 0.0)),
+            30.0,
+            Vector::new(10.0, 0.0),
+            Vector::new(1.0, 0.0),
+        );
+        // Just past the end of the sweep.
+        let past = Vector::from_angle(PI / 2.0 + 0.01) * 10.0;
+        assert!(arc.intersect(&ray(past.x, 20.0, 0.0, -1.0)).is_none());
+    }
+
+    #[test]
+    fn aabb_corner_graze() {
+        let aabb = Aabb::new(0.0, 0.0, 10.0, 10.0);
+        let hit = aabb.intersect(&ray(-5.0, 5.0, 1.0, 1.0)).expect("ray missed");
+        assert_close(hit.dist, 50f64.sqrt());
+        assert_close(hit.point.x, 0.0);
+        assert_close(hit.point.y, 10.0);
+        assert!(aabb.intersect(&ray(-5.0, 5.0 + 1e-6, 1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn aabb_ray_along_an_edge() {
+        let aabb = Aabb::new(0.0, 0.0, 10.0, 10.0);
+        assert_hit(
+            aabb.intersect(&ray(-5.0, 0.0, 1.0, 0.0)),
+            5.0,
+            Vector::new(0.0, 0.0),
+            Vector::new(-1.0, 0.0),
+        );
+        assert_hit(
+            aabb.intersect(&ray(10.0, 20.0, 0.0, -1.0)),
+            10.0,
+            Vector::new(10.0, 10.0),
+            Vector::new(0.0, 1.0),
+        );
+        assert!(aabb.intersect(&ray(-5.0, -1e-6, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn aabb_ray_from_inside() {
+        let aabb = Aabb::new(0.0, 0.0, 10.0, 10.0);
+        assert_hit(
+            aabb.intersect(&ray(5.0, 5.0, 1.0, 0.0)),
+            5.0,
+            Vector::new(10.0, 5.0),
+            Vector::new(1.0, 0.0),
+        );
+        assert_hit(
+            aabb.intersect(&ray(5.0, 8.0, 0.0, -1.0)),
+            8.0,
+            Vector::new(5.0, 0.0),
+            Vector::new(0.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn convex_polygon_ray_through_a_vertex() {
+        let square = ConvexPolygon::new(vec![
+            Vector::new(0.0, 0.0),
+            Vector::new(10.0, 0.0),
+            Vector::new(10.0, 10.0),
+            Vector::new(0.0, 10.0),
+        ]);
+        let hit = square.intersect(&ray(-5.0, -5.0, 1.0, 1.0)).expect("ray missed");
+        assert_close(hit.dist, 50f64.sqrt());
+        assert_close(hit.point.x, 0.0);
+        assert_close(hit.point.y, 0.0);
+
+        // Grazing a vertex without going in.
+        let hit = square.intersect(&ray(15.0, -5.0, -1.0, 1.0)).expect("ray missed");
+        assert_close(hit.point.x, 10.0);
+        assert_close(hit.point.y, 0.0);
+
+        // Straight at the tip of a diamond, where the two edges meet.
+        let diamond = ConvexPolygon::regular(0.0, 0.0, 10.0, 4, 0.0);
+        let hit = diamond.intersect(&ray(20.0, 0.0, -1.0, 0.0)).expect("ray missed");
+        assert_close(hit.dist, 10.0);
+        assert_close(hit.point.x, 10.0);
+        assert_close(hit.point.y, 0.0);
+        assert!(hit.normal.x > 0.0);
+    }
+
+    #[test]
+    fn bezier_tangent_ray() {
+        // y = -60 t (1 - t), so the curve peaks at (10, -15) halfway along.
+        let curve = Bezier::new(
+            Vector::new(0.0, 0.0),
+            Vector::new(0.0, -20.0),
+            Vector::new(20.0, -20.0),
+            Vector::new(20.0, 0.0),
+        );
+        let hit = curve.intersect(&ray(-10.0, -15.0, 1.0, 0.0)).expect("ray missed");
+        assert_close(hit.dist, 20.0);
+        assert_close(hit.point.x, 10.0);
+        assert_close(hit.point.y, -15.0);
+        assert_close(hit.normal.x, 0.0);
+        assert_close(hit.normal.y.abs(), 1.0);
+
+        assert!(curve.intersect(&ray(-10.0, -15.1, 1.0, 0.0)).is_none());
+        assert!(curve.intersect(&ray(-10.0, -14.9, 1.0, 0.0)).is_some());
+    }
+
+    #[test]
+    fn solve_cubic_degenerate_cases() {
+        // Quadratic, including a double root.
+        assert_roots(solve_cubic(0.0, 1.0, -3.0, 2.0), &[1.0, 2.0]);
+        assert_roots(solve_cubic(0.0, 1.0, -2.0, 1.0), &[1.0]);
+        assert_roots(solve_cubic(0.0, 1.0, 0.0, 1.0), &[]);
+        // Linear.
+        assert_roots(solve_cubic(0.0, 0.0, 2.0, -4.0), &[2.0]);
+        // Constant.
+        assert_roots(solve_cubic(0.0, 0.0, 0.0, 5.0), &[]);
+        assert_roots(solve_cubic(0.0, 0.0, 0.0, 0.0), &[]);
+        // Leading coefficients that vanish next to the others.
+        assert_roots(solve_cubic(1e-15, 1.0, -3.0, 2.0), &[1.0, 2.0]);
+    }
+
+    #[test]
+    fn solve_cubic_full_cubics() {
+        assert_roots(solve_cubic(1.0, -6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+        // (t - 1)² (t + 2), with a double root at 1.
+        assert_roots(solve_cubic(1.0, 0.0, -3.0, 2.0), &[-2.0, 1.0]);
+        // (t - 1)³, a triple root.
+        assert_roots(solve_cubic(1.0, -3.0, 3.0, -1.0), &[1.0]);
+        // t³ + t + 1 has a single real root.
+        assert_roots(solve_cubic(2.0, 0.0, 2.0, 2.0), &[-0.6823278038280193]);
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub struct Vector {
+    pub x: f64,
+    pub y: f64,
+}
+impl Vector {
+    pub fn new(x: f64, y: f64) -> Vector {
+        Vector { x, y }
+    }
+
+    pub fn from_angle(angle: f64) -> Vector {
+        Vector {
+            x: angle.cos(),
+            y: angle.sin(),
+        }
+    }
+
+    // A function that normalizes this vector.
+    pub fn normalize(&mut self) {
+        let len = ((self.x * self.x) + (self.y * self.y)).sqrt();
+        self.x /= len;
+        self.y /= len;
+    }
+
+    // A function that finds the distance between this vector and another.
+    pub fn distance(&self, other: &Vector) -> f64 {
+        let x_diff = self.x - other.x;
+        let y_diff = self.y - other.y;
+        let diff_sq = (x_diff * x_diff) + (y_diff * y_diff);
+        let sqrt_diff = diff_sq.sqrt();
+        return sqrt_diff;
+    }
+
+    pub fn mag(&self) -> f64 {
+        ((self.x * self.x) + (self.y * self.y)).sqrt()
+    }
+
+    pub fn dot(&self, other: &Vector) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    // The z component of the 3D cross product of this vector and another.
+    pub fn cross(&self, other: &Vector) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    // The angle of this vector in radians.
+    pub fn heading(&self) -> f64 {
+        self.y.atan2(self.x)
+    }
+}
+impl std::ops::Add<Vector> for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+impl std::ops::Sub<Vector> for Vector {
+    type Output = Vector;
+
+    fn sub(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+impl std::ops::AddAssign for Vector {
+    fn add_assign(&mut self, other: Vector) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+impl std::ops::SubAssign for Vector {
+    fn sub_assign(&mut self, other: Vector) {
+        self.x -= other.x;
+        self.y -= other.y;
+    }
+}
+impl std::ops::Mul<f64> for Vector {
+    type Output = Vector;
+
+    fn mul(self, other: f64) -> Vector {
+        Vector {
+            x: self.x * other,
+            y: self.y * other,
+        }
+    }
+}
+impl std::ops::MulAssign<f64> for Vector {
+    fn mul_assign(&mut self, rhs: f64) {
+        self.x *= rhs;
+        self.y *= rhs;
+    }
+}
+impl std::ops::Neg for Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Vector {
+        Vector {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Viewers that look in one direction through a limited field of view, and what
+//! they can see.
+
+use crate::{
+    grid::WallGrid,
+    ray::{Hit, Ray},
+    vector::Vector,
+    PI,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::GlGraphics;
+
+/// How much of one wall a viewer sees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallVisibility {
+    /// Index of the wall.
+    pub wall: usize,
+    /// Seen stretches of the wall, as sorted, non-overlapping ranges of the
+    /// position along it (0 at `a`, 1 at `b`).
+    pub seen: Vec<(f64, f64)>,
+    /// Total fraction of the wall's length that is seen.
+    pub fraction: f64,
+}
+
+/// Everything one viewer saw in a single look.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Visibility {
+    /// What each ray hit, from the left edge of the view to the right.
+    pub hits: Vec<Option<Hit>>,
+    /// Every wall hit by at least one ray, in order of index.
+    pub walls: Vec<WallVisibility>,
+}
+impl Visibility {
+    pub fn sees_wall(&self, wall: usize) -> bool {
+        self.walls.iter().any(|w| w.wall == wall)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewer {
+    pub pos: Vector,
+    /// Direction the viewer faces, in radians.
+    pub heading: f64,
+    /// Angle of the view cone, in radians.
+    pub fov: f64,
+    /// Rays cast per radian of the field of view.
+    pub density: f64,
+    pub color: [f32; 4],
+}
+impl Viewer {
+    pub fn new(x: f64, y: f64, heading: f64, color: [f32; 4]) -> Viewer {
+        Viewer {
+            pos: Vector::new(x, y),
+            heading,
+            fov: PI / 2.0,
+            density: 60.0,
+            color,
+        }
+    }
+
+    /// The rays spanning the view cone, from its left edge to its right.
+    pub fn rays(&self) -> Vec<Ray> {
+        let count = ((self.fov * self.density).ceil() as usize).max(1);
+        (0..=count)
+            .map(|i| {
+                let angle = self.heading - self.fov / 2.0 + self.fov * i as f64 / count as f64;
+                Ray::new([self.pos.x, self.pos.y], angle)
+            })
+            .collect()
+    }
+
+    /// Whether `angle` lies inside the view cone.
+    pub fn in_fov(&self, angle: f64) -> bool {
+        let offset = (angle - self.heading + PI).rem_euclid(2.0 * PI) - PI;
+        offset.abs() <= self.fov / 2.0
+    }
+
+    /// Whether a point is inside the view cone and not hidden behind a wall.
+    pub fn sees(&self, point: Vector, walls: &WallGrid) -> bool {
+        let to_point = point - self.pos;
+        if !self.in_fov(to_point.heading()) {
+            return false;
+        }
+        let ray = Ray {
+            pos: self.pos,
+            dir: to_point,
+        };
+        walls
+            .nearest_hit(&ray)
+            .is_none_or(|hit| hit.dist >= to_point.mag())
+    }
+
+    /// Casts every ray and works out which parts of which walls are seen.
+    ///
+    /// Neighbouring rays that land on the same wall are taken to see all of it in
+    /// between, so the seen fractions get more accurate with higher density.
+    pub fn look(&self, walls: &WallGrid) -> Visibility {
+        let hits: Vec<Option<Hit>> = self.rays().iter().map(|r| walls.nearest_hit(r)).collect();
+
+        let position = |hit: &Hit| {
+            let wall = &walls.walls()[hit.wall];
+            let ab = wall.b - wall.a;
+            let len_sq = ab.dot(&ab);
+            if len_sq == 0.0 {
+                0.0
+            } else {
+                ((hit.point - wall.a).dot(&ab) / len_sq).clamp(0.0, 1.0)
+            }
+        };
+
+        let mut ranges: Vec<(usize, f64, f64)> = vec![];
+        for (i, hit) in hits.iter().enumerate() {
+            let hit = match hit {
+                Some(hit) => hit,
+                None => continue,
+            };
+            let u = position(hit);
+            match i.checked_sub(1).and_then(|j| hits[j]) {
+                Some(prev) if prev.wall == hit.wall => {
+                    let v = position(&prev);
+                    ranges.push((hit.wall, u.min(v), u.max(v)));
+                }
+                _ => ranges.push((hit.wall, u, u)),
+            }
+        }
+        ranges.sort_by(|a, b| (a.0, a.1).partial_cmp(&(b.0, b.1)).unwrap());
+
+        let mut seen_walls: Vec<WallVisibility> = vec![];
+        for (wall, start, end) in ranges {
+            match seen_walls.last_mut() {
+                Some(last) if last.wall == wall => {
+                    let merged = last.seen.last_mut().unwrap();
+                    if start <= merged.1 {
+                        merged.1 = merged.1.max(end);
+                    } else {
+                        last.seen.push((start, end));
+                    }
+                }
+                _ => seen_walls.push(WallVisibility {
+                    wall,
+                    seen: vec![(start, end)],
+                    fraction: 0.0,
+                }),
+            }
+        }
+        for wall in &mut seen_walls {
+            wall.fraction = wall.seen.iter().map(|(start, end)| end - start).sum();
+        }
+
+        Visibility {
+            hits,
+            walls: seen_walls,
+        }
+    }
+
+    /// Draws the view cone, the seen parts of walls and the viewer itself.
+    pub fn draw(&self, visibility: &Visibility, walls: &WallGrid, transform: Matrix2d, gl: &mut GlGraphics) {
+        use graphics::*;
+
+        let mut ray_color = self.color;
+        ray_color[3] *= 0.25;
+        for hit in visibility.hits.iter().flatten() {
+            line(ray_color, 0.5, [self.pos.x, self.pos.y, hit.point.x, hit.point.y], transform, gl);
+        }
+
+        for seen in &visibility.walls {
+            let wall = &walls.walls()[seen.wall];
+            let ab = wall.b - wall.a;
+            for (start, end) in &seen.seen {
+                let (p, q) = (wall.a + ab * *start, wall.a + ab * *end);
+                line(self.color, 2.0, [p.x, p.y, q.x, q.y], transform, gl);
+            }
+        }
+
+        let mut body = self.color;
+        body[3] = 1.0;
+        ellipse(body, [self.pos.x - 6.0, self.pos.y - 6.0, 12.0, 12.0], transform, gl);
+        let nose = self.pos + Vector::from_angle(self.heading) * 14.0;
+        line(body, 1.5, [self.pos.x, self.pos.y, nose.x, nose.y], transform, gl);
+    }
+}